@@ -0,0 +1,218 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::emissivity::KELVIN;
+use crate::thermal_frame::ThermalFrame;
+
+// coefficients of the two-band transmission model used by FLIR cameras
+const ATMOSPHERE_X: f32 = 1.9;
+const ATMOSPHERE_ALPHA_1: f32 = 0.006569;
+const ATMOSPHERE_ALPHA_2: f32 = 0.01262;
+const ATMOSPHERE_BETA_1: f32 = -0.002276;
+const ATMOSPHERE_BETA_2: f32 = -0.00667;
+
+/// Air between the camera and the object, which absorbs part of the radiation of the object and emits some
+/// itself. At distance 0.0 nothing is corrected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Atmosphere {
+    /// Distance (m) from the camera to the object
+    pub distance: f32,
+    /// Temperature (°C) of the air
+    pub air_temp: f32,
+    /// Relative humidity (%) of the air
+    pub relative_humidity: f32,
+}
+
+impl Atmosphere {
+    pub fn new(distance: f32, air_temp: f32, relative_humidity: f32) -> Self {
+        Atmosphere {
+            distance: distance.max(0.0),
+            air_temp,
+            relative_humidity: relative_humidity.clamp(0.0, 100.0),
+        }
+    }
+
+    /// No air in between, temperatures are not changed.
+    pub fn none() -> Self {
+        Atmosphere::new(0.0, 20.0, 50.0)
+    }
+
+    pub fn with_distance(mut self, distance: f32) -> Self {
+        self.distance = distance.max(0.0);
+        self
+    }
+
+    pub fn with_air_temp(mut self, air_temp: f32) -> Self {
+        self.air_temp = air_temp;
+        self
+    }
+
+    pub fn with_relative_humidity(mut self, relative_humidity: f32) -> Self {
+        self.relative_humidity = relative_humidity.clamp(0.0, 100.0);
+        self
+    }
+
+    /// Fraction (0.0-1.0) of the radiation of the object that reaches the camera.
+    pub fn transmission(&self) -> f32 {
+        let t = self.air_temp;
+        // water vapour content estimated from the humidity and the saturation pressure at the air temperature
+        let h2o = self.relative_humidity / 100.0
+            * (1.5587 + 0.06939 * t - 0.00027816 * t * t + 0.00000068455 * t * t * t).exp();
+        let sqrt_distance = self.distance.sqrt();
+        let band = |alpha: f32, beta: f32| (-sqrt_distance * (alpha + beta * h2o.sqrt())).exp();
+        let transmission = ATMOSPHERE_X * band(ATMOSPHERE_ALPHA_1, ATMOSPHERE_BETA_1)
+            + (1.0 - ATMOSPHERE_X) * band(ATMOSPHERE_ALPHA_2, ATMOSPHERE_BETA_2);
+        transmission.clamp(0.0, 1.0)
+    }
+
+    /// Apparent temperature (°C) at the surface of the object that appears with `apparent_temp` (°C) at the
+    /// camera.
+    pub fn surface_temperature(&self, apparent_temp: f32) -> f32 {
+        self.surface_temperature_with(self.transmission(), apparent_temp)
+    }
+
+    fn surface_temperature_with(&self, transmission: f32, apparent_temp: f32) -> f32 {
+        if transmission >= 1.0 || transmission <= 0.0 {
+            return apparent_temp;
+        }
+        let apparent = (apparent_temp + KELVIN).powi(4);
+        let air = (self.air_temp + KELVIN).powi(4);
+        let surface = (apparent - (1.0 - transmission) * air) / transmission;
+        surface.max(0.0).powf(0.25) - KELVIN
+    }
+
+    /// Loads parameters saved with [`Atmosphere::save`], e.g. next to a recording.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        content
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl Default for Atmosphere {
+    fn default() -> Self {
+        Atmosphere::none()
+    }
+}
+
+/// Lines `distance = <m>`, `air_temp = <°C>` and `relative_humidity = <%>`, missing ones keep their default.
+/// Lines starting with `#` are comments.
+impl FromStr for Atmosphere {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut atmosphere = Atmosphere::none();
+        for (line_idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_err = |msg: &str| format!("line {}: {}", line_idx + 1, msg);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| line_err("expected <setting> = <value>"))?;
+            let value: f32 = value
+                .trim()
+                .parse()
+                .map_err(|_| line_err(&format!("invalid number '{}'", value.trim())))?;
+            atmosphere = match key.trim() {
+                "distance" => atmosphere.with_distance(value),
+                "air_temp" => atmosphere.with_air_temp(value),
+                "relative_humidity" => atmosphere.with_relative_humidity(value),
+                key => return Err(line_err(&format!("unknown setting '{key}'"))),
+            };
+        }
+        Ok(atmosphere)
+    }
+}
+
+impl fmt::Display for Atmosphere {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# atmosphere between camera and object")?;
+        writeln!(f, "distance = {}", self.distance)?;
+        writeln!(f, "air_temp = {}", self.air_temp)?;
+        writeln!(f, "relative_humidity = {}", self.relative_humidity)
+    }
+}
+
+/// Copy of the frame with the apparent temperatures at the surface of the objects.
+pub fn compensate_frame(frame: &ThermalFrame, atmosphere: &Atmosphere) -> ThermalFrame {
+    let mut compensated = frame.clone();
+    let transmission = atmosphere.transmission();
+    for value in compensated.data.iter_mut() {
+        *value = atmosphere.surface_temperature_with(transmission, *value);
+    }
+    compensated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apparent temperature (°C) of an object at `object_temp` (°C), the forward model of the compensation.
+    fn apparent_temperature(atmosphere: &Atmosphere, object_temp: f32) -> f32 {
+        let transmission = atmosphere.transmission();
+        let object = (object_temp + KELVIN).powi(4);
+        let air = (atmosphere.air_temp + KELVIN).powi(4);
+        (transmission * object + (1.0 - transmission) * air).powf(0.25) - KELVIN
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 0.01, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn nothing_is_absorbed_without_distance() {
+        for (air_temp, relative_humidity) in [(20.0, 50.0), (-10.0, 0.0), (35.0, 100.0)] {
+            let atmosphere = Atmosphere::new(0.0, air_temp, relative_humidity);
+            assert_eq!(atmosphere.transmission(), 1.0);
+            assert_eq!(atmosphere.surface_temperature(42.0), 42.0);
+        }
+    }
+
+    #[test]
+    fn transmission_drops_with_distance_and_humidity() {
+        let transmission =
+            |distance, relative_humidity| Atmosphere::new(distance, 20.0, relative_humidity).transmission();
+        for relative_humidity in [0.0, 50.0, 100.0] {
+            assert!(transmission(1.0, relative_humidity) < 1.0);
+            assert!(transmission(10.0, relative_humidity) < transmission(1.0, relative_humidity));
+            assert!(transmission(100.0, relative_humidity) < transmission(10.0, relative_humidity));
+        }
+        for distance in [1.0, 10.0, 100.0] {
+            assert!(transmission(distance, 50.0) < transmission(distance, 0.0));
+            assert!(transmission(distance, 100.0) < transmission(distance, 50.0));
+        }
+    }
+
+    #[test]
+    fn transmission_of_the_two_band_model() {
+        // reference value of the FLIR model (Minkina & Dudzik, Infrared Thermography, 2009): 10 m of air at
+        // 20 °C and 50 % humidity
+        assert!((Atmosphere::new(10.0, 20.0, 50.0).transmission() - 0.9807).abs() < 1e-4);
+    }
+
+    #[test]
+    fn inverts_the_transmission_model() {
+        for atmosphere in [
+            Atmosphere::new(10.0, 20.0, 50.0),
+            Atmosphere::new(100.0, 35.0, 90.0),
+            Atmosphere::new(50.0, -5.0, 20.0),
+        ] {
+            for object_temp in [-20.0, 0.0, 37.0, 120.0, 400.0] {
+                let apparent = apparent_temperature(&atmosphere, object_temp);
+                assert_close(atmosphere.surface_temperature(apparent), object_temp);
+            }
+            // an object at the air temperature appears at that temperature
+            assert_close(atmosphere.surface_temperature(atmosphere.air_temp), atmosphere.air_temp);
+        }
+    }
+}
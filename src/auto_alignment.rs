@@ -0,0 +1,280 @@
+use image;
+
+use crate::registration::Registration;
+use crate::temperature_field::TemperatureField;
+
+/// Size of the edge maps that are compared.
+const WORKING_WIDTH: u32 = 80;
+const WORKING_HEIGHT: u32 = 60;
+/// Search steps around the current estimate: offsets in working pixels and relative scale.
+const SEARCH_RADIUS: i32 = 2;
+const SCALE_STEP: f32 = 0.01;
+const SCALE_RANGE: (f32, f32) = (0.5, 2.0);
+/// Edge maps with a lower standard deviation (e.g. a uniform scene) carry no alignment information.
+const MIN_EDGE_STD_DEV: f32 = 1e-3;
+
+/// Estimates the scale and translation between the thermal and the visible image by correlating their edge
+/// maps (gradient magnitude of the temperature field and of the luminance of the visible image).
+///
+/// The estimate starts at "no correction" and is refined with every frame by a local search around the current
+/// estimate, so it follows slow drift of the rig. It is applied on top of a calibrated registration.
+#[derive(Debug, Clone)]
+pub struct AutoAligner {
+    smoothing: f32,
+    scale: f32,
+    offset_x: f32,
+    offset_y: f32,
+    correlation: f32,
+}
+
+impl AutoAligner {
+    pub fn new() -> Self {
+        AutoAligner {
+            smoothing: 0.2,
+            scale: 1.0,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            correlation: 0.0,
+        }
+    }
+
+    /// Weight (0.0-1.0) of the estimate of a new frame, lower values react slower but are more stable.
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Correction in normalized thermal coordinates: scaling around the center and offset.
+    pub fn alignment(&self) -> Registration {
+        Registration::affine(self.scale, self.scale, 0.0, self.offset_x, self.offset_y)
+    }
+
+    /// Correlation (-1.0-1.0) of the edge maps at the best position of the last update.
+    pub fn correlation(&self) -> f32 {
+        self.correlation
+    }
+
+    /// Refines the estimate with a new pair of frames and returns `base` followed by the correction.
+    ///
+    /// `visible` is the visible image as displayed, `temperature_field` the (upscaled) temperatures of the
+    /// thermal image.
+    pub fn update(
+        &mut self,
+        visible: &image::RgbImage,
+        temperature_field: &TemperatureField,
+        base: &Registration,
+    ) -> Registration {
+        if let (Some(visible_edges), Some(thermal_edges)) =
+            (visible_edge_map(visible), thermal_edge_map(temperature_field))
+        {
+            let offset_step_x = 1.0 / WORKING_WIDTH as f32;
+            let offset_step_y = 1.0 / WORKING_HEIGHT as f32;
+            let mut best = (f32::NEG_INFINITY, self.scale, self.offset_x, self.offset_y);
+            for scale_idx in -1..=1 {
+                let scale = (self.scale * (1.0 + scale_idx as f32 * SCALE_STEP)).clamp(SCALE_RANGE.0, SCALE_RANGE.1);
+                for dy in -SEARCH_RADIUS..=SEARCH_RADIUS {
+                    for dx in -SEARCH_RADIUS..=SEARCH_RADIUS {
+                        let offset_x = self.offset_x + dx as f32 * offset_step_x;
+                        let offset_y = self.offset_y + dy as f32 * offset_step_y;
+                        let candidate = base.then(&Registration::affine(scale, scale, 0.0, offset_x, offset_y));
+                        if let Some(correlation) = correlate(&visible_edges, &thermal_edges, &candidate) {
+                            if correlation > best.0 {
+                                best = (correlation, scale, offset_x, offset_y);
+                            }
+                        }
+                    }
+                }
+            }
+            if best.0.is_finite() {
+                self.correlation = best.0;
+                self.scale += (best.1 - self.scale) * self.smoothing;
+                self.offset_x += (best.2 - self.offset_x) * self.smoothing;
+                self.offset_y += (best.3 - self.offset_y) * self.smoothing;
+            }
+        }
+        base.then(&self.alignment())
+    }
+}
+
+impl Default for AutoAligner {
+    fn default() -> Self {
+        AutoAligner::new()
+    }
+}
+
+/// Gradient magnitude of the luminance of the image at the working size.
+fn visible_edge_map(visible: &image::RgbImage) -> Option<TemperatureField> {
+    let luma = image::imageops::grayscale(visible);
+    let luma = image::imageops::resize(
+        &luma,
+        WORKING_WIDTH,
+        WORKING_HEIGHT,
+        image::imageops::FilterType::Triangle,
+    );
+    let values = luma.pixels().map(|px| px.0[0] as f32).collect();
+    edge_map(&TemperatureField::new(WORKING_WIDTH, WORKING_HEIGHT, values))
+}
+
+/// Gradient magnitude of the temperatures at the working size.
+fn thermal_edge_map(temperature_field: &TemperatureField) -> Option<TemperatureField> {
+    let scale_x = temperature_field.width as f32 / WORKING_WIDTH as f32;
+    let scale_y = temperature_field.height as f32 / WORKING_HEIGHT as f32;
+    let mut values = Vec::with_capacity((WORKING_WIDTH * WORKING_HEIGHT) as usize);
+    for y in 0..WORKING_HEIGHT {
+        for x in 0..WORKING_WIDTH {
+            values.push(temperature_field.sample((x as f32 + 0.5) * scale_x - 0.5, (y as f32 + 0.5) * scale_y - 0.5));
+        }
+    }
+    edge_map(&TemperatureField::new(WORKING_WIDTH, WORKING_HEIGHT, values))
+}
+
+/// Sobel gradient magnitude, normalized to zero mean and unit standard deviation. `None` if the field has no
+/// edges.
+fn edge_map(field: &TemperatureField) -> Option<TemperatureField> {
+    let width = field.width as i64;
+    let height = field.height as i64;
+    let at = |x: i64, y: i64| field.data[(y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize];
+    let mut magnitudes = Vec::with_capacity(field.data.len());
+    for y in 0..height {
+        for x in 0..width {
+            let gx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x - 1, y) + at(x - 1, y + 1));
+            let gy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1))
+                - (at(x - 1, y - 1) + 2.0 * at(x, y - 1) + at(x + 1, y - 1));
+            magnitudes.push((gx * gx + gy * gy).sqrt());
+        }
+    }
+    let count = magnitudes.len() as f32;
+    let mean = magnitudes.iter().sum::<f32>() / count;
+    let std_dev = (magnitudes.iter().map(|&m| (m - mean) * (m - mean)).sum::<f32>() / count).sqrt();
+    if std_dev < MIN_EDGE_STD_DEV {
+        return None;
+    }
+    let data = magnitudes.iter().map(|&m| (m - mean) / std_dev).collect();
+    Some(TemperatureField::new(field.width, field.height, data))
+}
+
+/// Pearson correlation of the visible edges and the thermal edges they are mapped to. `None` if less than half
+/// of the visible image overlaps the thermal image.
+fn correlate(
+    visible_edges: &TemperatureField,
+    thermal_edges: &TemperatureField,
+    registration: &Registration,
+) -> Option<f32> {
+    let (mut sum_v, mut sum_t, mut sum_vv, mut sum_tt, mut sum_vt) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    let mut count = 0usize;
+    for y in 0..visible_edges.height {
+        for x in 0..visible_edges.width {
+            let (u, v) = match registration.map(
+                (x as f32 + 0.5) / visible_edges.width as f32,
+                (y as f32 + 0.5) / visible_edges.height as f32,
+            ) {
+                Some(uv) => uv,
+                None => continue,
+            };
+            if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                continue;
+            }
+            let visible_edge = visible_edges.data[(y * visible_edges.width + x) as usize];
+            let thermal_edge = thermal_edges.sample(
+                u * thermal_edges.width as f32 - 0.5,
+                v * thermal_edges.height as f32 - 0.5,
+            );
+            sum_v += visible_edge;
+            sum_t += thermal_edge;
+            sum_vv += visible_edge * visible_edge;
+            sum_tt += thermal_edge * thermal_edge;
+            sum_vt += visible_edge * thermal_edge;
+            count += 1;
+        }
+    }
+    if count < visible_edges.data.len() / 2 {
+        return None;
+    }
+    let n = count as f32;
+    let covariance = sum_vt / n - (sum_v / n) * (sum_t / n);
+    let variance_v = sum_vv / n - (sum_v / n) * (sum_v / n);
+    let variance_t = sum_tt / n - (sum_t / n) * (sum_t / n);
+    let denominator = (variance_v * variance_t).sqrt();
+    if denominator <= f32::EPSILON {
+        return None;
+    }
+    Some(covariance / denominator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 1.0 inside a few rectangles of different sizes (normalized coordinates), 0.0 elsewhere.
+    fn pattern(x: f32, y: f32) -> f32 {
+        let rects = [(0.1, 0.2, 0.35, 0.5), (0.55, 0.15, 0.7, 0.75), (0.2, 0.65, 0.45, 0.85)];
+        let inside = rects
+            .iter()
+            .any(|&(left, top, right, bottom)| (left..right).contains(&x) && (top..bottom).contains(&y));
+        if inside {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn visible_image(width: u32, height: u32) -> image::RgbImage {
+        image::RgbImage::from_fn(width, height, |x, y| {
+            let value = (pattern((x as f32 + 0.5) / width as f32, (y as f32 + 0.5) / height as f32) * 200.0) as u8;
+            image::Rgb([value + 20, value + 20, value + 20])
+        })
+    }
+
+    /// The pattern as seen by the thermal camera: a point of the visible image at `p` is at `p + shift`.
+    fn shifted_temperature_field(width: u32, height: u32, shift: (f32, f32)) -> TemperatureField {
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let u = (x as f32 + 0.5) / width as f32 - shift.0;
+                let v = (y as f32 + 0.5) / height as f32 - shift.1;
+                data.push(20.0 + 15.0 * pattern(u, v));
+            }
+        }
+        TemperatureField::new(width, height, data)
+    }
+
+    /// Aligns the pattern shifted by `shift` and checks the offset found, `tolerance` in working pixels.
+    fn assert_recovers_shift(shift: (f32, f32), tolerance: f32) {
+        let visible = visible_image(160, 120);
+        let temperature_field = shifted_temperature_field(96, 72, shift);
+        let mut aligner = AutoAligner::new().with_smoothing(1.0);
+        for _ in 0..5 {
+            aligner.update(&visible, &temperature_field, &Registration::identity());
+        }
+        let alignment = aligner.alignment();
+        let (offset_x, offset_y) = alignment.map(0.5, 0.5).map(|(u, v)| (u - 0.5, v - 0.5)).unwrap();
+        assert!(
+            (offset_x - shift.0).abs() < tolerance / WORKING_WIDTH as f32
+                && (offset_y - shift.1).abs() < tolerance / WORKING_HEIGHT as f32,
+            "offset ({offset_x}, {offset_y}) instead of {shift:?}"
+        );
+        assert!(aligner.correlation() > 0.8, "correlation {}", aligner.correlation());
+    }
+
+    #[test]
+    fn recovers_a_known_shift() {
+        assert_recovers_shift((3.0 / WORKING_WIDTH as f32, -2.0 / WORKING_HEIGHT as f32), 0.5);
+    }
+
+    #[test]
+    fn recovers_a_vertical_shift() {
+        // not a multiple of the horizontal step, the nearest one is a quarter of a working pixel off
+        assert_recovers_shift((0.0, -2.0 / WORKING_HEIGHT as f32), 0.1);
+    }
+
+    #[test]
+    fn uniform_scene_keeps_the_estimate() {
+        let visible = image::RgbImage::from_pixel(160, 120, image::Rgb([90, 90, 90]));
+        let temperature_field = TemperatureField::new(32, 24, vec![21.0; 32 * 24]);
+        let mut aligner = AutoAligner::new().with_smoothing(1.0);
+        let registration = aligner.update(&visible, &temperature_field, &Registration::identity());
+        assert_eq!(registration, Registration::identity().then(&Registration::identity()));
+        assert_eq!(aligner.correlation(), 0.0);
+    }
+}
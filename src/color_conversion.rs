@@ -0,0 +1,451 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bayer;
+
+use crate::rgb_color::RgbColor;
+
+const FACTOR_10BIT_TO_8BIT: f32 = 255.0 / 1024.0;
+
+/// Luma/chroma matrix coefficients of a YCbCr signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YuvMatrix {
+    /// ITU-R BT.601 (SD, most webcams)
+    Bt601,
+    /// ITU-R BT.709 (HD)
+    Bt709,
+}
+
+/// Value range of a YCbCr signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YuvRange {
+    /// Y in 16..=235, Cb/Cr in 16..=240 ("TV range")
+    Limited,
+    /// Y, Cb and Cr in 0..=255 ("PC range", JPEG)
+    Full,
+}
+
+/// YCbCr <-> RGB conversion for a given matrix and range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct YuvConversion {
+    pub matrix: YuvMatrix,
+    pub range: YuvRange,
+}
+
+impl YuvConversion {
+    pub fn new(matrix: YuvMatrix, range: YuvRange) -> Self {
+        YuvConversion { matrix, range }
+    }
+
+    /// (Kr, Kb) luma weights of the matrix.
+    fn luma_weights(&self) -> (f32, f32) {
+        match self.matrix {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+        }
+    }
+
+    /// (offset of Y, scale of Y, scale of Cb/Cr) of the 8-bit code values.
+    fn range_parameters(&self) -> (f32, f32, f32) {
+        match self.range {
+            YuvRange::Limited => (16.0, 219.0, 224.0),
+            YuvRange::Full => (0.0, 255.0, 255.0),
+        }
+    }
+
+    pub fn yuv_to_rgb(&self, y: u8, u: u8, v: u8) -> RgbColor {
+        let (kr, kb) = self.luma_weights();
+        let kg = 1.0 - kr - kb;
+        let (y_offset, y_scale, c_scale) = self.range_parameters();
+
+        // normalize to Y in 0..1, Cb/Cr in -0.5..0.5
+        let y = (y as f32 - y_offset) / y_scale;
+        let cb = (u as f32 - 128.0) / c_scale;
+        let cr = (v as f32 - 128.0) / c_scale;
+
+        let r = y + 2.0 * (1.0 - kr) * cr;
+        let g = y - (2.0 * kb * (1.0 - kb) / kg) * cb - (2.0 * kr * (1.0 - kr) / kg) * cr;
+        let b = y + 2.0 * (1.0 - kb) * cb;
+
+        RgbColor {
+            r: round_to_u8(r * 255.0),
+            g: round_to_u8(g * 255.0),
+            b: round_to_u8(b * 255.0),
+        }
+    }
+
+    /// Inverse of [`YuvConversion::yuv_to_rgb`], returns (Y, U, V).
+    pub fn rgb_to_yuv(&self, color: RgbColor) -> (u8, u8, u8) {
+        let (kr, kb) = self.luma_weights();
+        let kg = 1.0 - kr - kb;
+        let (y_offset, y_scale, c_scale) = self.range_parameters();
+
+        let r = color.r as f32 / 255.0;
+        let g = color.g as f32 / 255.0;
+        let b = color.b as f32 / 255.0;
+
+        let y = kr * r + kg * g + kb * b;
+        let cb = (b - y) / (2.0 * (1.0 - kb));
+        let cr = (r - y) / (2.0 * (1.0 - kr));
+
+        (
+            round_to_u8(y * y_scale + y_offset),
+            round_to_u8(cb * c_scale + 128.0),
+            round_to_u8(cr * c_scale + 128.0),
+        )
+    }
+}
+
+impl Default for YuvConversion {
+    /// BT.601 limited range, as delivered by most V4L2 cameras.
+    fn default() -> Self {
+        YuvConversion::new(YuvMatrix::Bt601, YuvRange::Limited)
+    }
+}
+
+impl FromStr for YuvMatrix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bt601" | "601" => Ok(YuvMatrix::Bt601),
+            "bt709" | "709" => Ok(YuvMatrix::Bt709),
+            _ => Err(format!("unknown YUV matrix '{s}' (choose bt601 or bt709)")),
+        }
+    }
+}
+
+impl fmt::Display for YuvMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            YuvMatrix::Bt601 => write!(f, "bt601"),
+            YuvMatrix::Bt709 => write!(f, "bt709"),
+        }
+    }
+}
+
+impl FromStr for YuvRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "limited" | "tv" => Ok(YuvRange::Limited),
+            "full" | "pc" => Ok(YuvRange::Full),
+            _ => Err(format!("unknown YUV range '{s}' (choose limited or full)")),
+        }
+    }
+}
+
+impl fmt::Display for YuvRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            YuvRange::Limited => write!(f, "limited"),
+            YuvRange::Full => write!(f, "full"),
+        }
+    }
+}
+
+/// Packed YUV 4:2:2 (Y0 U Y1 V): two pixels share one U and V sample.
+pub fn yuyv_to_rgb(yuyv_buffer: &[u8], yuyv_shape: (u32, u32), cam_rgb: &mut [u8], conversion: YuvConversion) {
+    let pixel_count = yuyv_shape.0 as usize * yuyv_shape.1 as usize;
+
+    for (yuyv, rgb) in yuyv_buffer[..pixel_count * 2]
+        .chunks_exact(4)
+        .zip(cam_rgb.chunks_exact_mut(6))
+    {
+        let (y0, u, y1, v) = (yuyv[0], yuyv[1], yuyv[2], yuyv[3]);
+        rgb[..3].copy_from_slice(&conversion.yuv_to_rgb(y0, u, v).to_vec());
+        rgb[3..].copy_from_slice(&conversion.yuv_to_rgb(y1, u, v).to_vec());
+    }
+}
+
+/// Planar YUV 4:2:0 (I420/YU12): Y plane, followed by the U and the V plane with half resolution in both
+/// directions.
+pub fn yuv420_to_rgb(buf: &[u8], shape: (u32, u32), cam_rgb: &mut [u8], conversion: YuvConversion) {
+    let (width, height) = (shape.0 as usize, shape.1 as usize);
+    let size = width * height;
+    let chroma_width = width.div_ceil(2);
+    let chroma_size = chroma_width * height.div_ceil(2);
+
+    for y_coo in 0..height {
+        for x_coo in 0..width {
+            let chroma_offset = (y_coo / 2) * chroma_width + x_coo / 2;
+            let y = buf[y_coo * width + x_coo];
+            let u = buf[size + chroma_offset];
+            let v = buf[size + chroma_size + chroma_offset];
+
+            let rgb_offset = (y_coo * width + x_coo) * 3;
+            cam_rgb[rgb_offset..rgb_offset + 3].copy_from_slice(&conversion.yuv_to_rgb(y, u, v).to_vec());
+        }
+    }
+}
+
+/// Semi-planar YUV 4:2:0 (NV12): Y plane, followed by one interleaved UV plane with half resolution in both
+/// directions.
+pub fn nv12_to_rgb(buf: &[u8], shape: (u32, u32), cam_rgb: &mut [u8], conversion: YuvConversion) {
+    let (width, height) = (shape.0 as usize, shape.1 as usize);
+    let size = width * height;
+    let chroma_stride = width.div_ceil(2) * 2;
+
+    for y_coo in 0..height {
+        for x_coo in 0..width {
+            let uv_offset = size + (y_coo / 2) * chroma_stride + (x_coo / 2) * 2;
+            let y = buf[y_coo * width + x_coo];
+            let u = buf[uv_offset];
+            let v = buf[uv_offset + 1];
+
+            let rgb_offset = (y_coo * width + x_coo) * 3;
+            cam_rgb[rgb_offset..rgb_offset + 3].copy_from_slice(&conversion.yuv_to_rgb(y, u, v).to_vec());
+        }
+    }
+}
+
+/// 10-bit packed Bayer (SGRBG10P, 5 bytes for every 4 pixels), linear demosaicing.
+pub fn sgrbg10p_to_rgb(raw_camera_buffer: &[u8], camera_image_shape: (u32, u32), resulting_rgb_buffer: &mut [u8]) {
+    // convert 10-bit bayer to 8 bit bayer
+    let bayer_buffer_size = (camera_image_shape.0 * camera_image_shape.1) as usize;
+    let raw_camera_buffer_size = bayer_buffer_size * 5 / 4;
+    let mut bayer_buffer = vec![0u8; bayer_buffer_size];
+
+    for (raw_idx, bay_idx) in (0..raw_camera_buffer_size)
+        .step_by(5)
+        .zip((0..bayer_buffer_size).step_by(4))
+    {
+        // unpack pixels: 4 bytes with the 8 MSBs, followed by one byte with the 2 LSBs of each pixel
+        let lsbs = raw_camera_buffer[raw_idx + 4];
+        for pix in 0..4 {
+            let value = (raw_camera_buffer[raw_idx + pix] as u16) << 2 | ((lsbs >> (2 * pix)) & 3) as u16;
+            // convert 10-bit values to 8-bit
+            bayer_buffer[bay_idx + pix] = (value as f32 * FACTOR_10BIT_TO_8BIT) as u8;
+        }
+    }
+
+    // debayer
+    let raster_depth = bayer::RasterDepth::Depth8;
+    let mut dst = bayer::RasterMut::new(
+        camera_image_shape.0 as usize,
+        camera_image_shape.1 as usize,
+        raster_depth,
+        resulting_rgb_buffer,
+    );
+    let color_filter_array = bayer::CFA::GBRG; // SGRBG10P
+    let demosaic_algorithm = bayer::Demosaic::Linear;
+
+    bayer::run_demosaic(
+        &mut bayer_buffer.as_slice(),
+        bayer::BayerDepth::Depth8,
+        color_filter_array,
+        demosaic_algorithm,
+        &mut dst,
+    )
+    .unwrap()
+}
+
+pub(crate) fn clamp_to_u8(value: f32) -> f32 {
+    value.clamp(0.0, 255.0)
+}
+
+fn round_to_u8(value: f32) -> u8 {
+    clamp_to_u8(value.round()) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: RgbColor = RgbColor { r: 255, g: 0, b: 0 };
+    const GREEN: RgbColor = RgbColor { r: 0, g: 255, b: 0 };
+    const BLUE: RgbColor = RgbColor { r: 0, g: 0, b: 255 };
+    const WHITE: RgbColor = RgbColor { r: 255, g: 255, b: 255 };
+    const BLACK: RgbColor = RgbColor { r: 0, g: 0, b: 0 };
+
+    /// (color, Y, Cb, Cr) code values of the primaries as published for the matrix and range.
+    fn reference_pixels(conversion: YuvConversion) -> [(RgbColor, (u8, u8, u8)); 5] {
+        match (conversion.matrix, conversion.range) {
+            (YuvMatrix::Bt601, YuvRange::Limited) => [
+                (WHITE, (235, 128, 128)),
+                (BLACK, (16, 128, 128)),
+                (RED, (81, 90, 240)),
+                (GREEN, (145, 54, 34)),
+                (BLUE, (41, 240, 110)),
+            ],
+            (YuvMatrix::Bt709, YuvRange::Limited) => [
+                (WHITE, (235, 128, 128)),
+                (BLACK, (16, 128, 128)),
+                (RED, (63, 102, 240)),
+                (GREEN, (173, 42, 26)),
+                (BLUE, (32, 240, 118)),
+            ],
+            (YuvMatrix::Bt601, YuvRange::Full) => [
+                (WHITE, (255, 128, 128)),
+                (BLACK, (0, 128, 128)),
+                (RED, (76, 85, 255)),
+                (GREEN, (150, 44, 21)),
+                (BLUE, (29, 255, 107)),
+            ],
+            (YuvMatrix::Bt709, YuvRange::Full) => [
+                (WHITE, (255, 128, 128)),
+                (BLACK, (0, 128, 128)),
+                (RED, (54, 99, 255)),
+                (GREEN, (182, 30, 12)),
+                (BLUE, (18, 255, 116)),
+            ],
+        }
+    }
+
+    fn all_conversions() -> Vec<YuvConversion> {
+        let mut conversions = Vec::new();
+        for matrix in [YuvMatrix::Bt601, YuvMatrix::Bt709] {
+            for range in [YuvRange::Limited, YuvRange::Full] {
+                conversions.push(YuvConversion::new(matrix, range));
+            }
+        }
+        conversions
+    }
+
+    fn assert_close(actual: RgbColor, expected: RgbColor, tolerance: i32, context: &str) {
+        let channels = [(actual.r, expected.r), (actual.g, expected.g), (actual.b, expected.b)];
+        for (actual_value, expected_value) in channels {
+            assert!(
+                (actual_value as i32 - expected_value as i32).abs() <= tolerance,
+                "{context}: got {:?}, expected {:?}",
+                actual.to_vec(),
+                expected.to_vec()
+            );
+        }
+    }
+
+    #[test]
+    fn rgb_to_yuv_matches_reference_pixels() {
+        for conversion in all_conversions() {
+            for (color, yuv) in reference_pixels(conversion) {
+                assert_eq!(
+                    conversion.rgb_to_yuv(color),
+                    yuv,
+                    "{} {} {:?}",
+                    conversion.matrix,
+                    conversion.range,
+                    color.to_vec()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn yuv_to_rgb_inverts_reference_pixels() {
+        for conversion in all_conversions() {
+            for (color, (y, u, v)) in reference_pixels(conversion) {
+                let context = format!("{} {} {:?}", conversion.matrix, conversion.range, color.to_vec());
+                // the code values are rounded (and Cr of full range red is clipped at 255)
+                assert_close(conversion.yuv_to_rgb(y, u, v), color, 2, &context);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_of_mixed_colors() {
+        let colors = [
+            RgbColor { r: 12, g: 200, b: 97 },
+            RgbColor { r: 180, g: 40, b: 60 },
+            RgbColor { r: 128, g: 128, b: 128 },
+            RgbColor { r: 240, g: 230, b: 20 },
+        ];
+        for conversion in all_conversions() {
+            for color in colors {
+                let (y, u, v) = conversion.rgb_to_yuv(color);
+                let context = format!("{} {} {:?}", conversion.matrix, conversion.range, color.to_vec());
+                assert_close(conversion.yuv_to_rgb(y, u, v), color, 2, &context);
+            }
+        }
+    }
+
+    #[test]
+    fn yuyv_shares_u_and_v_between_two_pixels() {
+        let conversion = YuvConversion::default();
+        // Y0 U Y1 V for pixels 0/1 and 2/3
+        let yuyv = [60, 90, 200, 240, 120, 54, 30, 34];
+        let mut rgb = [0u8; 4 * 3];
+        yuyv_to_rgb(&yuyv, (4, 1), &mut rgb, conversion);
+
+        let expected = [
+            conversion.yuv_to_rgb(60, 90, 240),
+            conversion.yuv_to_rgb(200, 90, 240),
+            conversion.yuv_to_rgb(120, 54, 34),
+            conversion.yuv_to_rgb(30, 54, 34),
+        ];
+        for (i, expected) in expected.iter().enumerate() {
+            assert_eq!(rgb[i * 3..i * 3 + 3], expected.to_vec()[..], "pixel {i}");
+        }
+        // U and V swapped would give another color
+        assert_ne!(
+            conversion.yuv_to_rgb(60, 90, 240).to_vec(),
+            conversion.yuv_to_rgb(60, 240, 90).to_vec()
+        );
+    }
+
+    /// Luma plane of the shape with a distinct value for every pixel.
+    fn luma_plane(width: usize, height: usize) -> Vec<u8> {
+        (0..width * height).map(|i| (16 + i * 7 % 200) as u8).collect()
+    }
+
+    /// (U, V) of the chroma sample at (x, y) of a chroma plane with the given width.
+    fn chroma_sample(x: usize, y: usize, chroma_width: usize) -> (u8, u8) {
+        let i = y * chroma_width + x;
+        ((40 + i * 13) as u8, (220 - i * 11) as u8)
+    }
+
+    fn assert_chroma_subsampled(buf: &[u8], shape: (u32, u32), rgb: &[u8], conversion: YuvConversion) {
+        let (width, height) = (shape.0 as usize, shape.1 as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let (u, v) = chroma_sample(x / 2, y / 2, width.div_ceil(2));
+                let expected = conversion.yuv_to_rgb(buf[y * width + x], u, v).to_vec();
+                let offset = (y * width + x) * 3;
+                assert_eq!(rgb[offset..offset + 3], expected[..], "pixel ({x}, {y}) of {shape:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn yuv420_chroma_planes_at_odd_shapes() {
+        let conversion = YuvConversion::new(YuvMatrix::Bt709, YuvRange::Full);
+        for (width, height) in [(5usize, 3usize), (4, 3), (5, 4), (1, 1)] {
+            let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+            let mut buf = luma_plane(width, height);
+            let mut v_plane = Vec::new();
+            for cy in 0..chroma_height {
+                for cx in 0..chroma_width {
+                    let (u, v) = chroma_sample(cx, cy, chroma_width);
+                    buf.push(u);
+                    v_plane.push(v);
+                }
+            }
+            buf.extend(v_plane);
+
+            let shape = (width as u32, height as u32);
+            let mut rgb = vec![0u8; width * height * 3];
+            yuv420_to_rgb(&buf, shape, &mut rgb, conversion);
+            assert_chroma_subsampled(&buf, shape, &rgb, conversion);
+        }
+    }
+
+    #[test]
+    fn nv12_chroma_plane_at_odd_shapes() {
+        let conversion = YuvConversion::new(YuvMatrix::Bt601, YuvRange::Limited);
+        for (width, height) in [(5usize, 3usize), (4, 3), (5, 4), (1, 1)] {
+            let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+            let mut buf = luma_plane(width, height);
+            for cy in 0..chroma_height {
+                for cx in 0..chroma_width {
+                    let (u, v) = chroma_sample(cx, cy, chroma_width);
+                    buf.extend([u, v]);
+                }
+            }
+
+            let shape = (width as u32, height as u32);
+            let mut rgb = vec![0u8; width * height * 3];
+            nv12_to_rgb(&buf, shape, &mut rgb, conversion);
+            assert_chroma_subsampled(&buf, shape, &rgb, conversion);
+        }
+    }
+}
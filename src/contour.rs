@@ -0,0 +1,358 @@
+use image;
+
+use crate::coordinate_mapper::{CoordinateMapper, CoordinateSpace};
+use crate::rgb_color::RgbColor;
+use crate::temperature_field::TemperatureField;
+
+/// Line segment of an isoline in grid coordinates of the temperature field (grid point (x, y) is the center of
+/// pixel (x, y)).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContourSegment {
+    /// Temperature (°C) of the isoline
+    pub level: f32,
+    pub start: (f32, f32),
+    pub end: (f32, f32),
+}
+
+impl ContourSegment {
+    /// The segment of a field upscaled by `interpolation_factor` in thermal-sensor coordinates (pixel (x, y)
+    /// covers x..x+1 and y..y+1).
+    pub fn in_sensor_coordinates(self, interpolation_factor: u32) -> Self {
+        let factor = interpolation_factor as f32;
+        let to_sensor = |(x, y): (f32, f32)| ((x + 0.5) / factor, (y + 0.5) / factor);
+        ContourSegment {
+            level: self.level,
+            start: to_sensor(self.start),
+            end: to_sensor(self.end),
+        }
+    }
+}
+
+/// Smallest isoline interval (°C), finer ones are below the noise of the sensor anyway.
+pub const MIN_CONTOUR_INTERVAL: f32 = 0.01;
+
+/// Most isolines of a frame, the interval is coarsened to stay below.
+const MAX_CONTOUR_LEVELS: f32 = 256.0;
+
+/// Isoline interval (°C) of at least [`MIN_CONTOUR_INTERVAL`], e.g. as value parser of a command line argument.
+pub fn parse_contour_interval(value: &str) -> Result<f32, String> {
+    let interval = value
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| format!("invalid contour interval '{value}'"))?;
+    if interval >= MIN_CONTOUR_INTERVAL && interval.is_finite() {
+        Ok(interval)
+    } else {
+        Err(format!(
+            "contour interval {interval} needs to be at least {MIN_CONTOUR_INTERVAL}"
+        ))
+    }
+}
+
+/// Isolines of the field at every multiple of `interval` (°C), computed with marching squares. If the field
+/// spans more than [`MAX_CONTOUR_LEVELS`] intervals, only every n-th multiple is drawn.
+pub fn contour_segments(field: &TemperatureField, interval: f32) -> Vec<ContourSegment> {
+    let mut segments = Vec::new();
+    if interval <= 0.0 || field.width < 2 || field.height < 2 {
+        return segments;
+    }
+    let finite = field.data.iter().copied().filter(|value| value.is_finite());
+    let (field_min, field_max) = finite.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), value| {
+        (min.min(value), max.max(value))
+    });
+    if field_min > field_max {
+        return segments;
+    }
+    let interval = interval
+        * ((field_max - field_min) / interval / MAX_CONTOUR_LEVELS)
+            .ceil()
+            .max(1.0);
+    if !interval.is_finite() {
+        return segments;
+    }
+    let at = |x: u32, y: u32| field.data[(y * field.width + x) as usize];
+
+    for y in 0..field.height - 1 {
+        for x in 0..field.width - 1 {
+            // corners clockwise from the top left
+            let corners = [
+                ((x as f32, y as f32), at(x, y)),
+                (((x + 1) as f32, y as f32), at(x + 1, y)),
+                (((x + 1) as f32, (y + 1) as f32), at(x + 1, y + 1)),
+                ((x as f32, (y + 1) as f32), at(x, y + 1)),
+            ];
+            // f32::min/max skip NaN, so invalid corners have to be checked on their own
+            if corners.iter().any(|c| !c.1.is_finite()) {
+                continue;
+            }
+            let cell_min = corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min);
+            let cell_max = corners.iter().map(|c| c.1).fold(f32::NEG_INFINITY, f32::max);
+            let first_level = (cell_min / interval).ceil() as i64;
+            let last_level = (cell_max / interval).floor() as i64;
+            for level_idx in first_level..=last_level {
+                let level = level_idx as f32 * interval;
+                cell_segments(&corners, level, &mut segments);
+            }
+        }
+    }
+    segments
+}
+
+/// Marching squares case of a single cell and level.
+fn cell_segments(corners: &[((f32, f32), f32); 4], level: f32, segments: &mut Vec<ContourSegment>) {
+    let mut case = 0;
+    for (bit, corner) in corners.iter().enumerate() {
+        if corner.1 >= level {
+            case |= 1 << bit;
+        }
+    }
+    // crossing on the edge from corner i to corner (i + 1) % 4
+    let crossing = |edge: usize| {
+        let ((x0, y0), value0) = corners[edge];
+        let ((x1, y1), value1) = corners[(edge + 1) % 4];
+        let fraction = if value1 == value0 {
+            0.5
+        } else {
+            (level - value0) / (value1 - value0)
+        };
+        (x0 + (x1 - x0) * fraction, y0 + (y1 - y0) * fraction)
+    };
+    let mut push = |edge1: usize, edge2: usize| {
+        segments.push(ContourSegment {
+            level,
+            start: crossing(edge1),
+            end: crossing(edge2),
+        });
+    };
+    // edges: 0 top, 1 right, 2 bottom, 3 left
+    match case {
+        0 | 15 => {}
+        1 | 14 => push(3, 0),
+        2 | 13 => push(0, 1),
+        3 | 12 => push(3, 1),
+        4 | 11 => push(1, 2),
+        6 | 9 => push(0, 2),
+        7 | 8 => push(2, 3),
+        5 | 10 => {
+            // saddle: decided by the mean of the cell
+            let center = corners.iter().map(|c| c.1).sum::<f32>() / 4.0;
+            let center_above = center >= level;
+            if (case == 5) == center_above {
+                push(0, 1);
+                push(2, 3);
+            } else {
+                push(3, 0);
+                push(1, 2);
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Draws segments in thermal-sensor coordinates (see [`ContourSegment::in_sensor_coordinates`]) into the
+/// displayed image of the mapper, every isoline in the color returned by `color_of_level`.
+pub fn draw_contours_into_image<F: Fn(f32) -> RgbColor>(
+    segments: &[ContourSegment],
+    mapper: &CoordinateMapper,
+    color_of_level: F,
+    image: &mut image::RgbImage,
+) {
+    for segment in segments {
+        draw_thermal_line_into_image(segment.start, segment.end, mapper, color_of_level(segment.level), image);
+    }
+}
+
+/// Draws the line between two points in thermal-sensor coordinates into the displayed image of the mapper.
+pub(crate) fn draw_thermal_line_into_image(
+    start: (f32, f32),
+    end: (f32, f32),
+    mapper: &CoordinateMapper,
+    color: RgbColor,
+    image: &mut image::RgbImage,
+) {
+    let to_image = |point: (f32, f32)| {
+        let (x, y) = mapper.map_point(point, CoordinateSpace::Thermal, CoordinateSpace::Display)?;
+        Some((x - 0.5, y - 0.5))
+    };
+    if let (Some(start), Some(end)) = (to_image(start), to_image(end)) {
+        draw_line_into_image(start, end, color, image);
+    }
+}
+
+/// Draws the line between the pixel centers (x, y as pixel indices) into the image.
+pub(crate) fn draw_line_into_image(start: (f32, f32), end: (f32, f32), color: RgbColor, image: &mut image::RgbImage) {
+    let px = image::Rgb([color.r, color.g, color.b]);
+    let steps = (end.0 - start.0).abs().max((end.1 - start.1).abs()).ceil().max(1.0) as u32;
+    for step in 0..=steps {
+        let fraction = step as f32 / steps as f32;
+        let x = (start.0 + (end.0 - start.0) * fraction).round();
+        let y = (start.1 + (end.1 - start.1) * fraction).round();
+        if x >= 0.0 && y >= 0.0 && (x as u32) < image.width() && (y as u32) < image.height() {
+            image.put_pixel(x as u32, y as u32, px);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(width: u32, height: u32, data: &[f32]) -> TemperatureField {
+        TemperatureField::new(width, height, data.to_vec())
+    }
+
+    fn assert_point_close(actual: (f32, f32), expected: (f32, f32)) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-5 && (actual.1 - expected.1).abs() < 1e-5,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    /// Segment regardless of its direction.
+    fn assert_segment(segment: &ContourSegment, a: (f32, f32), b: (f32, f32)) {
+        let forward = (segment.start.0 - a.0).abs() < 1e-5 && (segment.start.1 - a.1).abs() < 1e-5;
+        let (start, end) = if forward { (a, b) } else { (b, a) };
+        assert_point_close(segment.start, start);
+        assert_point_close(segment.end, end);
+    }
+
+    /// Midpoints of the edges of the unit cell: top, right, bottom, left.
+    const EDGE_MIDPOINTS: [(f32, f32); 4] = [(0.5, 0.0), (1.0, 0.5), (0.5, 1.0), (0.0, 0.5)];
+
+    #[test]
+    fn segment_table_of_single_cells() {
+        // corners clockwise from the top left, 0.8 above and 0.2 below the only level 0.5
+        let expected_edges: [&[(usize, usize)]; 16] = [
+            &[],
+            &[(3, 0)],
+            &[(0, 1)],
+            &[(3, 1)],
+            &[(1, 2)],
+            &[],
+            &[(0, 2)],
+            &[(2, 3)],
+            &[(2, 3)],
+            &[(0, 2)],
+            &[],
+            &[(1, 2)],
+            &[(3, 1)],
+            &[(0, 1)],
+            &[(3, 0)],
+            &[],
+        ];
+        for (case, edges) in expected_edges.iter().enumerate() {
+            if case == 5 || case == 10 {
+                // saddles, see below
+                continue;
+            }
+            let corner = |bit: usize| if case & (1 << bit) != 0 { 0.8 } else { 0.2 };
+            let data = [corner(0), corner(1), corner(3), corner(2)];
+            let segments = contour_segments(&field(2, 2, &data), 0.5);
+            assert_eq!(segments.len(), edges.len(), "case {case}");
+            for (segment, &(edge1, edge2)) in segments.iter().zip(edges.iter()) {
+                assert_eq!(segment.level, 0.5);
+                assert_segment(segment, EDGE_MIDPOINTS[edge1], EDGE_MIDPOINTS[edge2]);
+            }
+        }
+    }
+
+    #[test]
+    fn saddle_cells_follow_the_cell_mean() {
+        // top left and bottom right above the level 10, the mean (10.5) too: the warm corners are connected
+        let segments = contour_segments(&field(2, 2, &[14.0, 6.0, 6.0, 16.0]), 10.0);
+        assert_eq!(segments.len(), 2);
+        assert_segment(&segments[0], (0.5, 0.0), (1.0, 0.4));
+        assert_segment(&segments[1], (0.4, 1.0), (0.0, 0.5));
+
+        // same corners, the mean (9.5) is below the level: the cold corners are connected
+        let segments = contour_segments(&field(2, 2, &[14.0, 6.0, 6.0, 12.0]), 10.0);
+        assert_eq!(segments.len(), 2);
+        assert_segment(&segments[0], (0.0, 0.5), (0.5, 0.0));
+        assert_segment(&segments[1], (1.0, 2.0 / 3.0), (2.0 / 3.0, 1.0));
+
+        // top right and bottom left above the level, the mean (10.5) too
+        let segments = contour_segments(&field(2, 2, &[6.0, 14.0, 16.0, 6.0]), 10.0);
+        assert_eq!(segments.len(), 2);
+        assert_segment(&segments[0], (0.0, 0.4), (0.5, 0.0));
+        assert_segment(&segments[1], (1.0, 0.5), (0.6, 1.0));
+    }
+
+    #[test]
+    fn crossings_are_interpolated_linearly() {
+        // horizontal gradient 10 -> 20, the level 12 crosses at 20 % of the cell
+        let segments = contour_segments(&field(2, 2, &[10.0, 20.0, 10.0, 20.0]), 12.0);
+        assert_eq!(segments.len(), 1);
+        assert_segment(&segments[0], (0.2, 0.0), (0.2, 1.0));
+
+        // vertical gradient over three rows
+        let segments = contour_segments(&field(2, 3, &[0.0, 0.0, 10.0, 10.0, 20.0, 20.0]), 15.0);
+        assert_eq!(segments.len(), 1);
+        assert_segment(&segments[0], (0.0, 1.5), (1.0, 1.5));
+    }
+
+    #[test]
+    fn levels_are_multiples_of_the_interval() {
+        // ramp from -3 to 9 over four columns
+        let data = [-3.0, 1.0, 5.0, 9.0, -3.0, 1.0, 5.0, 9.0];
+        let segments = contour_segments(&field(4, 2, &data), 2.5);
+        let mut levels: Vec<f32> = segments.iter().map(|segment| segment.level).collect();
+        levels.dedup();
+        assert_eq!(levels, vec![-2.5, 0.0, 2.5, 5.0, 7.5]);
+        for segment in &segments {
+            // x = 0 at -3 °C, 4 °C per column
+            let x = (segment.level + 3.0) / 4.0;
+            assert_segment(segment, (x, 0.0), (x, 1.0));
+        }
+    }
+
+    #[test]
+    fn uniform_and_invalid_fields_have_no_contours() {
+        assert!(contour_segments(&field(2, 2, &[21.3; 4]), 1.0).is_empty());
+        assert!(contour_segments(&field(2, 2, &[0.0, 1.0, 2.0, 3.0]), 0.0).is_empty());
+        assert!(contour_segments(&field(1, 2, &[0.0, 3.0]), 1.0).is_empty());
+        assert!(contour_segments(&field(2, 2, &[0.0, 1.0, f32::NAN, 3.0]), 0.5).is_empty());
+    }
+
+    #[test]
+    fn tiny_intervals_are_coarsened() {
+        let ramp = field(2, 2, &[0.0, 100.0, 0.0, 100.0]);
+        for interval in [1e-6, 1e-30] {
+            let mut levels: Vec<f32> = contour_segments(&ramp, interval).iter().map(|s| s.level).collect();
+            levels.dedup();
+            assert!(!levels.is_empty());
+            assert!(
+                levels.len() <= MAX_CONTOUR_LEVELS as usize + 1,
+                "{} levels",
+                levels.len()
+            );
+        }
+        // intervals within the limit are kept: levels 1 to 100, all corners are at or above the level 0
+        assert_eq!(contour_segments(&ramp, 1.0).len(), 100);
+    }
+
+    #[test]
+    fn sensor_coordinates_of_upscaled_fields() {
+        let segment = ContourSegment {
+            level: 1.0,
+            start: (0.0, 0.0),
+            end: (5.5, 11.5),
+        };
+        let segment = segment.in_sensor_coordinates(6);
+        assert_point_close(segment.start, (0.5 / 6.0, 0.5 / 6.0));
+        assert_point_close(segment.end, (1.0, 2.0));
+    }
+
+    #[test]
+    fn parse_contour_intervals_above_the_minimum() {
+        assert_eq!(parse_contour_interval("2.5"), Ok(2.5));
+        assert_eq!(parse_contour_interval(" 1"), Ok(1.0));
+        assert_eq!(parse_contour_interval("0.01"), Ok(0.01));
+        assert!(parse_contour_interval("0.005").is_err());
+        assert!(parse_contour_interval("1e-30").is_err());
+        assert!(parse_contour_interval("inf").is_err());
+        assert!(parse_contour_interval("0").is_err());
+        assert!(parse_contour_interval("-2").is_err());
+        assert!(parse_contour_interval("NaN").is_err());
+        assert!(parse_contour_interval("fine").is_err());
+    }
+}
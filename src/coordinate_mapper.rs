@@ -0,0 +1,538 @@
+use crate::display_mode::DisplayMode;
+use crate::orientation::Orientation;
+use crate::picture_in_picture_inset;
+use crate::registration::Registration;
+
+/// Coordinate systems of the camera, all in continuous pixel coordinates: pixel (x, y) covers x..x+1 and
+/// y..y+1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoordinateSpace {
+    /// Displayed image, e.g. the blended image or both images side by side
+    Display,
+    /// Raw frame of the visible camera
+    Visible,
+    /// Raw frame of the thermal sensor, used for all measurements (regions, spots, line profiles)
+    Thermal,
+}
+
+/// Axis-aligned rectangle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Rect { x, y, width, height }
+    }
+
+    pub fn contains(&self, (x, y): (f32, f32)) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+
+    fn corners(&self) -> [(f32, f32); 4] {
+        [
+            (self.x, self.y),
+            (self.x + self.width, self.y),
+            (self.x + self.width, self.y + self.height),
+            (self.x, self.y + self.height),
+        ]
+    }
+}
+
+/// Converts points and rectangles between the displayed image, the visible camera and the thermal sensor.
+///
+/// Both sensors are oriented (flipped/rotated) first, the registration maps the normalized oriented visible
+/// image to the normalized oriented thermal image. The display mode determines where the oriented visible and
+/// thermal images are shown in the displayed image.
+#[derive(Debug, Clone)]
+pub struct CoordinateMapper {
+    visible_shape: (u32, u32),
+    visible_orientation: Orientation,
+    thermal_shape: (u32, u32),
+    thermal_orientation: Orientation,
+    registration: Registration,
+    inverse_registration: Option<Registration>,
+    mode: DisplayMode,
+    display_shape: Option<(u32, u32)>,
+    inset_fraction: f32,
+}
+
+impl CoordinateMapper {
+    /// Mapper for raw frames of the given sizes, by default the visible image is displayed as it is and the
+    /// thermal image is stretched over it.
+    pub fn new(visible_shape: (u32, u32), thermal_shape: (u32, u32)) -> Self {
+        CoordinateMapper {
+            visible_shape,
+            visible_orientation: Orientation::identity(),
+            thermal_shape,
+            thermal_orientation: Orientation::identity(),
+            registration: Registration::identity(),
+            inverse_registration: Some(Registration::identity()),
+            mode: DisplayMode::Visible,
+            display_shape: None,
+            inset_fraction: 0.0,
+        }
+    }
+
+    /// Mapper for the (upscaled) thermal image of the given size.
+    pub fn for_thermal_image(thermal_shape: (u32, u32), image_shape: (u32, u32)) -> Self {
+        CoordinateMapper::new(image_shape, thermal_shape).with_display(DisplayMode::Thermal, image_shape, 0.0)
+    }
+
+    pub fn with_visible_orientation(mut self, visible_orientation: Orientation) -> Self {
+        self.visible_orientation = visible_orientation;
+        self
+    }
+
+    pub fn with_thermal_orientation(mut self, thermal_orientation: Orientation) -> Self {
+        self.thermal_orientation = thermal_orientation;
+        self
+    }
+
+    pub fn with_registration(mut self, registration: Registration) -> Self {
+        self.registration = registration;
+        self.inverse_registration = registration.inverse();
+        self
+    }
+
+    /// Layout of the displayed image: the mode, its size and the inset fraction of picture-in-picture.
+    pub fn with_display(mut self, mode: DisplayMode, display_shape: (u32, u32), inset_fraction: f32) -> Self {
+        self.mode = mode;
+        self.display_shape = Some(display_shape);
+        self.inset_fraction = inset_fraction;
+        self
+    }
+
+    /// Size of the frame or image of the coordinate space.
+    pub fn shape(&self, space: CoordinateSpace) -> (u32, u32) {
+        match space {
+            CoordinateSpace::Display => self
+                .display_shape
+                .unwrap_or_else(|| self.visible_orientation.oriented_shape(self.visible_shape)),
+            CoordinateSpace::Visible => self.visible_shape,
+            CoordinateSpace::Thermal => self.thermal_shape,
+        }
+    }
+
+    /// Whether the point is inside the frame or image of the coordinate space.
+    pub fn contains(&self, point: (f32, f32), space: CoordinateSpace) -> bool {
+        let (width, height) = self.shape(space);
+        Rect::new(0.0, 0.0, width as f32, height as f32).contains(point)
+    }
+
+    /// Converts the point, `None` if the registration does not map it (e.g. to infinity) or the target is not
+    /// displayed. Points outside of the frames are extrapolated.
+    ///
+    /// Display points of the thermal image (thermal mode, right half of side-by-side, inset of
+    /// picture-in-picture) are mapped directly, all others through the visible image and the registration.
+    pub fn map_point(&self, point: (f32, f32), from: CoordinateSpace, to: CoordinateSpace) -> Option<(f32, f32)> {
+        if from == to {
+            return Some(point);
+        }
+        let thermal = match from {
+            CoordinateSpace::Thermal => point,
+            CoordinateSpace::Visible => self.visible_to_thermal(point)?,
+            CoordinateSpace::Display => self.display_to_thermal(point)?,
+        };
+        match to {
+            CoordinateSpace::Thermal => Some(thermal),
+            CoordinateSpace::Visible => self.thermal_to_visible(thermal),
+            CoordinateSpace::Display => self.thermal_to_display(thermal),
+        }
+    }
+
+    /// Bounding box of the converted corners of the rectangle.
+    pub fn map_rect(&self, rect: Rect, from: CoordinateSpace, to: CoordinateSpace) -> Option<Rect> {
+        let mut corners = Vec::with_capacity(4);
+        for corner in rect.corners() {
+            corners.push(self.map_point(corner, from, to)?);
+        }
+        let min_x = corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min);
+        let max_x = corners.iter().map(|c| c.0).fold(f32::NEG_INFINITY, f32::max);
+        let min_y = corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min);
+        let max_y = corners.iter().map(|c| c.1).fold(f32::NEG_INFINITY, f32::max);
+        Some(Rect::new(min_x, min_y, max_x - min_x, max_y - min_y))
+    }
+
+    /// Where the oriented visible and thermal images are shown in the displayed image.
+    fn panels(&self) -> (Option<Rect>, Option<Rect>) {
+        let (width, height) = self.shape(CoordinateSpace::Display);
+        let full = Rect::new(0.0, 0.0, width as f32, height as f32);
+        match self.mode {
+            DisplayMode::Thermal => (None, Some(full)),
+            DisplayMode::SideBySide => {
+                let half_width = width as f32 / 2.0;
+                (
+                    Some(Rect::new(0.0, 0.0, half_width, height as f32)),
+                    Some(Rect::new(half_width, 0.0, half_width, height as f32)),
+                )
+            }
+            DisplayMode::PictureInPicture => {
+                // the inset keeps the aspect ratio of the thermal image
+                let inset = picture_in_picture_inset(
+                    (width, height),
+                    self.thermal_orientation.oriented_shape(self.thermal_shape),
+                    self.inset_fraction,
+                )
+                .map(|(inset_x, inset_width, inset_height)| {
+                    Rect::new(inset_x as f32, 0.0, inset_width as f32, inset_height as f32)
+                });
+                (Some(full), inset)
+            }
+            _ => (Some(full), None),
+        }
+    }
+
+    /// Normalized position in the oriented visible image.
+    fn orient_visible(&self, point: (f32, f32)) -> (f32, f32) {
+        let (x, y) = self.visible_orientation.apply(point, self.visible_shape);
+        let (width, height) = self.visible_orientation.oriented_shape(self.visible_shape);
+        (x / width as f32, y / height as f32)
+    }
+
+    /// Position in the raw visible frame of a normalized position in the oriented visible image.
+    fn unorient_visible(&self, (u, v): (f32, f32)) -> (f32, f32) {
+        let (width, height) = self.visible_orientation.oriented_shape(self.visible_shape);
+        self.visible_orientation
+            .revert((u * width as f32, v * height as f32), self.visible_shape)
+    }
+
+    /// Normalized position in the oriented thermal image.
+    fn orient_thermal(&self, point: (f32, f32)) -> (f32, f32) {
+        let (x, y) = self.thermal_orientation.apply(point, self.thermal_shape);
+        let (width, height) = self.thermal_orientation.oriented_shape(self.thermal_shape);
+        (x / width as f32, y / height as f32)
+    }
+
+    /// Position in the raw thermal frame of a normalized position in the oriented thermal image.
+    fn unorient_thermal(&self, (u, v): (f32, f32)) -> (f32, f32) {
+        let (width, height) = self.thermal_orientation.oriented_shape(self.thermal_shape);
+        self.thermal_orientation
+            .revert((u * width as f32, v * height as f32), self.thermal_shape)
+    }
+
+    fn visible_to_thermal(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        let (u, v) = self.orient_visible(point);
+        Some(self.unorient_thermal(self.registration.map(u, v)?))
+    }
+
+    fn thermal_to_visible(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        let (u, v) = self.orient_thermal(point);
+        Some(self.unorient_visible(self.inverse_registration?.map(u, v)?))
+    }
+
+    fn display_to_thermal(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        match self.panels() {
+            (visible_panel, Some(thermal_panel)) if thermal_panel.contains(point) || visible_panel.is_none() => {
+                let u = (point.0 - thermal_panel.x) / thermal_panel.width;
+                let v = (point.1 - thermal_panel.y) / thermal_panel.height;
+                Some(self.unorient_thermal((u, v)))
+            }
+            (Some(visible_panel), _) => {
+                let u = (point.0 - visible_panel.x) / visible_panel.width;
+                let v = (point.1 - visible_panel.y) / visible_panel.height;
+                Some(self.unorient_thermal(self.registration.map(u, v)?))
+            }
+            _ => None,
+        }
+    }
+
+    fn thermal_to_display(&self, point: (f32, f32)) -> Option<(f32, f32)> {
+        let (u, v) = self.orient_thermal(point);
+        match self.panels() {
+            (_, Some(thermal_panel)) => Some((
+                thermal_panel.x + u * thermal_panel.width,
+                thermal_panel.y + v * thermal_panel.height,
+            )),
+            (Some(visible_panel), None) => {
+                let (u, v) = self.inverse_registration?.map(u, v)?;
+                Some((
+                    visible_panel.x + u * visible_panel.width,
+                    visible_panel.y + v * visible_panel.height,
+                ))
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+/// Position in the image shown in a view of the given size (all in pixels), the image is scaled to fit and
+/// centered (`image-fit: contain`). `None` outside of the image.
+pub fn view_to_image_position(
+    (x, y): (f32, f32),
+    (view_width, view_height): (f32, f32),
+    (image_width, image_height): (u32, u32),
+) -> Option<(f32, f32)> {
+    let scale = (view_width / image_width as f32).min(view_height / image_height as f32);
+    let image_x = (x - (view_width - image_width as f32 * scale) / 2.0) / scale;
+    let image_y = (y - (view_height - image_height as f32 * scale) / 2.0) / scale;
+    if (0.0..image_width as f32).contains(&image_x) && (0.0..image_height as f32).contains(&image_y) {
+        Some((image_x, image_y))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VISIBLE_SHAPE: (u32, u32) = (320, 240);
+    const THERMAL_SHAPE: (u32, u32) = (32, 24);
+
+    fn assert_point_close(actual: Option<(f32, f32)>, expected: (f32, f32)) {
+        let actual = actual.expect("point not mapped");
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    fn assert_rect_close(actual: Option<Rect>, expected: Rect) {
+        let actual = actual.expect("rectangle not mapped");
+        let difference = [
+            actual.x - expected.x,
+            actual.y - expected.y,
+            actual.width - expected.width,
+            actual.height - expected.height,
+        ];
+        assert!(difference.iter().all(|d| d.abs() < 1e-3), "{actual:?} != {expected:?}");
+    }
+
+    fn orientations() -> Vec<Orientation> {
+        let mut orientations = Vec::new();
+        for flip_horizontal in [false, true] {
+            for flip_vertical in [false, true] {
+                for rotate_90 in [false, true] {
+                    orientations.push(
+                        Orientation::identity()
+                            .with_flip_horizontal(flip_horizontal)
+                            .with_flip_vertical(flip_vertical)
+                            .with_rotate_90(rotate_90),
+                    );
+                }
+            }
+        }
+        orientations
+    }
+
+    #[test]
+    fn round_trips_with_all_orientations() {
+        let registration = Registration::affine(1.2, 0.9, 5.0, 0.05, -0.03);
+        for visible_orientation in orientations() {
+            for thermal_orientation in orientations() {
+                for mode in [DisplayMode::Visible, DisplayMode::Thermal, DisplayMode::SideBySide] {
+                    let display_shape = match mode {
+                        DisplayMode::SideBySide => (640, 240),
+                        _ => (320, 240),
+                    };
+                    let mapper = CoordinateMapper::new(VISIBLE_SHAPE, THERMAL_SHAPE)
+                        .with_visible_orientation(visible_orientation)
+                        .with_thermal_orientation(thermal_orientation)
+                        .with_registration(registration)
+                        .with_display(mode, display_shape, 0.0);
+                    for thermal in [(0.5, 0.5), (3.25, 17.5), (31.0, 2.0)] {
+                        let display = mapper.map_point(thermal, CoordinateSpace::Thermal, CoordinateSpace::Display);
+                        let back =
+                            mapper.map_point(display.unwrap(), CoordinateSpace::Display, CoordinateSpace::Thermal);
+                        assert_point_close(back, thermal);
+                        let visible = mapper.map_point(thermal, CoordinateSpace::Thermal, CoordinateSpace::Visible);
+                        let back =
+                            mapper.map_point(visible.unwrap(), CoordinateSpace::Visible, CoordinateSpace::Thermal);
+                        assert_point_close(back, thermal);
+                    }
+                    for visible in [(10.0, 20.0), (300.5, 100.25)] {
+                        let thermal = mapper.map_point(visible, CoordinateSpace::Visible, CoordinateSpace::Thermal);
+                        let back =
+                            mapper.map_point(thermal.unwrap(), CoordinateSpace::Thermal, CoordinateSpace::Visible);
+                        assert_point_close(back, visible);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rotated_thermal_image() {
+        // rotated clockwise, the first row of the sensor becomes the last column
+        let mapper = CoordinateMapper::for_thermal_image(THERMAL_SHAPE, (240, 320))
+            .with_thermal_orientation(Orientation::identity().with_rotate_90(true));
+        assert_eq!(mapper.shape(CoordinateSpace::Display), (240, 320));
+        assert_point_close(
+            mapper.map_point((0.5, 0.5), CoordinateSpace::Thermal, CoordinateSpace::Display),
+            (235.0, 5.0),
+        );
+        assert_point_close(
+            mapper.map_point((5.0, 315.0), CoordinateSpace::Display, CoordinateSpace::Thermal),
+            (31.5, 23.5),
+        );
+    }
+
+    #[test]
+    fn visible_to_thermal_without_orientation() {
+        let mapper = CoordinateMapper::new(VISIBLE_SHAPE, THERMAL_SHAPE);
+        assert_point_close(
+            mapper.map_point((160.0, 120.0), CoordinateSpace::Visible, CoordinateSpace::Thermal),
+            (16.0, 12.0),
+        );
+        let mirrored = mapper.with_visible_orientation(Orientation::identity().with_flip_horizontal(true));
+        assert_point_close(
+            mirrored.map_point((80.0, 60.0), CoordinateSpace::Visible, CoordinateSpace::Thermal),
+            (24.0, 6.0),
+        );
+    }
+
+    #[test]
+    fn halves_of_side_by_side() {
+        // shifted by a quarter of the thermal image, so the visible half maps differently than the thermal half
+        let mapper = CoordinateMapper::new(VISIBLE_SHAPE, THERMAL_SHAPE)
+            .with_registration(Registration::affine(1.0, 1.0, 0.0, 0.25, 0.0))
+            .with_display(DisplayMode::SideBySide, (640, 240), 0.0);
+        // left half: the visible image, through the registration
+        assert_point_close(
+            mapper.map_point((160.0, 120.0), CoordinateSpace::Display, CoordinateSpace::Thermal),
+            (24.0, 12.0),
+        );
+        // right half: the thermal image
+        assert_point_close(
+            mapper.map_point((480.0, 120.0), CoordinateSpace::Display, CoordinateSpace::Thermal),
+            (16.0, 12.0),
+        );
+        assert_point_close(
+            mapper.map_point((16.0, 12.0), CoordinateSpace::Thermal, CoordinateSpace::Display),
+            (480.0, 120.0),
+        );
+    }
+
+    #[test]
+    fn inside_and_outside_of_the_picture_in_picture_inset() {
+        let registration = Registration::affine(1.0, 1.0, 0.0, 0.25, 0.0);
+        let mapper = CoordinateMapper::new(VISIBLE_SHAPE, THERMAL_SHAPE)
+            .with_registration(registration)
+            .with_display(DisplayMode::PictureInPicture, VISIBLE_SHAPE, 0.25);
+        // inset of 80x60 pixels in the top right corner
+        assert_point_close(
+            mapper.map_point((280.0, 30.0), CoordinateSpace::Display, CoordinateSpace::Thermal),
+            (16.0, 12.0),
+        );
+        assert_point_close(
+            mapper.map_point((16.0, 12.0), CoordinateSpace::Thermal, CoordinateSpace::Display),
+            (280.0, 30.0),
+        );
+        // outside of the inset: the visible image
+        assert_point_close(
+            mapper.map_point((160.0, 120.0), CoordinateSpace::Display, CoordinateSpace::Thermal),
+            (24.0, 12.0),
+        );
+        // without an inset, thermal points are shown on the visible image
+        let mapper = mapper.with_display(DisplayMode::PictureInPicture, VISIBLE_SHAPE, 0.0);
+        assert_point_close(
+            mapper.map_point((16.0, 12.0), CoordinateSpace::Thermal, CoordinateSpace::Display),
+            (80.0, 120.0),
+        );
+    }
+
+    #[test]
+    fn registration_between_the_cameras() {
+        // the thermal image covers the center half of the visible image
+        let mapper = CoordinateMapper::new(VISIBLE_SHAPE, THERMAL_SHAPE)
+            .with_registration(Registration::affine(2.0, 2.0, 0.0, 0.0, 0.0));
+        assert_point_close(
+            mapper.map_point((240.0, 120.0), CoordinateSpace::Visible, CoordinateSpace::Thermal),
+            (32.0, 12.0),
+        );
+        assert_point_close(
+            mapper.map_point((0.0, 0.0), CoordinateSpace::Thermal, CoordinateSpace::Display),
+            (80.0, 60.0),
+        );
+
+        let singular = Registration::from_matrix([[1.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]]);
+        let mapper = CoordinateMapper::new(VISIBLE_SHAPE, THERMAL_SHAPE).with_registration(singular);
+        assert!(mapper
+            .map_point((160.0, 120.0), CoordinateSpace::Visible, CoordinateSpace::Thermal)
+            .is_some());
+        assert_eq!(
+            mapper.map_point((16.0, 12.0), CoordinateSpace::Thermal, CoordinateSpace::Visible),
+            None
+        );
+        assert_eq!(
+            mapper.map_point((16.0, 12.0), CoordinateSpace::Thermal, CoordinateSpace::Display),
+            None
+        );
+    }
+
+    #[test]
+    fn taps_on_a_view_of_the_same_aspect_ratio() {
+        assert_point_close(
+            view_to_image_position((0.0, 0.0), (640.0, 480.0), VISIBLE_SHAPE),
+            (0.0, 0.0),
+        );
+        assert_point_close(
+            view_to_image_position((639.0, 479.0), (640.0, 480.0), VISIBLE_SHAPE),
+            (319.5, 239.5),
+        );
+        assert_eq!(
+            view_to_image_position((640.0, 100.0), (640.0, 480.0), VISIBLE_SHAPE),
+            None
+        );
+        assert_eq!(
+            view_to_image_position((100.0, 480.0), (640.0, 480.0), VISIBLE_SHAPE),
+            None
+        );
+        assert_eq!(
+            view_to_image_position((-0.5, 100.0), (640.0, 480.0), VISIBLE_SHAPE),
+            None
+        );
+    }
+
+    #[test]
+    fn taps_on_a_wider_view() {
+        // scaled by 2 to 640x480, bars of 80 pixels left and right
+        let view = (800.0, 480.0);
+        assert_eq!(view_to_image_position((40.0, 240.0), view, VISIBLE_SHAPE), None);
+        assert_eq!(view_to_image_position((760.0, 240.0), view, VISIBLE_SHAPE), None);
+        assert_point_close(view_to_image_position((80.0, 0.0), view, VISIBLE_SHAPE), (0.0, 0.0));
+        assert_point_close(
+            view_to_image_position((400.0, 240.0), view, VISIBLE_SHAPE),
+            (160.0, 120.0),
+        );
+        assert_point_close(
+            view_to_image_position((719.0, 479.0), view, VISIBLE_SHAPE),
+            (319.5, 239.5),
+        );
+        assert_eq!(view_to_image_position((720.0, 479.0), view, VISIBLE_SHAPE), None);
+    }
+
+    #[test]
+    fn taps_on_a_taller_view() {
+        // scaled by 0.5 to 160x120, bars of 60 pixels above and below
+        let view = (160.0, 240.0);
+        assert_eq!(view_to_image_position((80.0, 30.0), view, VISIBLE_SHAPE), None);
+        assert_eq!(view_to_image_position((80.0, 200.0), view, VISIBLE_SHAPE), None);
+        assert_point_close(view_to_image_position((0.0, 60.0), view, VISIBLE_SHAPE), (0.0, 0.0));
+        assert_point_close(
+            view_to_image_position((159.0, 179.0), view, VISIBLE_SHAPE),
+            (318.0, 238.0),
+        );
+        assert_eq!(view_to_image_position((80.0, 180.0), view, VISIBLE_SHAPE), None);
+    }
+
+    #[test]
+    fn bounding_box_of_a_rotated_rectangle() {
+        let mapper = CoordinateMapper::for_thermal_image(THERMAL_SHAPE, (240, 320))
+            .with_thermal_orientation(Orientation::identity().with_rotate_90(true));
+        // corners (2, 3), (6, 3), (6, 8) and (2, 8) are rotated to (21, 2), (21, 6), (16, 6) and (16, 2)
+        assert_rect_close(
+            mapper.map_rect(
+                Rect::new(2.0, 3.0, 4.0, 5.0),
+                CoordinateSpace::Thermal,
+                CoordinateSpace::Display,
+            ),
+            Rect::new(160.0, 20.0, 50.0, 40.0),
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::io;
+
+use image;
+use v4l::FourCC;
+
+use crate::color_conversion::{self, YuvConversion};
+use crate::visible_source::VisibleFrame;
+
+/// Converts a raw camera frame of the given (width, height) into packed RGB24 (3 * width * height bytes).
+/// The YUV conversion is only relevant for YCbCr formats.
+pub type DecodeFn = fn(&[u8], (u32, u32), &mut [u8], YuvConversion) -> io::Result<()>;
+
+#[derive(Clone, Copy)]
+struct Decoder {
+    decode: DecodeFn,
+    /// Expected frame size in bytes for a given shape, `None` for compressed formats
+    frame_size: fn((u32, u32)) -> Option<usize>,
+}
+
+/// Maps pixel formats (FourCC) to the matching RGB converter.
+///
+/// `DecoderRegistry::new()` knows SGRBG10P (`pGAA`), YUYV, YUV420 (`YU12`), NV12, RGB24 (`RGB3`) and
+/// MJPEG (`MJPG`). Further formats can be added with [`DecoderRegistry::register`].
+pub struct DecoderRegistry {
+    decoders: HashMap<[u8; 4], Decoder>,
+    yuv_conversion: YuvConversion,
+}
+
+impl DecoderRegistry {
+    /// Creates a registry with the built-in formats.
+    pub fn new() -> Self {
+        let mut registry = DecoderRegistry::empty();
+        registry.register(FourCC::new(b"pGAA"), decode_sgrbg10p, |shape| {
+            // 5 bytes for every 4 pixels
+            Some(pixel_count(shape) * 5 / 4)
+        });
+        registry.register(FourCC::new(b"YUYV"), decode_yuyv, |shape| Some(pixel_count(shape) * 2));
+        registry.register(FourCC::new(b"YU12"), decode_yuv420, |shape| {
+            Some(yuv420_frame_size(shape))
+        });
+        registry.register(FourCC::new(b"NV12"), decode_nv12, |shape| {
+            Some(yuv420_frame_size(shape))
+        });
+        registry.register(FourCC::new(b"RGB3"), decode_rgb24, |shape| Some(pixel_count(shape) * 3));
+        registry.register(FourCC::new(b"MJPG"), decode_mjpeg, |_| None);
+        registry
+    }
+
+    /// Creates a registry without any format.
+    pub fn empty() -> Self {
+        DecoderRegistry {
+            decoders: HashMap::new(),
+            yuv_conversion: YuvConversion::default(),
+        }
+    }
+
+    /// Matrix and range used for YCbCr formats (default: BT.601, limited range).
+    pub fn with_yuv_conversion(mut self, yuv_conversion: YuvConversion) -> Self {
+        self.yuv_conversion = yuv_conversion;
+        self
+    }
+
+    /// Registers (or replaces) the decoder for `fourcc`. `frame_size` returns the expected size of a frame in
+    /// bytes for a given shape, or `None` if it is not known in advance (e.g. compressed formats).
+    pub fn register(&mut self, fourcc: FourCC, decode: DecodeFn, frame_size: fn((u32, u32)) -> Option<usize>) {
+        self.decoders.insert(fourcc.repr, Decoder { decode, frame_size });
+    }
+
+    pub fn supports(&self, fourcc: FourCC) -> bool {
+        self.decoders.contains_key(&fourcc.repr)
+    }
+
+    /// All registered formats, sorted alphabetically.
+    pub fn supported_fourccs(&self) -> Vec<FourCC> {
+        let mut fourccs: Vec<FourCC> = self.decoders.keys().map(FourCC::new).collect();
+        fourccs.sort_by_key(|fourcc| fourcc.repr);
+        fourccs
+    }
+
+    /// Size of a raw frame of the given format and shape in bytes, if the format has a fixed frame size.
+    pub fn frame_size(&self, fourcc: FourCC, shape: (u32, u32)) -> io::Result<Option<usize>> {
+        Ok((self.decoder(fourcc)?.frame_size)(shape))
+    }
+
+    /// Decodes `frame` into an RGB image of the frame's shape.
+    pub fn decode(&self, frame: &VisibleFrame) -> io::Result<image::RgbImage> {
+        let decoder = self.decoder(frame.fourcc)?;
+        if let Some(frame_size) = (decoder.frame_size)(frame.shape) {
+            if frame.data.len() < frame_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} frame of shape {:?} needs {} bytes, got {}",
+                        frame.fourcc,
+                        frame.shape,
+                        frame_size,
+                        frame.data.len()
+                    ),
+                ));
+            }
+        }
+        let mut rgb_buffer = vec![0u8; 3 * frame.shape.0 as usize * frame.shape.1 as usize];
+        (decoder.decode)(frame.data, frame.shape, &mut rgb_buffer, self.yuv_conversion)?;
+        Ok(image::RgbImage::from_raw(frame.shape.0, frame.shape.1, rgb_buffer).unwrap())
+    }
+
+    fn decoder(&self, fourcc: FourCC) -> io::Result<Decoder> {
+        self.decoders.get(&fourcc.repr).copied().ok_or_else(|| {
+            let supported: Vec<String> = self.supported_fourccs().iter().map(|f| f.to_string()).collect();
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "unsupported pixel format '{}' (supported: {})",
+                    fourcc,
+                    supported.join(", ")
+                ),
+            )
+        })
+    }
+}
+
+impl Default for DecoderRegistry {
+    fn default() -> Self {
+        DecoderRegistry::new()
+    }
+}
+
+fn pixel_count(shape: (u32, u32)) -> usize {
+    shape.0 as usize * shape.1 as usize
+}
+
+/// Luma plane plus two chroma planes with half resolution (rounded up) in both directions.
+fn yuv420_frame_size(shape: (u32, u32)) -> usize {
+    pixel_count(shape) + 2 * pixel_count((shape.0.div_ceil(2), shape.1.div_ceil(2)))
+}
+
+fn decode_sgrbg10p(buf: &[u8], shape: (u32, u32), rgb: &mut [u8], _: YuvConversion) -> io::Result<()> {
+    color_conversion::sgrbg10p_to_rgb(buf, shape, rgb);
+    Ok(())
+}
+
+fn decode_yuyv(buf: &[u8], shape: (u32, u32), rgb: &mut [u8], conversion: YuvConversion) -> io::Result<()> {
+    color_conversion::yuyv_to_rgb(buf, shape, rgb, conversion);
+    Ok(())
+}
+
+fn decode_yuv420(buf: &[u8], shape: (u32, u32), rgb: &mut [u8], conversion: YuvConversion) -> io::Result<()> {
+    color_conversion::yuv420_to_rgb(buf, shape, rgb, conversion);
+    Ok(())
+}
+
+fn decode_nv12(buf: &[u8], shape: (u32, u32), rgb: &mut [u8], conversion: YuvConversion) -> io::Result<()> {
+    color_conversion::nv12_to_rgb(buf, shape, rgb, conversion);
+    Ok(())
+}
+
+fn decode_rgb24(buf: &[u8], shape: (u32, u32), rgb: &mut [u8], _: YuvConversion) -> io::Result<()> {
+    rgb.copy_from_slice(&buf[..pixel_count(shape) * 3]);
+    Ok(())
+}
+
+fn decode_mjpeg(buf: &[u8], shape: (u32, u32), rgb: &mut [u8], _: YuvConversion) -> io::Result<()> {
+    let img = image::load_from_memory_with_format(buf, image::ImageFormat::Jpeg)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?
+        .to_rgb8();
+    if img.dimensions() != shape {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("MJPG frame has shape {:?}, expected {:?}", img.dimensions(), shape),
+        ));
+    }
+    rgb.copy_from_slice(img.as_raw());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::color_conversion::{YuvMatrix, YuvRange};
+    use crate::rgb_color::RgbColor;
+
+    fn frame<'a>(data: &'a [u8], fourcc: &[u8; 4], shape: (u32, u32)) -> VisibleFrame<'a> {
+        VisibleFrame {
+            data,
+            fourcc: FourCC::new(fourcc),
+            shape,
+            sequence: 0,
+            timestamp: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn yuv420_frame_size_rounds_chroma_up() {
+        let registry = DecoderRegistry::new();
+        for fourcc in [b"YU12", b"NV12"] {
+            assert_eq!(
+                registry.frame_size(FourCC::new(fourcc), (4, 2)).unwrap(),
+                Some(8 + 2 * 2)
+            );
+            assert_eq!(
+                registry.frame_size(FourCC::new(fourcc), (5, 3)).unwrap(),
+                Some(15 + 2 * 6)
+            );
+        }
+    }
+
+    #[test]
+    fn decodes_uniform_yuv_frames_to_the_reference_color() {
+        let red = RgbColor { r: 255, g: 0, b: 0 };
+        for (matrix, range) in [
+            (YuvMatrix::Bt601, YuvRange::Limited),
+            (YuvMatrix::Bt709, YuvRange::Full),
+        ] {
+            let conversion = YuvConversion::new(matrix, range);
+            let registry = DecoderRegistry::new().with_yuv_conversion(conversion);
+            let (y, u, v) = conversion.rgb_to_yuv(red);
+            let expected = conversion.yuv_to_rgb(y, u, v).to_vec();
+            let shape = (5, 3);
+            let yuv420 = [vec![y; 15], vec![u; 6], vec![v; 6]].concat();
+            let nv12 = [vec![y; 15], [u, v].repeat(6)].concat();
+            let yuyv = [y, u, y, v].repeat(8);
+            for (data, fourcc, shape) in [
+                (&yuv420, b"YU12", shape),
+                (&nv12, b"NV12", shape),
+                (&yuyv, b"YUYV", (4, 4)),
+            ] {
+                let image = registry.decode(&frame(data, fourcc, shape)).unwrap();
+                assert_eq!(image.dimensions(), shape);
+                for pixel in image.pixels() {
+                    assert_eq!(pixel.0[..], expected[..], "{} {matrix} {range}", FourCC::new(fourcc));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_short_frames() {
+        let registry = DecoderRegistry::new();
+        let data = vec![0u8; 15 + 2 * 6 - 1];
+        let err = registry.decode(&frame(&data, b"YU12", (5, 3))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn empty_registry_supports_nothing() {
+        let registry = DecoderRegistry::empty();
+        assert!(registry.supported_fourccs().is_empty());
+        let err = registry.decode(&frame(&[0; 3], b"RGB3", (1, 1))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How the visible and the thermal image are combined for display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayMode {
+    /// Thermal image blended over the greyscale visible image
+    #[default]
+    Blend,
+    Visible,
+    Thermal,
+    /// Thermal image as an inset in the corner of the visible image
+    PictureInPicture,
+    /// Visible image left and thermal image right of a movable vertical divider
+    SplitScreen,
+    /// Visible and thermal image next to each other
+    SideBySide,
+    /// Thermal image with the fine structure of the visible image added (MSX-like)
+    EdgeEnhanced,
+    /// Visible image with thermal colors only where the temperature is within a band
+    TemperatureGated,
+    /// Visible image with isolines of the temperature
+    Contours,
+}
+
+impl DisplayMode {
+    /// All modes in the order they are cycled through in the UI.
+    pub const ALL: [DisplayMode; 9] = [
+        DisplayMode::Blend,
+        DisplayMode::Visible,
+        DisplayMode::Thermal,
+        DisplayMode::PictureInPicture,
+        DisplayMode::SplitScreen,
+        DisplayMode::SideBySide,
+        DisplayMode::EdgeEnhanced,
+        DisplayMode::TemperatureGated,
+        DisplayMode::Contours,
+    ];
+
+    fn index(&self) -> usize {
+        DisplayMode::ALL.iter().position(|mode| mode == self).unwrap()
+    }
+
+    /// The following mode, stays at the last mode.
+    pub fn next(&self) -> DisplayMode {
+        DisplayMode::ALL[(self.index() + 1).min(DisplayMode::ALL.len() - 1)]
+    }
+
+    /// The preceding mode, stays at the first mode.
+    pub fn previous(&self) -> DisplayMode {
+        DisplayMode::ALL[self.index().saturating_sub(1)]
+    }
+}
+
+impl FromStr for DisplayMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // the numbers are the former mode values
+        match s.to_lowercase().as_str() {
+            "blend" | "0" => Ok(DisplayMode::Blend),
+            "visible" | "1" => Ok(DisplayMode::Visible),
+            "thermal" | "2" => Ok(DisplayMode::Thermal),
+            "pip" | "picture-in-picture" => Ok(DisplayMode::PictureInPicture),
+            "split" | "split-screen" => Ok(DisplayMode::SplitScreen),
+            "side-by-side" => Ok(DisplayMode::SideBySide),
+            "msx" | "edge-enhanced" => Ok(DisplayMode::EdgeEnhanced),
+            "gated" | "temperature-gated" => Ok(DisplayMode::TemperatureGated),
+            "contours" => Ok(DisplayMode::Contours),
+            _ => Err(format!(
+                "unknown display mode '{s}' (choose blend, visible, thermal, pip, split, side-by-side, msx, gated or contours)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DisplayMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisplayMode::Blend => write!(f, "blend"),
+            DisplayMode::Visible => write!(f, "visible"),
+            DisplayMode::Thermal => write!(f, "thermal"),
+            DisplayMode::PictureInPicture => write!(f, "pip"),
+            DisplayMode::SplitScreen => write!(f, "split"),
+            DisplayMode::SideBySide => write!(f, "side-by-side"),
+            DisplayMode::EdgeEnhanced => write!(f, "msx"),
+            DisplayMode::TemperatureGated => write!(f, "gated"),
+            DisplayMode::Contours => write!(f, "contours"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_and_previous_walk_through_all_modes() {
+        let mut mode = DisplayMode::ALL[0];
+        for &expected in DisplayMode::ALL.iter().skip(1) {
+            mode = mode.next();
+            assert_eq!(mode, expected);
+        }
+        // no wrap-around at either end, as with the former mode buttons
+        assert_eq!(mode.next(), DisplayMode::Contours);
+        for &expected in DisplayMode::ALL.iter().rev().skip(1) {
+            mode = mode.previous();
+            assert_eq!(mode, expected);
+        }
+        assert_eq!(mode.previous(), DisplayMode::Blend);
+    }
+
+    #[test]
+    fn parses_the_former_mode_values() {
+        assert_eq!("0".parse(), Ok(DisplayMode::Blend));
+        assert_eq!("1".parse(), Ok(DisplayMode::Visible));
+        assert_eq!("2".parse(), Ok(DisplayMode::Thermal));
+        assert!("3".parse::<DisplayMode>().is_err());
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for mode in DisplayMode::ALL {
+            assert_eq!(mode.to_string().parse(), Ok(mode));
+        }
+        assert_eq!("Picture-In-Picture".parse(), Ok(DisplayMode::PictureInPicture));
+        assert!("overlay".parse::<DisplayMode>().is_err());
+    }
+}
@@ -0,0 +1,377 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::material::Material;
+use crate::roi::Roi;
+use crate::thermal_frame::ThermalFrame;
+
+pub(crate) const KELVIN: f32 = 273.15;
+/// Lower bound of the emissivity, the compensation diverges towards 0.0.
+pub const MIN_EMISSIVITY: f32 = 0.01;
+
+/// Corrects apparent temperatures (measured with emissivity 1.0, the default of the MLX driver) for the
+/// emissivity of the object and the radiation it reflects from its surroundings.
+///
+/// The sensor sees `ε·σT_obj⁴ + (1 - ε)·σT_refl⁴`, which is solved for T_obj.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EmissivityCompensation {
+    /// Emissivity of the object, MIN_EMISSIVITY-1.0
+    pub emissivity: f32,
+    /// Apparent temperature (°C) of the surroundings reflected by the object
+    pub reflected_temp: f32,
+    /// Material the emissivity was picked from, `None` if it was entered directly
+    pub material: Option<Material>,
+}
+
+impl EmissivityCompensation {
+    pub fn new(emissivity: f32, reflected_temp: f32) -> Self {
+        EmissivityCompensation {
+            emissivity: emissivity.clamp(MIN_EMISSIVITY, 1.0),
+            reflected_temp,
+            material: None,
+        }
+    }
+
+    /// A black body, temperatures are not changed.
+    pub fn black_body() -> Self {
+        EmissivityCompensation::new(1.0, 20.0)
+    }
+
+    pub fn with_emissivity(mut self, emissivity: f32) -> Self {
+        self.emissivity = emissivity.clamp(MIN_EMISSIVITY, 1.0);
+        self.material = None;
+        self
+    }
+
+    /// Uses the emissivity of the material.
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.emissivity = material.emissivity.clamp(MIN_EMISSIVITY, 1.0);
+        self.material = Some(material);
+        self
+    }
+
+    pub fn with_reflected_temp(mut self, reflected_temp: f32) -> Self {
+        self.reflected_temp = reflected_temp;
+        self
+    }
+
+    /// `<setting> = <value>` lines of the emissivity, the reflected temperature and the material (if any), as
+    /// stored by [`EmissivityCompensation::save`].
+    pub fn settings(&self) -> Vec<String> {
+        let mut settings = vec![
+            format!("emissivity = {}", self.emissivity),
+            format!("reflected_temp = {}", self.reflected_temp),
+        ];
+        if let Some(material) = self.material {
+            settings.push(format!("material = {material}"));
+        }
+        settings
+    }
+
+    /// Parses the lines of [`EmissivityCompensation::settings`], missing ones keep the values of a black body.
+    /// Lines starting with `#` are comments.
+    pub fn parse_settings(s: &str) -> Result<Self, String> {
+        let mut compensation = EmissivityCompensation::black_body();
+        let mut material = None;
+        for (line_idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_err = |msg: &str| format!("line {}: {}", line_idx + 1, msg);
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| line_err("expected <setting> = <value>"))?;
+            let value = value.trim();
+            match key.trim() {
+                "emissivity" => compensation.emissivity = parse_emissivity(value).map_err(|err| line_err(&err))?,
+                "reflected_temp" => {
+                    compensation.reflected_temp = value
+                        .parse()
+                        .map_err(|_| line_err(&format!("invalid number '{value}'")))?
+                }
+                "material" => material = Some(value.parse::<Material>().map_err(|err| line_err(&err))?),
+                key => return Err(line_err(&format!("unknown setting '{key}'"))),
+            }
+        }
+        // the emissivity may have been adjusted after picking the material
+        compensation.material = material;
+        Ok(compensation)
+    }
+
+    /// Loads a compensation saved with [`EmissivityCompensation::save`], e.g. next to a recording.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        EmissivityCompensation::parse_settings(&content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut content = String::from("# emissivity compensation of the scene\n");
+        for setting in self.settings() {
+            content.push_str(&setting);
+            content.push('\n');
+        }
+        std::fs::write(path, content)
+    }
+
+    /// Temperature (°C) of the object that appears with `apparent_temp` (°C).
+    pub fn object_temperature(&self, apparent_temp: f32) -> f32 {
+        if self.emissivity >= 1.0 {
+            return apparent_temp;
+        }
+        let apparent = (apparent_temp + KELVIN).powi(4);
+        let reflected = (self.reflected_temp + KELVIN).powi(4);
+        let object = (apparent - (1.0 - self.emissivity) * reflected) / self.emissivity;
+        object.max(0.0).powf(0.25) - KELVIN
+    }
+}
+
+impl Default for EmissivityCompensation {
+    fn default() -> Self {
+        EmissivityCompensation::black_body()
+    }
+}
+
+impl fmt::Display for EmissivityCompensation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(material) = self.material {
+            write!(f, "{material} ")?;
+        }
+        write!(f, "ε{:.2} Tr{:.0}°C", self.emissivity, self.reflected_temp)
+    }
+}
+
+/// Emissivity value within MIN_EMISSIVITY-1.0, e.g. as value parser of a command line argument.
+pub fn parse_emissivity(value: &str) -> Result<f32, String> {
+    let emissivity = value
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| format!("invalid emissivity '{value}'"))?;
+    check_emissivity(emissivity)
+}
+
+fn check_emissivity(emissivity: f32) -> Result<f32, String> {
+    if (MIN_EMISSIVITY..=1.0).contains(&emissivity) {
+        Ok(emissivity)
+    } else {
+        Err(format!("emissivity {emissivity} needs to be {MIN_EMISSIVITY}-1.0"))
+    }
+}
+
+/// Copy of the frame with object temperatures: pixels in a region of interest with an own compensation use it
+/// (the first matching region wins), all others the global compensation.
+pub fn compensate_frame(frame: &ThermalFrame, global: &EmissivityCompensation, rois: &[Roi]) -> ThermalFrame {
+    let mut compensated = frame.clone();
+    let rois: Vec<(&Roi, EmissivityCompensation)> =
+        rois.iter().filter_map(|roi| Some((roi, roi.emissivity?))).collect();
+    for y in 0..frame.height {
+        for x in 0..frame.width {
+            let compensation = rois
+                .iter()
+                .find(|(roi, _)| roi.shape.contains(x as f32 + 0.5, y as f32 + 0.5))
+                .map(|(_, compensation)| compensation)
+                .unwrap_or(global);
+            let value = &mut compensated.data[(y * frame.width + x) as usize];
+            *value = compensation.object_temperature(*value);
+        }
+    }
+    compensated
+}
+
+/// Emissivity of a region of interest: `<name>:<emissivity>` or `<name>:<emissivity>:<reflected temperature>`,
+/// the emissivity is a value or the name of a material.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoiEmissivity {
+    pub name: String,
+    pub emissivity: f32,
+    pub material: Option<Material>,
+    /// `None` to use the global reflected temperature
+    pub reflected_temp: Option<f32>,
+}
+
+impl RoiEmissivity {
+    /// Emissivity of the region, `None` if it uses the global one.
+    pub fn from_roi(roi: &Roi) -> Option<Self> {
+        let compensation = roi.emissivity?;
+        Some(RoiEmissivity {
+            name: roi.name.clone(),
+            emissivity: compensation.emissivity,
+            material: compensation.material,
+            reflected_temp: Some(compensation.reflected_temp),
+        })
+    }
+
+    /// Compensation of the region, with `global_reflected_temp` (°C) if it has no reflected temperature of its own.
+    pub fn compensation(&self, global_reflected_temp: f32) -> EmissivityCompensation {
+        let compensation =
+            EmissivityCompensation::new(self.emissivity, self.reflected_temp.unwrap_or(global_reflected_temp));
+        match self.material {
+            Some(material) => compensation.with_material(material),
+            None => compensation,
+        }
+    }
+}
+
+impl FromStr for RoiEmissivity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(':').collect();
+        let (name, emissivity, reflected_temp) = match fields[..] {
+            [name, emissivity] => (name, emissivity, None),
+            [name, emissivity, reflected_temp] => (name, emissivity, Some(reflected_temp)),
+            _ => {
+                return Err(format!(
+                    "invalid region emissivity '{s}' (expected <name>:<emissivity|material>[:<reflected °C>])"
+                ))
+            }
+        };
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<f32>()
+                .map_err(|_| format!("invalid value '{value}' in region emissivity '{s}'"))
+        };
+        let (emissivity, material) = match Material::builtin(emissivity) {
+            Some(material) => (material.emissivity, Some(material)),
+            None => (parse(emissivity)?, None),
+        };
+        let emissivity = check_emissivity(emissivity).map_err(|err| format!("region '{name}': {err}"))?;
+        Ok(RoiEmissivity {
+            name: name.to_string(),
+            emissivity,
+            material,
+            reflected_temp: reflected_temp.map(parse).transpose()?,
+        })
+    }
+}
+
+impl fmt::Display for RoiEmissivity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.material {
+            Some(material) => write!(f, "{}:{material}", self.name)?,
+            None => write!(f, "{}:{}", self.name, self.emissivity)?,
+        }
+        if let Some(reflected_temp) = self.reflected_temp {
+            write!(f, ":{reflected_temp}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::roi::RoiShape;
+
+    /// Apparent temperature (°C) of an object at `object_temp` (°C), the forward model of the compensation.
+    fn apparent_temperature(compensation: &EmissivityCompensation, object_temp: f32) -> f32 {
+        let object = (object_temp + KELVIN).powi(4);
+        let reflected = (compensation.reflected_temp + KELVIN).powi(4);
+        (compensation.emissivity * object + (1.0 - compensation.emissivity) * reflected).powf(0.25) - KELVIN
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 0.01, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn black_body_is_unchanged() {
+        let compensation = EmissivityCompensation::black_body().with_reflected_temp(80.0);
+        for temp in [-20.0, 0.0, 21.5, 150.0] {
+            assert_eq!(compensation.object_temperature(temp), temp);
+        }
+    }
+
+    #[test]
+    fn inverts_the_radiation_model() {
+        let compensation = EmissivityCompensation::new(0.9, 20.0);
+        // reference value: 50 °C apparent at ε 0.90 and 20 °C reflected
+        assert_close(compensation.object_temperature(50.0), 52.86);
+        for (emissivity, reflected_temp) in [(0.95, 20.0), (0.5, 35.0), (0.1, -5.0)] {
+            let compensation = EmissivityCompensation::new(emissivity, reflected_temp);
+            for object_temp in [0.0, 37.0, 120.0] {
+                let apparent = apparent_temperature(&compensation, object_temp);
+                assert_close(compensation.object_temperature(apparent), object_temp);
+            }
+            // an object at the reflected temperature appears at that temperature
+            assert_close(compensation.object_temperature(reflected_temp), reflected_temp);
+        }
+    }
+
+    #[test]
+    fn regions_override_the_global_emissivity() {
+        let frame = ThermalFrame::new(2, 1, vec![50.0, 50.0]);
+        let global = EmissivityCompensation::new(0.9, 20.0);
+        let roi = Roi::new(
+            "A",
+            RoiShape::Rectangle {
+                x: 1.0,
+                y: 0.0,
+                width: 1.0,
+                height: 1.0,
+            },
+        )
+        .with_emissivity(Some(EmissivityCompensation::black_body()));
+        let compensated = compensate_frame(&frame, &global, &[roi]);
+        assert_close(compensated.data[0], 52.86);
+        assert_eq!(compensated.data[1], 50.0);
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let wood = EmissivityCompensation::default()
+            .with_material(Material::builtin("wood").unwrap())
+            .with_reflected_temp(-3.5);
+        let path = std::env::temp_dir().join(format!("thermocam_emissivity_{}.txt", std::process::id()));
+        wood.save(&path).unwrap();
+        let loaded = EmissivityCompensation::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), wood);
+
+        let value = EmissivityCompensation::new(0.42, 25.0);
+        let parsed = EmissivityCompensation::parse_settings(&value.settings().join("\n"));
+        assert_eq!(parsed, Ok(value));
+        assert!(EmissivityCompensation::parse_settings("emissivity = 1.2").is_err());
+        assert!(EmissivityCompensation::parse_settings("material = cheese").is_err());
+    }
+
+    #[test]
+    fn emissivity_out_of_range_is_rejected() {
+        assert_eq!(parse_emissivity("0.95"), Ok(0.95));
+        assert_eq!(parse_emissivity("1"), Ok(1.0));
+        assert!(parse_emissivity("1.5").is_err());
+        assert!(parse_emissivity("0.0").is_err());
+        assert!(parse_emissivity("-0.5").is_err());
+        assert!(parse_emissivity("high").is_err());
+        assert!("A:1.5".parse::<RoiEmissivity>().is_err());
+        assert_eq!("A:wood:25".parse::<RoiEmissivity>().unwrap().reflected_temp, Some(25.0));
+    }
+
+    #[test]
+    fn region_emissivities_round_trip() {
+        let shape = RoiShape::Rectangle {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+        };
+        let wood = EmissivityCompensation::new(0.5, -3.5).with_material(Material::builtin("wood").unwrap());
+        for compensation in [wood, EmissivityCompensation::new(0.42, 25.0)] {
+            let roi = Roi::new("left-plug", shape.clone()).with_emissivity(Some(compensation));
+            let roi_emissivity = RoiEmissivity::from_roi(&roi).unwrap();
+            let parsed = roi_emissivity.to_string().parse::<RoiEmissivity>();
+            assert_eq!(parsed.as_ref(), Ok(&roi_emissivity));
+            assert_eq!(parsed.unwrap().compensation(80.0), compensation);
+        }
+        assert_eq!(RoiEmissivity::from_roi(&Roi::new("A", shape)), None);
+        assert_eq!(
+            "A:0.9".parse::<RoiEmissivity>().unwrap().compensation(30.0),
+            EmissivityCompensation::new(0.9, 30.0)
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::palette::parse_hex_color;
+use crate::rgb_color::RgbColor;
+
+/// Temperature band that is drawn in a solid color.
+#[derive(Debug, Clone, Copy)]
+pub struct Isotherm {
+    pub min_temp: f32,
+    pub max_temp: f32,
+    pub color: RgbColor,
+}
+
+impl Isotherm {
+    pub fn new(min_temp: f32, max_temp: f32, color: RgbColor) -> Self {
+        Isotherm {
+            min_temp,
+            max_temp,
+            color,
+        }
+    }
+
+    /// Whether the temperature is within the band (limits included).
+    pub fn contains(&self, temp_in_celsius: f32) -> bool {
+        (self.min_temp..=self.max_temp).contains(&temp_in_celsius)
+    }
+}
+
+/// `<min>:<max>:<#rrggbb>`, e.g. `40:60:#ffff00`.
+impl FromStr for Isotherm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(':').collect();
+        let [min_temp, max_temp, color] = fields[..] else {
+            return Err(format!("invalid isotherm '{s}' (expected <min>:<max>:<#rrggbb>)"));
+        };
+        let temperature = |value: &str| {
+            value
+                .trim()
+                .parse::<f32>()
+                .map_err(|_| format!("invalid temperature '{value}'"))
+        };
+        let (min_temp, max_temp) = (temperature(min_temp)?, temperature(max_temp)?);
+        if min_temp > max_temp {
+            return Err(format!("isotherm minimum {min_temp} is above the maximum {max_temp}"));
+        }
+        Ok(Isotherm::new(min_temp, max_temp, parse_hex_color(color.trim())?))
+    }
+}
+
+impl fmt::Display for Isotherm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:#{:02x}{:02x}{:02x}",
+            self.min_temp, self.max_temp, self.color.r, self.color.g, self.color.b
+        )
+    }
+}
+
+/// Area of a frame covered by an isotherm.
+#[derive(Debug, Clone, Copy)]
+pub struct IsothermArea {
+    pub isotherm: Isotherm,
+    /// Number of pixels of the upscaled frame within the band
+    pub pixel_count: usize,
+    /// Share of the frame within the band (0.0-100.0)
+    pub percentage: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bands() {
+        let isotherm: Isotherm = "40:60.5:#ffa000".parse().unwrap();
+        assert_eq!((isotherm.min_temp, isotherm.max_temp), (40.0, 60.5));
+        assert_eq!(
+            [isotherm.color.r, isotherm.color.g, isotherm.color.b],
+            [0xff, 0xa0, 0x00]
+        );
+        assert_eq!(isotherm.to_string(), "40:60.5:#ffa000");
+        assert_eq!(
+            " -10 : -5 : 00ff00 ".parse::<Isotherm>().unwrap().to_string(),
+            "-10:-5:#00ff00"
+        );
+        // a single temperature
+        assert!("37:37:#ff0000".parse::<Isotherm>().is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_bands() {
+        for (definition, error) in [
+            ("60:40:#ffff00", "isotherm minimum 60 is above the maximum 40"),
+            ("40:60:#ffff0", "invalid hex color '#ffff0'"),
+            ("40:60:#yellow", "invalid hex color '#yellow'"),
+            ("40:warm:#ffff00", "invalid temperature 'warm'"),
+            ("40:60", "invalid isotherm '40:60' (expected <min>:<max>:<#rrggbb>)"),
+            (
+                "40:50:60:#ffff00",
+                "invalid isotherm '40:50:60:#ffff00' (expected <min>:<max>:<#rrggbb>)",
+            ),
+        ] {
+            assert_eq!(definition.parse::<Isotherm>().unwrap_err(), error);
+        }
+    }
+
+    #[test]
+    fn band_includes_its_limits() {
+        let isotherm = Isotherm::new(40.0, 60.0, RgbColor { r: 255, g: 255, b: 0 });
+        assert!(isotherm.contains(40.0));
+        assert!(isotherm.contains(50.0));
+        assert!(isotherm.contains(60.0));
+        assert!(!isotherm.contains(39.99));
+        assert!(!isotherm.contains(60.01));
+        assert!(!isotherm.contains(f32::NAN));
+    }
+}
@@ -1,145 +1,217 @@
+pub mod atmosphere;
+pub mod auto_alignment;
+pub mod color_conversion;
+pub mod contour;
+pub mod coordinate_mapper;
+pub mod decoder;
+pub mod display_mode;
+pub mod emissivity;
+pub mod isotherm;
+pub mod line_profile;
+pub mod material;
+pub mod orientation;
+pub mod palette;
+pub mod registration;
 pub mod rgb_color;
+pub mod roi;
+pub mod spot_meter;
+pub mod temperature_field;
 pub mod temperature_pixel;
+pub mod temporal_filter;
+pub mod thermal_frame;
+pub mod thermal_source;
 pub mod thermo_image_processing;
-
-use std::fs::File;
-use std::io::Read;
+pub mod visible_source;
 
 use image;
-use image::imageops::FilterType;
-
-use bayer;
-
-#[cfg(not(target_arch = "arm"))]
-use npyz;
-
-use linux_embedded_hal::I2cdev;
-use mlx9064x;
-use mlx9064x::Mlx90640Driver;
-use std::thread::sleep;
-use std::time::Duration;
 
+use color_conversion::clamp_to_u8;
+use coordinate_mapper::{CoordinateMapper, CoordinateSpace};
+use display_mode::DisplayMode;
+use isotherm::IsothermArea;
+use registration::Registration;
 use rgb_color::RgbColor;
+use spot_meter::DeltaReading;
+use temperature_field::TemperatureField;
 use temperature_pixel::TemperaturPixel;
+use thermal_frame::{FrameStats, ProcessedThermalFrame, ThermalFrame};
 use thermo_image_processing::ThermoImageProcessor;
 
-const FACTOR_10BIT_TO_8BIT: f32 = 255.0 / 1024.0;
-
-pub fn get_thermo_image_raw_data(
-    use_simulation_data: bool,
-    shape: &mut (u32, u32),
-    mlx_sensor_data: &mut Vec<f32>,
-    sensor: &mut Option<Mlx90640Driver<I2cdev>>,
-    period: u64,
-) {
-    if use_simulation_data {
-        get_thermo_simulation_data(shape, mlx_sensor_data);
-        sleep(Duration::from_millis(period));
-    } else {
-        match sensor {
-            Some(sensor) => {
-                sensor.generate_image_if_ready(mlx_sensor_data).unwrap();
-                sleep(Duration::from_millis(period));
-                sensor.generate_image_if_ready(mlx_sensor_data).unwrap();
-            }
-            None => panic!("no sensor available in non-simulation mode"),
-        }
-    }
-}
-
-fn get_thermo_simulation_data(shape: &mut (u32, u32), mlx_sensor_data: &mut Vec<f32>) {
-    #[cfg(not(target_arch = "arm"))]
-    {
-        let bytes = std::fs::read("data/flir_f32.npy").unwrap();
-        let reader = npyz::NpyFile::new(&bytes[..]).unwrap();
-        let shape_vec = reader.shape().to_vec();
-        *shape = (shape_vec[0] as u32, shape_vec[1] as u32);
-        *mlx_sensor_data = reader.into_vec::<f32>().unwrap();
-    }
-}
-
-pub fn get_camera_simulation_data(sim_data_buffer: &mut [u8; 384000]) {
-    let mut f = File::open("data/received_image_data.bin").unwrap();
-    f.read(sim_data_buffer).unwrap();
-}
-
-pub fn process_raw_thermo_image_data(
-    mlx_sensor_data: &Vec<f32>,
-    mlx_sensor_data_shape: (u32, u32),
-    settings: &ThermoImageProcessor,
-) -> (
-    TemperaturPixel,
-    TemperaturPixel,
-    f32,
-    image::ImageBuffer<image::Rgb<u8>, Vec<u8>>,
-) {
-    let mut rgb_thermo_data: Vec<u8> =
-        Vec::with_capacity((3 * mlx_sensor_data_shape.0 * mlx_sensor_data_shape.1) as usize);
-    let mut max_pixel = TemperaturPixel {
-        x: 0,
-        y: 0,
-        value: settings.manual_scale_min_temp,
-    };
-    let mut min_pixel = TemperaturPixel {
-        x: 0,
-        y: 0,
-        value: settings.manual_scale_max_temp,
-    };
-    let mut mean_temperature = 0.0;
-    for (i, &temp_in_celsius) in mlx_sensor_data.iter().enumerate() {
-        let row = i as u32 / mlx_sensor_data_shape.1;
-        let col = i as u32 % mlx_sensor_data_shape.1;
-
-        if temp_in_celsius <= min_pixel.value {
-            min_pixel.value = temp_in_celsius;
-            min_pixel.x = col;
-            min_pixel.y = row;
-        }
-        if temp_in_celsius >= max_pixel.value {
-            max_pixel.value = temp_in_celsius;
-            max_pixel.x = col;
-            max_pixel.y = row;
-        }
-        mean_temperature += temp_in_celsius;
-    }
-    mean_temperature /= mlx_sensor_data.len() as f32;
+/// Interval (°C) of the isolines in contour mode if none is configured.
+pub const DEFAULT_CONTOUR_INTERVAL: f32 = 2.0;
+/// Color of the outlines and hotspots of regions of interest.
+pub const ROI_COLOR: RgbColor = RgbColor { r: 0, g: 255, b: 255 };
+/// Color of the markers of spot meters.
+pub const SPOT_COLOR: RgbColor = RgbColor { r: 255, g: 0, b: 255 };
+/// Color of the line of the line profile.
+pub const PROFILE_COLOR: RgbColor = RgbColor { r: 255, g: 255, b: 0 };
+
+pub fn process_raw_thermo_image_data(frame: &ThermalFrame, settings: &ThermoImageProcessor) -> ProcessedThermalFrame {
+    // everything below works on object temperatures: the air in between is removed first, then the emissivity
+    let frame = &atmosphere::compensate_frame(frame, &settings.atmosphere);
+    let frame = &emissivity::compensate_frame(frame, &settings.emissivity, &settings.rois);
+    let stats = FrameStats::from_frame(frame);
     let min_temp;
     let max_temp;
     if !settings.autoscale_enabled {
         min_temp = settings.manual_scale_min_temp;
         max_temp = settings.manual_scale_max_temp;
     } else {
-        min_temp = min_pixel.value;
-        max_temp = max_pixel.value;
+        min_temp = stats.min.value;
+        max_temp = stats.max.value;
     }
-    for &temp_in_celsius in mlx_sensor_data.iter() {
-        let fraction = normalize(min_temp, max_temp, temp_in_celsius);
-        let interpolated_color = RgbColor::lerp(settings.min_temp_color, settings.max_temp_color, fraction);
-        rgb_thermo_data.extend(interpolated_color.to_vec());
-    }
-    let img = image::RgbImage::from_raw(mlx_sensor_data_shape.1, mlx_sensor_data_shape.0, rgb_thermo_data).unwrap();
 
+    // upscale the temperatures (not the colors), so every displayed pixel carries a temperature
     let interpolation_factor = settings.interpolation_factor;
-    let mut upscaled_image = image::imageops::resize(
-        &img,
-        img.width() * interpolation_factor,
-        img.height() * interpolation_factor,
-        FilterType::Lanczos3,
+    let temperature_field = frame
+        .to_temperature_field()
+        .upscale(interpolation_factor, settings.upscale_filter);
+
+    let mut isotherm_pixel_counts = vec![0usize; settings.isotherms.len()];
+    let mut rgb_thermo_data: Vec<u8> = Vec::with_capacity(3 * temperature_field.data.len());
+    for &temp_in_celsius in temperature_field.data.iter() {
+        // the first matching isotherm determines the color
+        let mut isotherm_color = None;
+        for (isotherm, pixel_count) in settings.isotherms.iter().zip(isotherm_pixel_counts.iter_mut()) {
+            if isotherm.contains(temp_in_celsius) {
+                *pixel_count += 1;
+                isotherm_color = isotherm_color.or(Some(isotherm.color));
+            }
+        }
+        let color = isotherm_color.unwrap_or_else(|| {
+            let fraction = normalize(min_temp, max_temp, temp_in_celsius);
+            settings.palette.color_at(fraction)
+        });
+        rgb_thermo_data.extend(color.to_vec());
+    }
+    let isotherm_areas = settings
+        .isotherms
+        .iter()
+        .zip(isotherm_pixel_counts)
+        .map(|(isotherm, pixel_count)| IsothermArea {
+            isotherm: *isotherm,
+            pixel_count,
+            percentage: 100.0 * pixel_count as f32 / temperature_field.data.len() as f32,
+        })
+        .collect();
+    let upscaled_image =
+        image::RgbImage::from_raw(temperature_field.width, temperature_field.height, rgb_thermo_data).unwrap();
+
+    // isolines, the contour mode always needs them
+    let contour_interval = match (settings.contour_interval, settings.mode) {
+        (None, DisplayMode::Contours) => Some(DEFAULT_CONTOUR_INTERVAL),
+        (contour_interval, _) => contour_interval,
+    };
+    let contours: Vec<_> = contour_interval
+        .map(|interval| {
+            contour::contour_segments(&temperature_field, interval)
+                .into_iter()
+                .map(|segment| segment.in_sensor_coordinates(interpolation_factor))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // measurements stay in sensor coordinates, the image is oriented and the markers are mapped onto it
+    let mut upscaled_image = settings.thermal_orientation.apply_to_image(upscaled_image);
+    let mapper = CoordinateMapper::for_thermal_image(
+        (frame.width, frame.height),
+        (upscaled_image.width(), upscaled_image.height()),
+    )
+    .with_thermal_orientation(settings.thermal_orientation);
+    if settings.contour_interval.is_some() {
+        contour::draw_contours_into_image(
+            &contours,
+            &mapper,
+            |_| RgbColor { r: 0, g: 0, b: 0 },
+            &mut upscaled_image,
+        );
+    }
+
+    draw_pixel_marker_into_image(
+        &stats.min,
+        &mapper,
+        RgbColor { r: 0, g: 255, b: 0 },
+        &mut upscaled_image,
+    );
+    draw_pixel_marker_into_image(
+        &stats.max,
+        &mapper,
+        RgbColor { r: 255, g: 255, b: 255 },
+        &mut upscaled_image,
     );
 
-    let x = min_pixel.x * interpolation_factor + interpolation_factor / 2;
-    let y = min_pixel.y * interpolation_factor + interpolation_factor / 2;
-    draw_cross_into_image(x, y, RgbColor { r: 0, g: 255, b: 0 }, &mut upscaled_image);
+    let roi_stats = settings
+        .rois
+        .iter()
+        .filter_map(|roi| roi.stats(frame))
+        .collect::<Vec<_>>();
+    roi::draw_roi_outlines_into_image(&settings.rois, &mapper, ROI_COLOR, &mut upscaled_image);
+    for roi_stats in roi_stats.iter() {
+        draw_pixel_marker_into_image(&roi_stats.stats.max, &mapper, ROI_COLOR, &mut upscaled_image);
+    }
 
-    let x = max_pixel.x * interpolation_factor + interpolation_factor / 2;
-    let y = max_pixel.y * interpolation_factor + interpolation_factor / 2;
-    draw_cross_into_image(x, y, RgbColor { r: 255, g: 255, b: 255 }, &mut upscaled_image);
+    let spot_readings = settings
+        .spot_meters
+        .iter()
+        .filter_map(|spot| spot.reading(frame))
+        .collect::<Vec<_>>();
+    spot_meter::draw_spot_markers_into_image(&settings.spot_meters, &mapper, SPOT_COLOR, &mut upscaled_image);
+    let delta_readings = settings
+        .temperature_deltas
+        .iter()
+        .filter_map(|delta| {
+            Some(DeltaReading {
+                delta: delta.clone(),
+                difference: delta.difference(&spot_readings, &roi_stats)?,
+            })
+        })
+        .collect();
+
+    // sampled on the upscaled temperatures, so the profile matches the displayed image
+    let profile_samples = match &settings.line_profile {
+        Some(line_profile) => {
+            line_profile::draw_profile_line_into_image(line_profile, &mapper, PROFILE_COLOR, &mut upscaled_image);
+            line_profile.samples(&temperature_field, interpolation_factor)
+        }
+        None => Vec::new(),
+    };
+
+    ProcessedThermalFrame {
+        stats,
+        image: upscaled_image,
+        temperature_field: settings.thermal_orientation.apply_to_field(&temperature_field),
+        isotherm_areas,
+        contours,
+        roi_stats,
+        spot_readings,
+        delta_readings,
+        profile_samples,
+    }
+}
 
-    (max_pixel, min_pixel, mean_temperature, upscaled_image)
+/// Position of current_temp within min_temp..max_temp (0.0-1.0 inside the range), 0.5 for an empty range (e.g. a
+/// uniform scene).
+pub fn normalize(min_temp: f32, max_temp: f32, current_temp: f32) -> f32 {
+    if max_temp > min_temp {
+        (current_temp - min_temp) / (max_temp - min_temp)
+    } else {
+        0.5
+    }
 }
 
-fn normalize(min_temp: f32, max_temp: f32, current_temp: f32) -> f32 {
-    (current_temp - min_temp) / (max_temp - min_temp)
+/// Draws a cross at the center of the sensor pixel into the displayed image of the mapper.
+fn draw_pixel_marker_into_image(
+    pixel: &TemperaturPixel,
+    mapper: &CoordinateMapper,
+    color: RgbColor,
+    image: &mut image::RgbImage,
+) {
+    let center = (pixel.x as f32 + 0.5, pixel.y as f32 + 0.5);
+    if let Some((x, y)) = mapper.map_point(center, CoordinateSpace::Thermal, CoordinateSpace::Display) {
+        if mapper.contains((x, y), CoordinateSpace::Display) {
+            draw_cross_into_image(x as u32, y as u32, color, image);
+        }
+    }
 }
 
 fn draw_cross_into_image(
@@ -159,10 +231,10 @@ fn draw_cross_into_image(
     if x >= 2 {
         upscaled_image.put_pixel(x - 2, y, px);
     }
-    if x < img_width {
+    if x + 1 < img_width {
         upscaled_image.put_pixel(x + 1, y, px);
     }
-    if x < img_width - 1 {
+    if x + 2 < img_width {
         upscaled_image.put_pixel(x + 2, y, px);
     }
     upscaled_image.put_pixel(x, y, px);
@@ -172,33 +244,40 @@ fn draw_cross_into_image(
     if y >= 2 {
         upscaled_image.put_pixel(x, y - 2, px);
     }
-    if y < img_height {
+    if y + 1 < img_height {
         upscaled_image.put_pixel(x, y + 1, px);
     }
-    if y < img_height - 1 {
+    if y + 2 < img_height {
         upscaled_image.put_pixel(x, y + 2, px);
     }
 }
 
 /// Blends two images of different sizes.
 /// The parameter foreground alpha (0.0-1.0) determines how much influence image1 has to result.
-/// Output size is determined by image1. image1 is converted to grayscale.
-pub fn blend_images_of_different_sizes(image1: &mut image::RgbImage, image2: &image::RgbImage, foreground_alpha: f32) {
-    let img1_width = image1.width() as f32;
-    let img1_height = image1.height() as f32;
-    let img2_width = image2.width() as f32;
-    let img2_height = image2.height() as f32;
+/// Output size is determined by image1. image1 is converted to grayscale. image2 is placed according to the
+/// registration, pixels outside of image2 stay grayscale.
+pub fn blend_images_of_different_sizes(
+    image1: &mut image::RgbImage,
+    image2: &image::RgbImage,
+    foreground_alpha: f32,
+    registration: &Registration,
+) {
+    let img1_width = image1.width();
+    let img1_height = image1.height();
 
     for (x, y, rgb_px) in image1.enumerate_pixels_mut() {
-        let sample_image2_x = ((x as f32 / img1_width) * img2_width) as u32;
-        let sample_image2_y = ((y as f32 / img1_height) * img2_height) as u32;
-
-        let image2_sample = image2.get_pixel(sample_image2_x, sample_image2_y);
-
         // luminance greyscale
         let mut image1_greyscale = 0.3 * rgb_px.0[0] as f32 + 0.59 * rgb_px.0[1] as f32 + 0.11 * rgb_px.0[2] as f32;
         image1_greyscale = clamp_to_u8(image1_greyscale);
 
+        let image2_sample = match registered_sample(image2, registration, x, y, img1_width, img1_height) {
+            Some(sample) => sample,
+            None => {
+                *rgb_px = image::Rgb([image1_greyscale as u8; 3]);
+                continue;
+            }
+        };
+
         let blended_r = (image2_sample.0[0] as f32 * foreground_alpha) + (image1_greyscale * (1.0 - foreground_alpha));
         let blended_g = (image2_sample.0[1] as f32 * foreground_alpha) + (image1_greyscale * (1.0 - foreground_alpha));
         let blended_b = (image2_sample.0[2] as f32 * foreground_alpha) + (image1_greyscale * (1.0 - foreground_alpha));
@@ -209,123 +288,495 @@ pub fn blend_images_of_different_sizes(image1: &mut image::RgbImage, image2: &im
     }
 }
 
-pub fn sgrbg10p_to_rgb(raw_camera_buffer: &[u8], camera_image_shape: (u32, u32), resulting_rgb_buffer: &mut [u8]) {
-    // convert 10-bit bayer to 16 bit bayer
-    let raw_camera_buffer_size = (camera_image_shape.0 * camera_image_shape.1) as usize;
-    let bayer_buffer_size = (raw_camera_buffer_size as f32 * 1.25) as usize;
-    let mut bayer_buffer = vec![0u8; bayer_buffer_size as usize];
-
-    for (raw_idx, bay_idx) in (0..bayer_buffer_size)
-        .step_by(5)
-        .zip((0..raw_camera_buffer_size).step_by(4))
-    {
-        // unpack pixels
-        let raw_cam_buf_offset_4 = raw_camera_buffer[raw_idx + 4];
-        let pix1 = (raw_camera_buffer[raw_idx] as u16) << 2 | (raw_cam_buf_offset_4 & 3) as u16;
-        let pix2 = (raw_camera_buffer[raw_idx + 1] as u16) << 2 | ((raw_cam_buf_offset_4 >> 2) & 3) as u16;
-        let pix3 = (raw_camera_buffer[raw_idx + 2] as u16) << 2 | ((raw_cam_buf_offset_4 >> 4) & 3) as u16;
-        let pix4 = (raw_camera_buffer[raw_idx + 3] as u16) << 2 | ((raw_cam_buf_offset_4 >> 6) & 3) as u16;
-
-        // convert 10-bit values to 8-bit
-        bayer_buffer[bay_idx] = (pix1 as f32 * FACTOR_10BIT_TO_8BIT) as u8;
-        bayer_buffer[bay_idx + 1] = (pix2 as f32 * FACTOR_10BIT_TO_8BIT) as u8;
-        bayer_buffer[bay_idx + 2] = (pix3 as f32 * FACTOR_10BIT_TO_8BIT) as u8;
-        bayer_buffer[bay_idx + 3] = (pix4 as f32 * FACTOR_10BIT_TO_8BIT) as u8;
-    }
-
-    // debayer
-    let raster_depth = bayer::RasterDepth::Depth8;
-    let mut dst = bayer::RasterMut::new(
-        camera_image_shape.0 as usize,
-        camera_image_shape.1 as usize,
-        raster_depth,
-        resulting_rgb_buffer,
-    );
-    let color_filter_array = bayer::CFA::GBRG; // SGRBG10P
-    let demosaic_algorithm = bayer::Demosaic::Linear;
-
-    bayer::run_demosaic(
-        &mut bayer_buffer.as_slice(),
-        bayer::BayerDepth::Depth8,
-        color_filter_array,
-        demosaic_algorithm,
-        &mut dst,
-    )
-    .unwrap()
+/// Pixel of image2 that the registration maps pixel (x, y) of an image with the given size to, `None` outside of
+/// image2.
+fn registered_sample(
+    image2: &image::RgbImage,
+    registration: &Registration,
+    x: u32,
+    y: u32,
+    img1_width: u32,
+    img1_height: u32,
+) -> Option<image::Rgb<u8>> {
+    let (sample_image2_x, sample_image2_y) = registered_position(
+        (image2.width(), image2.height()),
+        registration,
+        x,
+        y,
+        img1_width,
+        img1_height,
+    )?;
+    Some(*image2.get_pixel(sample_image2_x, sample_image2_y))
+}
+
+/// Position in an image of size image2_shape that the registration maps pixel (x, y) of an image with the given
+/// size to, `None` outside of image2.
+fn registered_position(
+    image2_shape: (u32, u32),
+    registration: &Registration,
+    x: u32,
+    y: u32,
+    img1_width: u32,
+    img1_height: u32,
+) -> Option<(u32, u32)> {
+    let (u, v) = registration.map(
+        (x as f32 + 0.5) / img1_width as f32,
+        (y as f32 + 0.5) / img1_height as f32,
+    )?;
+    if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+        return None;
+    }
+    let (img2_width, img2_height) = image2_shape;
+    Some((
+        ((u * img2_width as f32) as u32).min(img2_width - 1),
+        ((v * img2_height as f32) as u32).min(img2_height - 1),
+    ))
 }
 
-pub fn yuyv_to_rgb(yuyv_buffer: &[u8], yuyv_shape: (u32, u32), cam_rgb: &mut [u8]) {
-    // from https://gist.github.com/wlhe/fcad2999ceb4a826bd811e9fdb6fe652
-    let yuyv_buf_size: usize = yuyv_shape.0 as usize * yuyv_shape.1 as usize * 2;
-    let mut rgb_idx_offset = 0;
+/// Shows image2 only where the temperature is within gate_min_temp..gate_max_temp and image1 everywhere else.
+/// The alpha of image2 fades in over the outer 10 % of the band, so the gate has soft edges.
+/// temperature_field has to have the size of image2. Output size is determined by image1. image2 is placed
+/// according to the registration.
+pub fn gate_images_of_different_sizes(
+    image1: &mut image::RgbImage,
+    image2: &image::RgbImage,
+    temperature_field: &TemperatureField,
+    gate_min_temp: f32,
+    gate_max_temp: f32,
+    registration: &Registration,
+) {
+    assert_eq!(
+        (temperature_field.width, temperature_field.height),
+        image2.dimensions(),
+        "temperature field and gated image need to have the same size"
+    );
+    let img1_width = image1.width();
+    let img1_height = image1.height();
+    let feather = (gate_max_temp - gate_min_temp) * 0.1;
 
-    for yuyv_idx in (0..yuyv_buf_size).step_by(4) {
-        let y = yuyv_buffer[yuyv_idx] as i32; // y0
-        let u = yuyv_buffer[yuyv_idx + 1] as i32; // u0
-        let v = yuyv_buffer[yuyv_idx + 3] as i32; // v0
+    for (x, y, rgb_px) in image1.enumerate_pixels_mut() {
+        let (sample_image2_x, sample_image2_y) = match registered_position(
+            (image2.width(), image2.height()),
+            registration,
+            x,
+            y,
+            img1_width,
+            img1_height,
+        ) {
+            Some(position) => position,
+            None => continue,
+        };
+        let temp_in_celsius =
+            temperature_field.data[(sample_image2_y * temperature_field.width + sample_image2_x) as usize];
+        let alpha = if feather > 0.0 {
+            ((temp_in_celsius - gate_min_temp) / feather).clamp(0.0, 1.0)
+                * ((gate_max_temp - temp_in_celsius) / feather).clamp(0.0, 1.0)
+        } else if (gate_min_temp..=gate_max_temp).contains(&temp_in_celsius) {
+            1.0
+        } else {
+            0.0
+        };
+        if alpha <= 0.0 {
+            continue;
+        }
+        let image2_sample = image2.get_pixel(sample_image2_x, sample_image2_y);
+        for channel in 0..3 {
+            rgb_px.0[channel] =
+                (image2_sample.0[channel] as f32 * alpha + rgb_px.0[channel] as f32 * (1.0 - alpha)) as u8;
+        }
+    }
+}
 
-        let r = y as f32 + 1.4065 * (v - 128) as f32; // r0
-        let g = y as f32 - 0.3455 * (v - 128) as f32 - 0.7169 * (v - 128) as f32; // g0
-        let b = y as f32 + 1.1790 * (u - 128) as f32; // b0
+/// Non-negative edge strength, e.g. as value parser of a command line argument.
+pub fn parse_edge_strength(value: &str) -> Result<f32, String> {
+    let edge_strength = value
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| format!("invalid edge strength '{value}'"))?;
+    if edge_strength >= 0.0 {
+        Ok(edge_strength)
+    } else {
+        Err(format!("edge strength {edge_strength} needs to be 0 or above"))
+    }
+}
 
-        cam_rgb[0 + rgb_idx_offset] = r as u8;
-        cam_rgb[1 + rgb_idx_offset] = g as u8;
-        cam_rgb[2 + rgb_idx_offset] = b as u8;
+/// Adds the fine structure (high-pass filtered luminance) of image1 onto image2, similar to MSX of commercial
+/// cameras. The parameter edge_strength scales the added detail (0.0 shows image2 only).
+/// Output size is determined by image1. image2 is placed according to the registration, pixels outside of image2
+/// show the detail only.
+pub fn enhance_edges_of_different_sizes(
+    image1: &mut image::RgbImage,
+    image2: &image::RgbImage,
+    edge_strength: f32,
+    registration: &Registration,
+) {
+    const BLUR_RADIUS: usize = 2;
+    let img1_width = image1.width();
+    let img1_height = image1.height();
+    let detail = high_pass_luminance(image1, BLUR_RADIUS);
 
-        let u = yuyv_buffer[yuyv_idx + 1] as i32; // y1
-        let y = yuyv_buffer[yuyv_idx + 2] as i32; // u1
-        let v = yuyv_buffer[yuyv_idx + 3] as i32; // v1
+    for (x, y, rgb_px) in image1.enumerate_pixels_mut() {
+        let image2_sample =
+            registered_sample(image2, registration, x, y, img1_width, img1_height).unwrap_or(image::Rgb([128; 3]));
+        let pixel_detail = edge_strength * detail[(y * img1_width + x) as usize];
+        // rounded, the detail of flat areas is only almost zero
+        rgb_px.0[0] = clamp_to_u8((image2_sample.0[0] as f32 + pixel_detail).round()) as u8;
+        rgb_px.0[1] = clamp_to_u8((image2_sample.0[1] as f32 + pixel_detail).round()) as u8;
+        rgb_px.0[2] = clamp_to_u8((image2_sample.0[2] as f32 + pixel_detail).round()) as u8;
+    }
+}
 
-        let mut r = y as f32 + 1.4065 * (v - 128) as f32; // r1
-        let mut g = y as f32 - 0.3455 * (v - 128) as f32 - 0.7169 * (v - 128) as f32; // g1
-        let mut b = y as f32 + 1.1790 * (u - 128) as f32; // b1
+/// Luminance minus its box blurred version (separable, edges are clamped), row-major.
+fn high_pass_luminance(image: &image::RgbImage, radius: usize) -> Vec<f32> {
+    let width = image.width() as usize;
+    let height = image.height() as usize;
+    let luminance: Vec<f32> = image
+        .pixels()
+        .map(|px| 0.3 * px.0[0] as f32 + 0.59 * px.0[1] as f32 + 0.11 * px.0[2] as f32)
+        .collect();
+    let window = (2 * radius + 1) as f32;
+
+    let mut horizontal = vec![0f32; luminance.len()];
+    for y in 0..height {
+        let row = &luminance[y * width..(y + 1) * width];
+        for x in 0..width {
+            let sum: f32 = (0..2 * radius + 1)
+                .map(|k| row[(x + k).saturating_sub(radius).min(width - 1)])
+                .sum();
+            horizontal[y * width + x] = sum / window;
+        }
+    }
+    let mut detail = luminance;
+    for y in 0..height {
+        for x in 0..width {
+            let sum: f32 = (0..2 * radius + 1)
+                .map(|k| horizontal[(y + k).saturating_sub(radius).min(height - 1) * width + x])
+                .sum();
+            detail[y * width + x] -= sum / window;
+        }
+    }
+    detail
+}
 
-        r = clamp_to_u8(r);
-        g = clamp_to_u8(g);
-        b = clamp_to_u8(b);
+/// Draws image2 as an inset into the upper right corner of image1.
+/// The parameter inset_fraction (0.0-1.0) is the width of the inset relative to image1, the aspect ratio of image2
+/// is kept.
+pub fn draw_picture_in_picture(image1: &mut image::RgbImage, image2: &image::RgbImage, inset_fraction: f32) {
+    let (inset_x, inset_width, inset_height) = match picture_in_picture_inset(
+        (image1.width(), image1.height()),
+        (image2.width(), image2.height()),
+        inset_fraction,
+    ) {
+        Some(inset) => inset,
+        None => return,
+    };
 
-        cam_rgb[3 + rgb_idx_offset] = r as u8;
-        cam_rgb[4 + rgb_idx_offset] = g as u8;
-        cam_rgb[5 + rgb_idx_offset] = b as u8;
+    let border_px = image::Rgb([255, 255, 255]);
+    for y in 0..inset_height {
+        for x in 0..inset_width {
+            let is_border = x < INSET_BORDER_WIDTH
+                || y < INSET_BORDER_WIDTH
+                || x >= inset_width - INSET_BORDER_WIDTH
+                || y >= inset_height - INSET_BORDER_WIDTH;
+            let px = if is_border {
+                border_px
+            } else {
+                let sample_image2_x = ((x as f32 / inset_width as f32) * image2.width() as f32) as u32;
+                let sample_image2_y = ((y as f32 / inset_height as f32) * image2.height() as f32) as u32;
+                *image2.get_pixel(sample_image2_x, sample_image2_y)
+            };
+            image1.put_pixel(inset_x + x, y, px);
+        }
+    }
+}
 
-        rgb_idx_offset += 6;
+const INSET_BORDER_WIDTH: u32 = 1;
+
+/// Horizontal position, width and height of the inset of `draw_picture_in_picture`, `None` if it is too small to
+/// be drawn.
+pub(crate) fn picture_in_picture_inset(
+    image1_shape: (u32, u32),
+    image2_shape: (u32, u32),
+    inset_fraction: f32,
+) -> Option<(u32, u32, u32)> {
+    let inset_width = ((image1_shape.0 as f32 * inset_fraction.clamp(0.0, 1.0)) as u32).min(image1_shape.0);
+    let inset_height =
+        ((inset_width as f32 / image2_shape.0 as f32 * image2_shape.1 as f32) as u32).min(image1_shape.1);
+    if inset_width <= 2 * INSET_BORDER_WIDTH || inset_height <= 2 * INSET_BORDER_WIDTH {
+        return None;
     }
+    Some((image1_shape.0 - inset_width, inset_width, inset_height))
 }
 
-pub fn yuv420_to_rgb(buf: &[u8], shape: (u32, u32)) -> image::ImageBuffer<image::Rgb<u8>, Vec<u8>> {
-    let step: u32 = shape.0;
-    let size: usize = shape.0 as usize * shape.1 as usize;
-    let mut cam_rgb = vec![0u8; size * 3];
-    for y_coo in 0..shape.1 {
-        for x_coo in 0..shape.0 {
-            let offset = (y_coo * step + x_coo) as usize;
-            let y: f32 = buf[offset] as f32;
-            let u: f32 = buf[(size as u32 + (y_coo / 2) * (step / 2) + x_coo / 2) as usize] as f32;
-            let v: f32 = buf[((size as f32 * 1.125) as u32 + (y_coo / 2) * (step / 2) + x_coo / 2) as usize] as f32;
-
-            let mut r: f32 = y + 1.402 * (v - 128.0);
-            let mut g: f32 = y - 0.344 * (u - 128.0) - 0.714 * (v - 128.0);
-            let mut b: f32 = y + 1.772 * (u - 128.0);
-
-            r = clamp_to_u8(r);
-            g = clamp_to_u8(g);
-            b = clamp_to_u8(b);
-
-            cam_rgb[(y_coo * step + x_coo) as usize] = r as u8;
-            cam_rgb[(y_coo * step + x_coo + 1) as usize] = g as u8;
-            cam_rgb[(y_coo * step + x_coo + 2) as usize] = b as u8;
+/// Replaces image1 right of a vertical divider with image2.
+/// The parameter divider_position (0.0-1.0) is the position of the divider relative to the width of image1.
+/// Output size is determined by image1. image2 is placed according to the registration.
+pub fn split_images_of_different_sizes(
+    image1: &mut image::RgbImage,
+    image2: &image::RgbImage,
+    divider_position: f32,
+    registration: &Registration,
+) {
+    let img1_width = image1.width();
+    let img1_height = image1.height();
+    let divider_x = (img1_width as f32 * divider_position.clamp(0.0, 1.0)) as u32;
+
+    for (x, y, rgb_px) in image1.enumerate_pixels_mut() {
+        if x == divider_x {
+            *rgb_px = image::Rgb([255, 255, 255]);
+        } else if x > divider_x {
+            *rgb_px =
+                registered_sample(image2, registration, x, y, img1_width, img1_height).unwrap_or(image::Rgb([0, 0, 0]));
         }
     }
-    let img = image::RgbImage::from_raw(shape.0, shape.1, cam_rgb).unwrap();
-    img
 }
 
-fn clamp_to_u8(value: f32) -> f32 {
-    if value < 0.0 {
-        0.0
-    } else if value > 255.0 {
-        255.0
-    } else {
-        value
+/// Places image1 and image2 next to each other, image2 is scaled to the size of image1.
+pub fn images_side_by_side(image1: &image::RgbImage, image2: &image::RgbImage) -> image::RgbImage {
+    let img1_width = image1.width();
+    let img1_height = image1.height();
+    let mut combined = image::RgbImage::new(2 * img1_width, img1_height);
+    for (x, y, rgb_px) in combined.enumerate_pixels_mut() {
+        *rgb_px = if x < img1_width {
+            *image1.get_pixel(x, y)
+        } else {
+            let sample_image2_x = (((x - img1_width) as f32 / img1_width as f32) * image2.width() as f32) as u32;
+            let sample_image2_y = ((y as f32 / img1_height as f32) * image2.height() as f32) as u32;
+            *image2.get_pixel(sample_image2_x, sample_image2_y)
+        };
+    }
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_within_the_range() {
+        assert_eq!(normalize(20.0, 30.0, 20.0), 0.0);
+        assert_eq!(normalize(20.0, 30.0, 25.0), 0.5);
+        assert_eq!(normalize(20.0, 30.0, 35.0), 1.5);
+    }
+
+    #[test]
+    fn normalize_of_a_uniform_scene() {
+        assert_eq!(normalize(21.5, 21.5, 21.5), 0.5);
+        assert_eq!(normalize(30.0, 20.0, 25.0), 0.5);
+    }
+
+    #[test]
+    fn isotherm_areas_are_shares_of_the_upscaled_frame() {
+        let frame = ThermalFrame::new(2, 1, vec![20.0, 30.0]);
+        let settings = ThermoImageProcessor::new(6)
+            .with_upscale_filter(temperature_field::UpscaleFilter::Bilinear)
+            .with_isotherms(vec![
+                "19:25:#0000ff".parse().unwrap(),
+                "29:31:#ff0000".parse().unwrap(),
+                "40:50:#ffffff".parse().unwrap(),
+            ]);
+        let processed = process_raw_thermo_image_data(&frame, &settings);
+        // every row of the upscaled frame: 3 pixels clamped to 20 °C, 6 ramping up to 29.2 °C and 3 at 30 °C
+        assert_eq!(processed.temperature_field.data.len(), 12 * 6);
+        let areas: Vec<(usize, f32)> = processed
+            .isotherm_areas
+            .iter()
+            .map(|area| (area.pixel_count, area.percentage))
+            .collect();
+        assert_eq!(areas[0], (6 * 6, 50.0));
+        assert_eq!(areas[1].0, 4 * 6);
+        assert!((areas[1].1 - 100.0 / 3.0).abs() < 1e-4);
+        assert_eq!(areas[2], (0, 0.0));
+    }
+
+    #[test]
+    fn hotspot_markers_at_the_image_border() {
+        for (hot_x, hot_y) in [(31, 0), (0, 23), (31, 23)] {
+            let mut data = vec![20.0; 32 * 24];
+            data[hot_y * 32 + hot_x] = 40.0;
+            let frame = ThermalFrame::new(32, 24, data);
+            for interpolation_factor in [1, 2] {
+                let processed = process_raw_thermo_image_data(&frame, &ThermoImageProcessor::new(interpolation_factor));
+                let center = (
+                    hot_x as u32 * interpolation_factor + interpolation_factor / 2,
+                    hot_y as u32 * interpolation_factor + interpolation_factor / 2,
+                );
+                assert_eq!(
+                    processed.image.get_pixel(center.0, center.1),
+                    &image::Rgb([255, 255, 255])
+                );
+            }
+        }
+    }
+
+    /// Thermal test image: a different color for every pixel.
+    fn thermal_image(width: u32, height: u32) -> image::RgbImage {
+        image::RgbImage::from_fn(width, height, |x, y| image::Rgb([(40 * x) as u8, (40 * y) as u8, 100]))
+    }
+
+    /// The thermal image scaled to the given size without any detail.
+    fn scaled_thermal_image(thermal: &image::RgbImage, width: u32, height: u32) -> image::RgbImage {
+        image::imageops::resize(thermal, width, height, image::imageops::FilterType::Nearest)
+    }
+
+    #[test]
+    fn edges_of_a_flat_camera_image_leave_the_thermal_image_unchanged() {
+        let thermal = thermal_image(4, 3);
+        let mut fused = image::RgbImage::from_pixel(8, 6, image::Rgb([90, 120, 60]));
+        enhance_edges_of_different_sizes(&mut fused, &thermal, 2.0, &Registration::identity());
+        assert_eq!(fused, scaled_thermal_image(&thermal, 8, 6));
+    }
+
+    #[test]
+    fn edge_strength_zero_shows_the_thermal_image_only() {
+        let thermal = thermal_image(4, 3);
+        let mut fused = image::RgbImage::from_fn(8, 6, |x, y| image::Rgb([if (x + y) % 2 == 0 { 255 } else { 0 }; 3]));
+        enhance_edges_of_different_sizes(&mut fused, &thermal, 0.0, &Registration::identity());
+        assert_eq!(fused, scaled_thermal_image(&thermal, 8, 6));
+    }
+
+    /// Gates a gray 8x6 camera image with the 4x3 thermal image, every column of the thermal image has one of the
+    /// temperatures.
+    fn gated_image(column_temps: [f32; 4], gate_min_temp: f32, gate_max_temp: f32) -> image::RgbImage {
+        let data = (0..3).flat_map(|_| column_temps).collect();
+        let temperature_field = TemperatureField::new(4, 3, data);
+        let mut gated = image::RgbImage::from_pixel(8, 6, image::Rgb([50; 3]));
+        gate_images_of_different_sizes(
+            &mut gated,
+            &thermal_image(4, 3),
+            &temperature_field,
+            gate_min_temp,
+            gate_max_temp,
+            &Registration::identity(),
+        );
+        gated
+    }
+
+    #[test]
+    fn gate_shows_the_thermal_image_within_the_band_only() {
+        // band 15-35 °C fades in over 2 °C: 16 °C and 34 °C are half way
+        let gated = gated_image([10.0, 16.0, 25.0, 34.0], 15.0, 35.0);
+        let thermal = scaled_thermal_image(&thermal_image(4, 3), 8, 6);
+        let faded = |x: u32, y: u32| image::Rgb(thermal.get_pixel(x, y).0.map(|c| (c as f32 * 0.5 + 25.0) as u8));
+        for y in 0..6 {
+            // below the band
+            assert_eq!(gated.get_pixel(0, y), &image::Rgb([50; 3]));
+            // lower edge
+            assert_eq!(gated.get_pixel(2, y), &faded(2, y));
+            // inside
+            assert_eq!(gated.get_pixel(4, y), thermal.get_pixel(4, y));
+            assert_eq!(gated.get_pixel(5, y), thermal.get_pixel(5, y));
+            // upper edge
+            assert_eq!(gated.get_pixel(7, y), &faded(7, y));
+        }
+        // above the band
+        let gated = gated_image([40.0, 25.0, 25.0, 25.0], 15.0, 35.0);
+        assert_eq!(gated.get_pixel(1, 5), &image::Rgb([50; 3]));
+        assert_eq!(gated.get_pixel(2, 5), thermal.get_pixel(2, 5));
+    }
+
+    #[test]
+    fn narrow_gates_are_not_faded_out() {
+        let thermal = scaled_thermal_image(&thermal_image(4, 3), 8, 6);
+        let gated = gated_image([10.0, 20.2, 20.2, 40.0], 20.0, 20.4);
+        assert_eq!(gated.get_pixel(2, 3), thermal.get_pixel(2, 3));
+        let gated = gated_image([10.0, 20.0, 20.0, 40.0], 20.0, 20.0);
+        assert_eq!(gated.get_pixel(2, 3), thermal.get_pixel(2, 3));
+        assert_eq!(gated.get_pixel(0, 3), &image::Rgb([50; 3]));
+    }
+
+    #[test]
+    fn split_at_the_divider() {
+        let thermal = thermal_image(4, 3);
+        let scaled_thermal = scaled_thermal_image(&thermal, 8, 6);
+        let camera = image::RgbImage::from_pixel(8, 6, image::Rgb([50; 3]));
+        for (divider_position, divider_x) in [(0.0, Some(0)), (0.5, Some(4)), (1.0, None)] {
+            let mut split = camera.clone();
+            split_images_of_different_sizes(&mut split, &thermal, divider_position, &Registration::identity());
+            assert_eq!(split.dimensions(), (8, 6));
+            for (x, y, px) in split.enumerate_pixels() {
+                let expected = match divider_x {
+                    Some(divider_x) if x == divider_x => &image::Rgb([255; 3]),
+                    Some(divider_x) if x > divider_x => scaled_thermal.get_pixel(x, y),
+                    _ => camera.get_pixel(x, y),
+                };
+                assert_eq!(px, expected, "({x}, {y}) split at {divider_position}");
+            }
+        }
+    }
+
+    #[test]
+    fn side_by_side_scales_the_second_image_into_the_right_half() {
+        let camera = image::RgbImage::from_fn(6, 4, |x, y| image::Rgb([x as u8, y as u8, 0]));
+        let thermal = thermal_image(3, 5);
+        let combined = images_side_by_side(&camera, &thermal);
+        assert_eq!(combined.dimensions(), (12, 4));
+        for y in 0..4 {
+            for x in 0..6 {
+                assert_eq!(combined.get_pixel(x, y), camera.get_pixel(x, y));
+            }
+        }
+        assert_eq!(combined.get_pixel(6, 0), thermal.get_pixel(0, 0));
+        assert_eq!(combined.get_pixel(8, 1), thermal.get_pixel(1, 1));
+        assert_eq!(combined.get_pixel(11, 3), thermal.get_pixel(2, 3));
+    }
+
+    #[test]
+    fn inset_in_the_upper_right_corner() {
+        assert_eq!(
+            picture_in_picture_inset((320, 240), (32, 24), 0.25),
+            Some((240, 80, 60))
+        );
+        assert_eq!(
+            picture_in_picture_inset((320, 240), (24, 32), 0.5),
+            Some((160, 160, 213))
+        );
+        assert_eq!(picture_in_picture_inset((320, 240), (32, 24), 1.0), Some((0, 320, 240)));
+        // limited to the height of image1
+        assert_eq!(
+            picture_in_picture_inset((320, 240), (10, 40), 0.5),
+            Some((160, 160, 240))
+        );
+        assert_eq!(picture_in_picture_inset((320, 240), (32, 24), 0.0), None);
+        assert_eq!(picture_in_picture_inset((320, 240), (32, 24), 0.005), None);
+    }
+
+    #[test]
+    fn picture_in_picture_draws_the_inset_with_a_border() {
+        let thermal = thermal_image(4, 3);
+        let camera = image::RgbImage::from_pixel(16, 12, image::Rgb([50; 3]));
+        let mut combined = camera.clone();
+        draw_picture_in_picture(&mut combined, &thermal, 0.5);
+        assert_eq!(combined.dimensions(), (16, 12));
+        // inset of 8x6 pixels at x = 8
+        assert_eq!(combined.get_pixel(7, 0), &image::Rgb([50; 3]));
+        assert_eq!(combined.get_pixel(8, 6), &image::Rgb([50; 3]));
+        assert_eq!(combined.get_pixel(8, 0), &image::Rgb([255; 3]));
+        assert_eq!(combined.get_pixel(15, 5), &image::Rgb([255; 3]));
+        assert_eq!(combined.get_pixel(9, 1), thermal.get_pixel(0, 0));
+        assert_eq!(combined.get_pixel(13, 4), thermal.get_pixel(2, 2));
+
+        let mut combined = camera.clone();
+        draw_picture_in_picture(&mut combined, &thermal, 0.0);
+        assert_eq!(combined, camera);
+    }
+
+    #[test]
+    fn parse_non_negative_edge_strengths_only() {
+        assert_eq!(parse_edge_strength("1.5"), Ok(1.5));
+        assert_eq!(parse_edge_strength("0"), Ok(0.0));
+        assert!(parse_edge_strength("-0.5").is_err());
+        assert!(parse_edge_strength("NaN").is_err());
+        assert!(parse_edge_strength("strong").is_err());
+    }
+
+    #[test]
+    fn high_pass_of_a_step_is_positive_on_the_bright_side() {
+        let image = image::RgbImage::from_fn(10, 3, |x, _| image::Rgb([if x < 5 { 0 } else { 200 }; 3]));
+        let detail = high_pass_luminance(&image, 2);
+        assert_eq!(detail[0], 0.0);
+        assert!(detail[4] < 0.0);
+        assert!(detail[5] > 0.0);
+        assert_eq!(detail[9], 0.0);
     }
 }
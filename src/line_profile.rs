@@ -0,0 +1,288 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use image;
+
+use crate::contour::{draw_line_into_image, draw_thermal_line_into_image};
+use crate::coordinate_mapper::CoordinateMapper;
+use crate::emissivity::EmissivityCompensation;
+use crate::rgb_color::RgbColor;
+use crate::temperature_field::TemperatureField;
+
+/// Line segment across the thermal image in thermal-sensor coordinates (pixel (x, y) covers x..x+1 and y..y+1).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineProfile {
+    pub start: (f32, f32),
+    pub end: (f32, f32),
+}
+
+impl LineProfile {
+    pub fn new(start: (f32, f32), end: (f32, f32)) -> Self {
+        LineProfile { start, end }
+    }
+
+    /// Length in sensor pixels.
+    pub fn length(&self) -> f32 {
+        let dx = self.end.0 - self.start.0;
+        let dy = self.end.1 - self.start.1;
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    /// Temperatures along the line, sampled (bilinearly) about once per pixel of the upscaled `field`.
+    pub fn samples(&self, field: &TemperatureField, interpolation_factor: u32) -> Vec<ProfileSample> {
+        let factor = interpolation_factor as f32;
+        let steps = (self.length() * factor).ceil().max(1.0) as u32;
+        (0..=steps)
+            .map(|step| {
+                let fraction = step as f32 / steps as f32;
+                let x = self.start.0 + (self.end.0 - self.start.0) * fraction;
+                let y = self.start.1 + (self.end.1 - self.start.1) * fraction;
+                ProfileSample {
+                    distance: self.length() * fraction,
+                    x,
+                    y,
+                    temperature: field.sample(x * factor - 0.5, y * factor - 0.5),
+                }
+            })
+            .collect()
+    }
+}
+
+/// `<x1>,<y1>,<x2>,<y2>` in sensor coordinates.
+impl FromStr for LineProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values: Vec<f32> = s
+            .split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<f32>()
+                    .map_err(|_| format!("invalid coordinate '{value}' in line profile '{s}'"))
+            })
+            .collect::<Result<_, _>>()?;
+        match values[..] {
+            [x1, y1, x2, y2] => Ok(LineProfile::new((x1, y1), (x2, y2))),
+            _ => Err(format!("invalid line profile '{s}' (expected <x1>,<y1>,<x2>,<y2>)")),
+        }
+    }
+}
+
+impl fmt::Display for LineProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.start.0, self.start.1, self.end.0, self.end.1)
+    }
+}
+
+/// Temperature at a point of a line profile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProfileSample {
+    /// Distance from the start of the line (sensor pixels)
+    pub distance: f32,
+    /// Position in sensor coordinates
+    pub x: f32,
+    pub y: f32,
+    pub temperature: f32,
+}
+
+/// Writes the samples as CSV with the header `distance,x,y,temperature`, preceded by `#` comment lines with the
+/// emissivity settings the temperatures were compensated with.
+pub fn write_csv<W: Write>(
+    samples: &[ProfileSample],
+    emissivity: &EmissivityCompensation,
+    writer: &mut W,
+) -> io::Result<()> {
+    for setting in emissivity.settings() {
+        writeln!(writer, "# {setting}")?;
+    }
+    writeln!(writer, "distance,x,y,temperature")?;
+    for sample in samples {
+        writeln!(
+            writer,
+            "{:.3},{:.3},{:.3},{:.2}",
+            sample.distance, sample.x, sample.y, sample.temperature
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the samples as CSV, missing directories of the path are created.
+pub fn save_csv(samples: &[ProfileSample], emissivity: &EmissivityCompensation, path: &str) -> io::Result<()> {
+    if let Some(directory) = Path::new(path).parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_csv(samples, emissivity, &mut writer)?;
+    writer.flush()
+}
+
+/// Plot of the temperature over the distance, scaled to the range of the samples.
+pub fn render_profile_plot(
+    samples: &[ProfileSample],
+    width: u32,
+    height: u32,
+    color: RgbColor,
+    background: RgbColor,
+) -> image::RgbImage {
+    let mut plot = image::RgbImage::from_pixel(width, height, image::Rgb([background.r, background.g, background.b]));
+    if width == 0 || height == 0 {
+        return plot;
+    }
+    let (first, last) = match (samples.first(), samples.last()) {
+        (Some(first), Some(last)) if last.distance > first.distance => (first, last),
+        _ => return plot,
+    };
+    let min_temp = samples.iter().map(|s| s.temperature).fold(f32::INFINITY, f32::min);
+    let max_temp = samples.iter().map(|s| s.temperature).fold(f32::NEG_INFINITY, f32::max);
+    let to_plot = |sample: &ProfileSample| {
+        let fraction_x = (sample.distance - first.distance) / (last.distance - first.distance);
+        // flat profiles are drawn in the middle
+        let fraction_y = if max_temp > min_temp {
+            (sample.temperature - min_temp) / (max_temp - min_temp)
+        } else {
+            0.5
+        };
+        (
+            fraction_x * (width - 1) as f32,
+            (1.0 - fraction_y) * (height - 1) as f32,
+        )
+    };
+    for pair in samples.windows(2) {
+        draw_line_into_image(to_plot(&pair[0]), to_plot(&pair[1]), color, &mut plot);
+    }
+    plot
+}
+
+/// Draws the line into the displayed image of the mapper.
+pub fn draw_profile_line_into_image(
+    profile: &LineProfile,
+    mapper: &CoordinateMapper,
+    color: RgbColor,
+    image: &mut image::RgbImage,
+) {
+    // flips, rotations and homographies keep straight lines straight
+    draw_thermal_line_into_image(profile.start, profile.end, mapper, color, image);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+
+    #[test]
+    fn csv_header_names_the_emissivity() {
+        let samples = [ProfileSample {
+            distance: 0.0,
+            x: 1.5,
+            y: 2.5,
+            temperature: 31.25,
+        }];
+        let emissivity = EmissivityCompensation::default()
+            .with_material(Material::builtin("human-skin").unwrap())
+            .with_reflected_temp(22.0);
+        let mut csv = Vec::new();
+        write_csv(&samples, &emissivity, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "# emissivity = 0.98\n# reflected_temp = 22\n# material = human-skin\ndistance,x,y,temperature\n0.000,1.500,2.500,31.25\n"
+        );
+    }
+
+    /// Field whose temperature is 10 times the grid x coordinate plus the grid y coordinate.
+    fn gradient_field(width: u32, height: u32) -> TemperatureField {
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| 10.0 * x as f32 + y as f32))
+            .collect();
+        TemperatureField::new(width, height, data)
+    }
+
+    fn sample(distance: f32, temperature: f32) -> ProfileSample {
+        ProfileSample {
+            distance,
+            x: distance,
+            y: 0.0,
+            temperature,
+        }
+    }
+
+    #[test]
+    fn samples_along_the_line() {
+        let field = gradient_field(4, 3);
+        let samples = LineProfile::new((0.5, 1.5), (3.5, 1.5)).samples(&field, 1);
+        let values: Vec<(f32, f32, f32)> = samples.iter().map(|s| (s.distance, s.x, s.temperature)).collect();
+        assert_eq!(
+            values,
+            vec![(0.0, 0.5, 1.0), (1.0, 1.5, 11.0), (2.0, 2.5, 21.0), (3.0, 3.5, 31.0)]
+        );
+        assert!(samples.iter().all(|s| s.y == 1.5));
+    }
+
+    #[test]
+    fn samples_of_an_upscaled_field() {
+        // 2 samples per sensor pixel
+        let field = gradient_field(8, 6);
+        let samples = LineProfile::new((0.5, 1.0), (2.0, 1.0)).samples(&field, 2);
+        let distances: Vec<f32> = samples.iter().map(|s| s.distance).collect();
+        assert_eq!(distances, vec![0.0, 0.5, 1.0, 1.5]);
+        let temperatures: Vec<f32> = samples.iter().map(|s| s.temperature).collect();
+        assert_eq!(temperatures, vec![6.5, 16.5, 26.5, 36.5]);
+    }
+
+    #[test]
+    fn samples_of_a_zero_length_line() {
+        // between the grid points: bilinear interpolation of the four neighbours
+        let samples = LineProfile::new((1.25, 0.75), (1.25, 0.75)).samples(&gradient_field(4, 3), 1);
+        assert_eq!(samples.len(), 2);
+        for sample in samples {
+            assert_eq!(sample.distance, 0.0);
+            assert_eq!((sample.x, sample.y), (1.25, 0.75));
+            assert!((sample.temperature - 7.75).abs() < 1e-5, "{}", sample.temperature);
+        }
+    }
+
+    #[test]
+    fn parse_line_profiles() {
+        let profile: LineProfile = " 1.5, 2,30,4.25".parse().unwrap();
+        assert_eq!(profile, LineProfile::new((1.5, 2.0), (30.0, 4.25)));
+        assert_eq!(profile.to_string().parse::<LineProfile>(), Ok(profile));
+        assert_eq!(
+            "1,2,3".parse::<LineProfile>(),
+            Err("invalid line profile '1,2,3' (expected <x1>,<y1>,<x2>,<y2>)".to_string())
+        );
+        assert!("1,2,3,4,5"
+            .parse::<LineProfile>()
+            .unwrap_err()
+            .contains("expected <x1>"));
+        assert_eq!(
+            "1,a,3,4".parse::<LineProfile>(),
+            Err("invalid coordinate 'a' in line profile '1,a,3,4'".to_string())
+        );
+        assert!("".parse::<LineProfile>().unwrap_err().contains("invalid coordinate ''"));
+    }
+
+    #[test]
+    fn plot_from_the_coldest_to_the_hottest_sample() {
+        let white = RgbColor { r: 255, g: 255, b: 255 };
+        let black = RgbColor { r: 0, g: 0, b: 0 };
+        let plot = render_profile_plot(&[sample(0.0, 20.0), sample(1.0, 30.0)], 10, 5, white, black);
+        assert_eq!(plot.dimensions(), (10, 5));
+        assert_eq!(plot.get_pixel(0, 4), &image::Rgb([255; 3]));
+        assert_eq!(plot.get_pixel(9, 0), &image::Rgb([255; 3]));
+        assert_eq!(plot.get_pixel(0, 0), &image::Rgb([0; 3]));
+    }
+
+    #[test]
+    fn empty_plots() {
+        let white = RgbColor { r: 255, g: 255, b: 255 };
+        let black = RgbColor { r: 0, g: 0, b: 0 };
+        let samples = [sample(0.0, 20.0), sample(1.0, 30.0)];
+        assert_eq!(render_profile_plot(&samples, 0, 5, white, black).dimensions(), (0, 5));
+        assert_eq!(render_profile_plot(&samples, 10, 0, white, black).dimensions(), (10, 0));
+        let plot = render_profile_plot(&samples[..1], 10, 5, white, black);
+        assert!(plot.pixels().all(|px| px == &image::Rgb([0; 3])));
+    }
+}
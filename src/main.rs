@@ -6,11 +6,28 @@ use clap;
 use image;
 use image::imageops::FilterType;
 
-use linux_embedded_hal::I2cdev;
 use mlx9064x;
-use mlx9064x::Mlx90640Driver;
 
+use thermocam::atmosphere::Atmosphere;
+use thermocam::auto_alignment::AutoAligner;
+use thermocam::color_conversion::{YuvConversion, YuvMatrix, YuvRange};
+use thermocam::coordinate_mapper::{view_to_image_position, CoordinateMapper, CoordinateSpace};
+use thermocam::decoder::DecoderRegistry;
+use thermocam::display_mode::DisplayMode;
+use thermocam::emissivity::{EmissivityCompensation, RoiEmissivity};
+use thermocam::isotherm::Isotherm;
+use thermocam::line_profile::{LineProfile, ProfileSample};
+use thermocam::material::Material;
+use thermocam::orientation::Orientation;
+use thermocam::palette::Palette;
+use thermocam::registration::{Calibration, Registration};
 use thermocam::rgb_color::RgbColor;
+use thermocam::roi::Roi;
+use thermocam::spot_meter::{SpotMeter, TemperatureDelta};
+use thermocam::temperature_field::UpscaleFilter;
+use thermocam::temporal_filter::{TemporalFilter, TemporalFilterStage};
+use thermocam::thermal_source::{Mlx90640Source, RecordedSource, ThermalRecorder, ThermalSource};
+use thermocam::visible_source::{FileReplaySource, V4lSource, VisibleSource};
 use thermocam::{self, thermo_image_processing::ThermoImageProcessor};
 
 use slint;
@@ -20,20 +37,27 @@ const COLOR_BLEND_STEPS: u32 = 150;
 const INTERPOLATION_FACTOR: u32 = 6;
 const MIN_TEMP: f32 = 18.0;
 const MAX_TEMP: f32 = 35.0;
-const MIN_TEMP_COLOR: RgbColor = RgbColor { r: 0, g: 0, b: 255 };
-const MAX_TEMP_COLOR: RgbColor = RgbColor { r: 255, g: 0, b: 0 };
+const PICTURE_IN_PICTURE_FRACTION: f32 = 0.4;
+const EDGE_STRENGTH_STEP: f32 = 0.25;
+const GATE_TEMP_STEP: f32 = 1.0;
+/// Measurement range of the MLX90640 (°C), the gate buttons stay within it
+const SENSOR_MIN_TEMP: f32 = -40.0;
+const SENSOR_MAX_TEMP: f32 = 300.0;
+const EMISSIVITY_STEP: f32 = 0.01;
+const REFLECTED_TEMP_STEP: f32 = 1.0;
+const PROFILE_PLOT_WIDTH: u32 = 200;
+const PROFILE_PLOT_HEIGHT: u32 = 40;
+const TAP_SPOT_NAME: &str = "Tap";
+
+/// Tapped point and size of the image element (logical pixels).
+type TapPosition = (f32, f32, f32, f32);
 
 // use opencv::{highgui, prelude::*, videoio, Result};
-use v4l::buffer::Type;
-use v4l::io::mmap::Stream;
-use v4l::io::traits::CaptureStream;
-use v4l::video::Capture;
-use v4l::Device;
 use v4l::FourCC;
 
 slint::include_modules!();
 fn main() -> std::io::Result<()> {
-    let (
+    let CliOptions {
         use_simulation_data,
         deactivate_autoscale,
         camera_image_width,
@@ -41,212 +65,566 @@ fn main() -> std::io::Result<()> {
         new_fourcc,
         foreground_alpha,
         mode_in,
-    ) = parse_cli();
+        yuv_conversion,
+        palette,
+        upscale_filter,
+        registration_file,
+        calibration_file,
+        auto_alignment_enabled,
+        edge_strength,
+        gate_min_temp,
+        gate_max_temp,
+        isotherms,
+        contour_interval,
+        emissivity,
+        atmosphere,
+        mut rois,
+        spot_meters,
+        temperature_deltas,
+        line_profile,
+        profile_csv_file,
+        thermal_orientation,
+        visible_orientation,
+        temporal_filter,
+        motion_threshold,
+        record_file,
+        replay_file,
+    } = parse_cli();
+
+    if let Some(calibration_file) = calibration_file {
+        calibrate_registration(&calibration_file, registration_file.as_deref());
+        return Ok(());
+    }
+    let registration = match &registration_file {
+        Some(registration_file) => Registration::load(registration_file).expect("Could not load the registration file"),
+        None => Registration::identity(),
+    };
+
+    let frame_rate_in = mlx9064x::FrameRate::Eight;
+    let frame_rate: f32 = frame_rate_in.into();
+    let period = ((1.0 / frame_rate) * 1000.0) as u64;
+    if DEBUG_FEATURES {
+        println!("FPS: {:?} ({:?} ms)", frame_rate, period);
+    }
+    let recording = replay_file
+        .map(|replay_file| RecordedSource::open(&replay_file, period).expect("Failed to load the recording"));
+    let emissivity = emissivity
+        .or_else(|| recording.as_ref()?.emissivity())
+        .unwrap_or_default();
+    let atmosphere = atmosphere
+        .or_else(|| recording.as_ref()?.atmosphere())
+        .unwrap_or_default();
+    // regions without --roi-emissivity take the emissivity saved for the region of the same name
+    for roi_emissivity in recording.iter().flat_map(|recording| recording.roi_emissivities()) {
+        if let Some(roi) = rois
+            .iter_mut()
+            .find(|roi| roi.name == roi_emissivity.name && roi.emissivity.is_none())
+        {
+            roi.emissivity = Some(roi_emissivity.compensation(emissivity.reflected_temp));
+        }
+    }
 
     let thermo_process_settings = Arc::new(Mutex::new(
         ThermoImageProcessor::new(INTERPOLATION_FACTOR)
             .with_autoscale_enabled(!deactivate_autoscale)
             .with_manual_scale_min_temp(MIN_TEMP)
             .with_manual_scale_max_temp(MAX_TEMP)
-            .with_min_temp_color(MIN_TEMP_COLOR)
-            .with_max_temp_color(MAX_TEMP_COLOR)
-            .with_mode(mode_in),
+            .with_palette(palette.clone())
+            .with_isotherms(isotherms)
+            .with_contour_interval(contour_interval)
+            .with_emissivity(emissivity)
+            .with_atmosphere(atmosphere)
+            .with_rois(rois)
+            .with_spot_meters(spot_meters)
+            .with_temperature_deltas(temperature_deltas)
+            .with_line_profile(line_profile)
+            .with_thermal_orientation(thermal_orientation)
+            .with_visible_orientation(visible_orientation)
+            .with_upscale_filter(upscale_filter)
+            .with_temporal_filter(temporal_filter)
+            .with_motion_threshold(motion_threshold)
+            .with_mode(mode_in)
+            .with_registration(registration)
+            .with_auto_alignment_enabled(auto_alignment_enabled)
+            .with_edge_strength(edge_strength)
+            .with_gate_min_temp(gate_min_temp)
+            .with_gate_max_temp(gate_max_temp),
     ));
 
     let main_window = MainWindow::new();
 
     // UI callbacks
+    let callbacks = CallbackContext::new(&thermo_process_settings, &main_window);
     let thermo_process_settings_clone = Arc::clone(&thermo_process_settings);
     main_window.on_autoscale_toggled(move |autoscale_enabled: bool| {
         thermo_process_settings_clone.lock().unwrap().autoscale_enabled = autoscale_enabled;
     });
+    main_window.on_manual_scale_min_temp_decreased(callbacks.settings(|settings, _| {
+        settings.manual_scale_min_temp -= 1.0;
+    }));
+    main_window.on_manual_scale_min_temp_increased(callbacks.settings(|settings, _| {
+        settings.manual_scale_min_temp += 1.0;
+    }));
+    main_window.on_manual_scale_max_temp_decreased(callbacks.settings(|settings, _| {
+        settings.manual_scale_max_temp -= 1.0;
+    }));
+    main_window.on_manual_scale_max_temp_increased(callbacks.settings(|settings, _| {
+        settings.manual_scale_max_temp += 1.0;
+    }));
+    main_window.on_mode_decreased(callbacks.settings(|settings, main_window| {
+        settings.mode = settings.mode.previous();
+        show_display_mode(main_window, settings);
+    }));
+    main_window.on_mode_increased(callbacks.settings(|settings, main_window| {
+        settings.mode = settings.mode.next();
+        show_display_mode(main_window, settings);
+    }));
+    main_window.on_edge_strength_decreased(callbacks.settings(|settings, _| {
+        settings.edge_strength = (settings.edge_strength - EDGE_STRENGTH_STEP).max(0.0);
+    }));
+    main_window.on_edge_strength_increased(callbacks.settings(|settings, _| {
+        settings.edge_strength += EDGE_STRENGTH_STEP;
+    }));
+    main_window.on_gate_min_temp_decreased(callbacks.settings(|settings, main_window| {
+        settings.gate_min_temp = (settings.gate_min_temp - GATE_TEMP_STEP).max(SENSOR_MIN_TEMP);
+        show_display_mode(main_window, settings);
+    }));
+    main_window.on_gate_min_temp_increased(callbacks.settings(|settings, main_window| {
+        settings.gate_min_temp = (settings.gate_min_temp + GATE_TEMP_STEP).min(settings.gate_max_temp);
+        show_display_mode(main_window, settings);
+    }));
+    main_window.on_gate_max_temp_decreased(callbacks.settings(|settings, main_window| {
+        settings.gate_max_temp = (settings.gate_max_temp - GATE_TEMP_STEP).max(settings.gate_min_temp);
+        show_display_mode(main_window, settings);
+    }));
+    main_window.on_gate_max_temp_increased(callbacks.settings(|settings, main_window| {
+        settings.gate_max_temp = (settings.gate_max_temp + GATE_TEMP_STEP).min(SENSOR_MAX_TEMP);
+        show_display_mode(main_window, settings);
+    }));
     let thermo_process_settings_clone = Arc::clone(&thermo_process_settings);
-    main_window.on_manual_scale_min_temp_decreased(move || {
-        thermo_process_settings_clone.lock().unwrap().manual_scale_min_temp -= 1.0;
-    });
-    let thermo_process_settings_clone = Arc::clone(&thermo_process_settings);
-    main_window.on_manual_scale_min_temp_increased(move || {
-        thermo_process_settings_clone.lock().unwrap().manual_scale_min_temp += 1.0;
-    });
-    let thermo_process_settings_clone = Arc::clone(&thermo_process_settings);
-    main_window.on_manual_scale_max_temp_decreased(move || {
-        thermo_process_settings_clone.lock().unwrap().manual_scale_max_temp -= 1.0;
-    });
-    let thermo_process_settings_clone = Arc::clone(&thermo_process_settings);
-    main_window.on_manual_scale_max_temp_increased(move || {
-        thermo_process_settings_clone.lock().unwrap().manual_scale_max_temp += 1.0;
-    });
-    let thermo_process_settings_clone = Arc::clone(&thermo_process_settings);
-    main_window.on_mode_decreased(move || {
-        let mut settings = thermo_process_settings_clone.lock().unwrap();
-        if settings.mode >= 1 {
-            settings.mode -= 1;
-        }
-    });
-    let thermo_process_settings_clone = Arc::clone(&thermo_process_settings);
-    main_window.on_mode_increased(move || {
-        let mut settings = thermo_process_settings_clone.lock().unwrap();
-        if settings.mode < 2 {
-            settings.mode += 1;
-        }
+    main_window.on_split_position_changed(move |split_position: f32| {
+        thermo_process_settings_clone.lock().unwrap().split_position = split_position.clamp(0.0, 1.0);
     });
 
-    // generate and set scale image
-    let col_buf = RgbColor::discrete_blend(MIN_TEMP_COLOR, MAX_TEMP_COLOR, COLOR_BLEND_STEPS);
-    let mut buf: Vec<u8> = Vec::new();
-    for c in col_buf.iter().rev() {
-        buf.extend(c.to_vec());
-    }
-    let scale_img = image::RgbImage::from_raw(1, COLOR_BLEND_STEPS, buf).unwrap();
-    let scale_upscaled_img = image::imageops::resize(&scale_img, 15, scale_img.height(), FilterType::Nearest);
-    let scale_image = slint::Image::from_rgb8(slint::SharedPixelBuffer::clone_from_slice(
-        scale_upscaled_img.as_raw(),
-        scale_upscaled_img.width(),
-        scale_upscaled_img.height(),
-    ));
-    main_window.set_scale_image(scale_image);
+    // emissivity adjusted by the buttons: 0 for the global one, i for the one of region i - 1
+    let emissivity_target = Arc::new(Mutex::new(0usize));
+    let emissivity_target_clone = Arc::clone(&emissivity_target);
+    main_window.on_emissivity_target_changed(callbacks.settings(move |settings, main_window| {
+        let mut target = emissivity_target_clone.lock().unwrap();
+        *target = (*target + 1) % (settings.rois.len() + 1);
+        show_emissivity(main_window, settings, *target);
+    }));
+    main_window.on_emissivity_decreased(callbacks.emissivity(&emissivity_target, |e| {
+        e.with_emissivity(e.emissivity - EMISSIVITY_STEP)
+    }));
+    main_window.on_emissivity_increased(callbacks.emissivity(&emissivity_target, |e| {
+        e.with_emissivity(e.emissivity + EMISSIVITY_STEP)
+    }));
+    main_window.on_material_changed(callbacks.emissivity(&emissivity_target, |e| match e.material {
+        Some(material) => e.with_material(material.next_builtin()),
+        None => e.with_material(thermocam::material::MATERIALS[0]),
+    }));
+    main_window.on_reflected_temp_decreased(callbacks.emissivity(&emissivity_target, |e| {
+        e.with_reflected_temp(e.reflected_temp - REFLECTED_TEMP_STEP)
+    }));
+    main_window.on_reflected_temp_increased(callbacks.emissivity(&emissivity_target, |e| {
+        e.with_reflected_temp(e.reflected_temp + REFLECTED_TEMP_STEP)
+    }));
 
-    // handle dynamic UI stuff
-    let handle_weak = main_window.as_weak();
-    let thread = std::thread::spawn(move || {
-        let mut thermo_image_shape = (32, 32);
-        let mut sensor_opt: Option<Mlx90640Driver<I2cdev>> = None;
+    // tapped point (position and size of the image element), resolved with the next displayed image
+    let tapped_position: Arc<Mutex<Option<TapPosition>>> = Arc::new(Mutex::new(None));
+    let tapped_position_clone = Arc::clone(&tapped_position);
+    main_window.on_image_tapped(move |x: f32, y: f32, view_width: f32, view_height: f32| {
+        *tapped_position_clone.lock().unwrap() = Some((x, y, view_width, view_height));
+    });
 
-        let frame_rate_in = mlx9064x::FrameRate::Eight;
-        let frame_rate: f32 = frame_rate_in.into();
-        let period = ((1.0 / frame_rate) * 1000.0) as u64;
+    // latest line profile, exported on request
+    let profile_samples: Arc<Mutex<Vec<ProfileSample>>> = Arc::new(Mutex::new(Vec::new()));
+    let profile_samples_clone = Arc::clone(&profile_samples);
+    main_window.on_profile_exported(callbacks.settings(move |settings, main_window| {
+        let profile_samples = profile_samples_clone.lock().unwrap();
+        let emissivity = &settings.emissivity;
+        let status = match thermocam::line_profile::save_csv(&profile_samples, emissivity, &profile_csv_file) {
+            Ok(()) => format!("Saved to {profile_csv_file}"),
+            Err(err) => format!("Not saved to {profile_csv_file}: {err}"),
+        };
         if DEBUG_FEATURES {
-            println!("FPS: {:?} ({:?} ms)", frame_rate, period);
+            println!("Line profile: {status}");
         }
+        main_window.set_profile_export_status(slint::SharedString::from(&status));
+    }));
 
-        if !use_simulation_data {
-            // A buffer for storing the temperature "image"
-            let i2c_bus = I2cdev::new("/dev/i2c-1").expect("/dev/i2c-1 needs to be an I2C controller");
-            // Default address for these cameras is 0x33
-            let mut sensor = Mlx90640Driver::new(i2c_bus, 0x33).unwrap();
+    main_window.on_palette_changed(callbacks.settings(|settings, main_window| {
+        settings.palette = settings.palette.next_builtin();
+        main_window.set_scale_image(generate_scale_image(&settings.palette));
+        main_window.set_palette_name(slint::SharedString::from(settings.palette.name()));
+    }));
 
-            sensor.set_frame_rate(frame_rate_in).unwrap();
-            sensor.set_access_pattern(mlx9064x::AccessPattern::Chess).unwrap();
-            thermo_image_shape = (sensor.height() as u32, sensor.width() as u32);
-            sensor.synchronize().unwrap();
+    main_window.on_temporal_filter_changed(callbacks.settings(|settings, main_window| {
+        settings.temporal_filter = settings.temporal_filter.next();
+        main_window.set_temporal_filter_name(slint::SharedString::from(settings.temporal_filter.name()));
+    }));
 
-            sensor_opt = Some(sensor);
-        }
-        let mut mlx_sensor_data = vec![0f32; thermo_image_shape.0 as usize * thermo_image_shape.1 as usize];
+    // generate and set scale image
+    main_window.set_scale_image(generate_scale_image(&palette));
+    main_window.set_palette_name(slint::SharedString::from(palette.name()));
+    show_display_mode(&main_window, &thermo_process_settings.lock().unwrap());
+    show_emissivity(&main_window, &thermo_process_settings.lock().unwrap(), 0);
+    main_window.set_temporal_filter_name(slint::SharedString::from(temporal_filter.name()));
 
-        let mut cam_image_shape = (camera_image_width, camera_image_height);
-        let mut cam_data_buffer: &[u8];
-        let mut sim_data_buffer: Box<[u8; 384000]>;
-        let mut stream_opt: Option<Stream> = None;
+    // handle dynamic UI stuff
+    let handle_weak = main_window.as_weak();
+    let thread = std::thread::spawn(move || {
+        let mut thermal_source = open_thermal_source(use_simulation_data, recording, frame_rate_in, period);
+        let mut recorder = record_file.map(|record_file| {
+            let recorder = ThermalRecorder::create(&record_file, thermal_source.width(), thermal_source.height())
+                .expect("Failed to create the recording");
+            let settings = thermo_process_settings.lock().unwrap();
+            recorder
+                .save_atmosphere(&settings.atmosphere)
+                .expect("Failed to save the atmosphere of the recording");
+            recorder
+                .save_emissivity(&settings.emissivity, &settings.rois)
+                .expect("Failed to save the emissivity of the recording");
+            recorder
+        });
+        // the sidecar keeps the emissivities of the start of the recording, later changes are only reported
+        let mut reported_emissivities = emissivities_of_scene(&thermo_process_settings.lock().unwrap());
 
-        if !use_simulation_data {
-            let mut dev = Device::new(0).expect("Failed to open device");
-            let mut fmt = dev.format().expect("Failed to read format");
-            cam_image_shape = (fmt.width, fmt.height);
-            let fourcc = fmt.fourcc;
+        let decoders = DecoderRegistry::new().with_yuv_conversion(yuv_conversion);
+        let new_fourcc_bytes = new_fourcc
+            .as_bytes()
+            .try_into()
+            .expect("fourcc needs to have 4 characters");
+        let new_fourcc = FourCC::new(new_fourcc_bytes);
+        let mut visible_source = open_visible_source(
+            use_simulation_data,
+            camera_image_width,
+            camera_image_height,
+            new_fourcc,
+            &decoders,
+        );
+        // the driver may deliver another format than the requested one
+        let negotiated_fourcc = visible_source.fourcc();
+        if !decoders.supports(negotiated_fourcc) {
+            let supported: Vec<String> = decoders.supported_fourccs().iter().map(|f| f.to_string()).collect();
+            panic!(
+                "camera delivers fourcc {negotiated_fourcc} (requested {new_fourcc}), which is not supported (choose one of {})",
+                supported.join(", ")
+            );
+        }
 
-            if DEBUG_FEATURES {
-                println!("Before change: camera shape {cam_image_shape:?} + {fourcc}");
-            }
-            fmt.width = camera_image_width;
-            fmt.height = camera_image_height;
-            let new_fourcc_bytes = new_fourcc.as_bytes().try_into().unwrap();
-            fmt.fourcc = FourCC::new(new_fourcc_bytes); // YUYV
-            dev.set_format(&fmt).expect("Failed to write format");
+        let mut auto_aligner = AutoAligner::new();
+        let mut temporal_filter_stage = TemporalFilterStage::new();
 
+        loop {
+            let camera_frame = visible_source.next_frame().expect("Failed to read camera frame");
+            let cam_image_shape = camera_frame.shape;
             if DEBUG_FEATURES {
-                fmt = dev.format().expect("Failed to read format");
-                let cam_image_shape = (fmt.width, fmt.height);
-                let fourcc = fmt.fourcc;
-                println!("After change: camera shape {cam_image_shape:?} + {fourcc}");
+                println!(
+                    "Buffer size: {}, fourcc: {}, seq: {}, timestamp: {:?}, width: {}, height: {}",
+                    camera_frame.data.len(),
+                    camera_frame.fourcc,
+                    camera_frame.sequence,
+                    camera_frame.timestamp,
+                    cam_image_shape.0,
+                    cam_image_shape.1,
+                );
+                let mut f = File::create("data/received_image_data.bin").unwrap();
+                f.write_all(camera_frame.data).unwrap();
             }
 
-            stream_opt =
-                Some(Stream::with_buffers(&mut dev, Type::VideoCapture, 4).expect("Failed to create buffer stream"));
-        }
+            // decode camera data
+            let mut camera_rgb_image = match decoders.decode(&camera_frame) {
+                Ok(image) => image,
+                Err(err) => {
+                    eprintln!("Skipping camera frame {}: {err}", camera_frame.sequence);
+                    continue;
+                }
+            };
 
-        loop {
-            if use_simulation_data {
-                sim_data_buffer = Box::new([0; 384000]);
-                thermocam::get_camera_simulation_data(&mut sim_data_buffer);
-                cam_data_buffer = &*sim_data_buffer;
-            } else {
-                let result = stream_opt.as_mut().unwrap().next().unwrap();
-                cam_data_buffer = result.0;
-                if DEBUG_FEATURES {
-                    println!(
-                        "Buffer size: {}, seq: {}, timestamp: {}, width: {}, height: {}",
-                        cam_data_buffer.len(),
-                        result.1.sequence,
-                        result.1.timestamp,
-                        cam_image_shape.0,
-                        cam_image_shape.1,
+            let thermal_frame = thermal_source.next_frame().expect("Failed to read thermal frame");
+            let (temporal_filter, motion_threshold, emissivities) = {
+                let thermo_process_settings = thermo_process_settings.lock().unwrap();
+                (
+                    thermo_process_settings.temporal_filter,
+                    thermo_process_settings.motion_threshold,
+                    emissivities_of_scene(&thermo_process_settings),
+                )
+            };
+            if let Some(recorder) = recorder.as_mut() {
+                recorder
+                    .write_frame(&thermal_frame)
+                    .expect("Failed to record thermal frame");
+                if emissivities != reported_emissivities {
+                    eprintln!(
+                        "Emissivity changed at recorded frame {}, the recording keeps the one of its start",
+                        thermal_frame.frame_index
                     );
+                    reported_emissivities = emissivities;
                 }
             }
-
-            if DEBUG_FEATURES {
-                let mut f = File::create("data/received_image_data.bin").unwrap();
-                f.write_all(cam_data_buffer).unwrap();
-            }
-
-            let rgb_buffer_size = 3 * cam_image_shape.0 as usize * cam_image_shape.1 as usize;
-            let mut cam_rgb_raw_buf = vec![0u8; rgb_buffer_size];
-
-            // decode camera data
-            thermocam::sgrbg10p_to_rgb(cam_data_buffer, cam_image_shape, &mut cam_rgb_raw_buf);
-            let mut camera_rgb_image =
-                image::RgbImage::from_raw(cam_image_shape.0, cam_image_shape.1, cam_rgb_raw_buf).unwrap();
-
-            thermocam::get_thermo_image_raw_data(
-                use_simulation_data,
-                &mut thermo_image_shape,
-                &mut mlx_sensor_data,
-                &mut sensor_opt,
-                period,
-            );
+            let thermal_frame = temporal_filter_stage.apply(&thermal_frame, temporal_filter, motion_threshold);
 
             let mode;
-            let min_pixel;
-            let max_pixel;
+            let split_position;
+            let edge_strength;
+            let gate_min_temp;
+            let gate_max_temp;
+            let palette;
+            let rois;
+            let spot_meters;
+            let line_profile;
+            let thermal_orientation;
+            let visible_orientation;
+            let mut registration;
+            let auto_alignment_enabled;
+            let processed_thermal_frame;
             let min_manual_scale_temp;
             let max_manual_scale_temp;
-            let mean_temp;
-            let thermo_image;
             {
                 // lock mutex in own scope to reduce time locked
                 let thermo_process_settings = thermo_process_settings.lock().unwrap();
-                (max_pixel, min_pixel, mean_temp, thermo_image) = thermocam::process_raw_thermo_image_data(
-                    &mlx_sensor_data,
-                    thermo_image_shape,
-                    &thermo_process_settings,
-                );
+                processed_thermal_frame =
+                    thermocam::process_raw_thermo_image_data(&thermal_frame, &thermo_process_settings);
                 if thermo_process_settings.autoscale_enabled {
-                    min_manual_scale_temp = min_pixel.value;
-                    max_manual_scale_temp = max_pixel.value;
+                    min_manual_scale_temp = processed_thermal_frame.stats.min.value;
+                    max_manual_scale_temp = processed_thermal_frame.stats.max.value;
                 } else {
                     min_manual_scale_temp = thermo_process_settings.manual_scale_min_temp;
                     max_manual_scale_temp = thermo_process_settings.manual_scale_max_temp;
                 }
                 mode = thermo_process_settings.mode;
+                split_position = thermo_process_settings.split_position;
+                edge_strength = thermo_process_settings.edge_strength;
+                gate_min_temp = thermo_process_settings.gate_min_temp;
+                gate_max_temp = thermo_process_settings.gate_max_temp;
+                palette = thermo_process_settings.palette.clone();
+                rois = thermo_process_settings.rois.clone();
+                spot_meters = thermo_process_settings.spot_meters.clone();
+                line_profile = thermo_process_settings.line_profile;
+                thermal_orientation = thermo_process_settings.thermal_orientation;
+                visible_orientation = thermo_process_settings.visible_orientation;
+                registration = thermo_process_settings.registration;
+                auto_alignment_enabled = thermo_process_settings.auto_alignment_enabled;
             }
 
-            // flip image horizontally
-            camera_rgb_image = image::DynamicImage::ImageRgb8(camera_rgb_image).fliph().to_rgb8();
+            let visible_shape = (camera_rgb_image.width(), camera_rgb_image.height());
+            camera_rgb_image = visible_orientation.apply_to_image(camera_rgb_image);
 
-            let displayed_image = match mode {
-                0 => {
-                    thermocam::blend_images_of_different_sizes(&mut camera_rgb_image, &thermo_image, foreground_alpha);
+            if auto_alignment_enabled {
+                registration = auto_aligner.update(
+                    &camera_rgb_image,
+                    &processed_thermal_frame.temperature_field,
+                    &registration,
+                );
+                if DEBUG_FEATURES {
+                    println!("Auto alignment correlation: {:.3}", auto_aligner.correlation());
+                }
+            }
+
+            // displays the oriented camera image until the layout of the mode is known
+            let coordinate_mapper = CoordinateMapper::new(visible_shape, (thermal_frame.width, thermal_frame.height))
+                .with_visible_orientation(visible_orientation)
+                .with_thermal_orientation(thermal_orientation)
+                .with_registration(registration);
+
+            let stats = processed_thermal_frame.stats;
+            let thermo_image = processed_thermal_frame.image;
+            let mut displayed_image = match mode {
+                DisplayMode::Blend => {
+                    thermocam::blend_images_of_different_sizes(
+                        &mut camera_rgb_image,
+                        &thermo_image,
+                        foreground_alpha,
+                        &registration,
+                    );
+                    camera_rgb_image
+                }
+                DisplayMode::Visible => camera_rgb_image,
+                DisplayMode::Thermal => thermo_image,
+                DisplayMode::PictureInPicture => {
+                    thermocam::draw_picture_in_picture(
+                        &mut camera_rgb_image,
+                        &thermo_image,
+                        PICTURE_IN_PICTURE_FRACTION,
+                    );
+                    camera_rgb_image
+                }
+                DisplayMode::SplitScreen => {
+                    thermocam::split_images_of_different_sizes(
+                        &mut camera_rgb_image,
+                        &thermo_image,
+                        split_position,
+                        &registration,
+                    );
+                    camera_rgb_image
+                }
+                DisplayMode::SideBySide => thermocam::images_side_by_side(&camera_rgb_image, &thermo_image),
+                DisplayMode::TemperatureGated => {
+                    thermocam::gate_images_of_different_sizes(
+                        &mut camera_rgb_image,
+                        &thermo_image,
+                        &processed_thermal_frame.temperature_field,
+                        gate_min_temp,
+                        gate_max_temp,
+                        &registration,
+                    );
+                    camera_rgb_image
+                }
+                DisplayMode::Contours => {
+                    thermocam::contour::draw_contours_into_image(
+                        &processed_thermal_frame.contours,
+                        &coordinate_mapper,
+                        |level| {
+                            palette.color_at(thermocam::normalize(
+                                min_manual_scale_temp,
+                                max_manual_scale_temp,
+                                level,
+                            ))
+                        },
+                        &mut camera_rgb_image,
+                    );
+                    camera_rgb_image
+                }
+                DisplayMode::EdgeEnhanced => {
+                    thermocam::enhance_edges_of_different_sizes(
+                        &mut camera_rgb_image,
+                        &thermo_image,
+                        edge_strength,
+                        &registration,
+                    );
                     camera_rgb_image
                 }
-                1 => camera_rgb_image,
-                2 => thermo_image,
-                _ => panic!("image display mode not supported (choose 0, 1 or 2)"),
             };
 
-            let min_pixel_formatted = format!("Min: {:.2}°C", min_pixel.value);
-            let mean_pixel_formatted = format!("Mean: {:.2}°C", mean_temp);
-            let max_pixel_formatted = format!("Max: {:.2}°C", max_pixel.value);
+            let coordinate_mapper = coordinate_mapper.with_display(
+                mode,
+                (displayed_image.width(), displayed_image.height()),
+                PICTURE_IN_PICTURE_FRACTION,
+            );
+
+            if let Some((x, y, view_width, view_height)) = tapped_position.lock().unwrap().take() {
+                let sensor_position = view_to_image_position(
+                    (x, y),
+                    (view_width, view_height),
+                    (displayed_image.width(), displayed_image.height()),
+                )
+                .and_then(|position| {
+                    coordinate_mapper.map_point(position, CoordinateSpace::Display, CoordinateSpace::Thermal)
+                })
+                .filter(|&position| coordinate_mapper.contains(position, CoordinateSpace::Thermal));
+                // the spot stays at the sensor position, tapping outside of the thermal image removes it
+                let mut settings = thermo_process_settings.lock().unwrap();
+                settings.spot_meters.retain(|spot| spot.name != TAP_SPOT_NAME);
+                if let Some((sensor_x, sensor_y)) = sensor_position {
+                    settings
+                        .spot_meters
+                        .push(SpotMeter::new(TAP_SPOT_NAME, sensor_x, sensor_y));
+                }
+            }
+
+            // the thermal image already contains the outlines
+            let displays_camera_image = !matches!(
+                mode,
+                DisplayMode::Thermal | DisplayMode::PictureInPicture | DisplayMode::SideBySide
+            );
+            if displays_camera_image {
+                thermocam::roi::draw_roi_outlines_into_image(
+                    &rois,
+                    &coordinate_mapper,
+                    thermocam::ROI_COLOR,
+                    &mut displayed_image,
+                );
+                thermocam::spot_meter::draw_spot_markers_into_image(
+                    &spot_meters,
+                    &coordinate_mapper,
+                    thermocam::SPOT_COLOR,
+                    &mut displayed_image,
+                );
+                if let Some(line_profile) = &line_profile {
+                    thermocam::line_profile::draw_profile_line_into_image(
+                        line_profile,
+                        &coordinate_mapper,
+                        thermocam::PROFILE_COLOR,
+                        &mut displayed_image,
+                    );
+                }
+            }
+            let profile_plot = thermocam::line_profile::render_profile_plot(
+                &processed_thermal_frame.profile_samples,
+                PROFILE_PLOT_WIDTH,
+                PROFILE_PLOT_HEIGHT,
+                thermocam::PROFILE_COLOR,
+                RgbColor { r: 0, g: 0, b: 0 },
+            );
+            let profile_active = !processed_thermal_frame.profile_samples.is_empty();
+            let profile_formatted = processed_thermal_frame
+                .profile_samples
+                .iter()
+                .map(|sample| sample.temperature)
+                .fold(None, |range: Option<(f32, f32)>, temperature| match range {
+                    Some((min, max)) => Some((min.min(temperature), max.max(temperature))),
+                    None => Some((temperature, temperature)),
+                })
+                .map(|(min, max)| format!("{max:.1}°C\n{min:.1}°C"))
+                .unwrap_or_default();
+            *profile_samples.lock().unwrap() = processed_thermal_frame.profile_samples.clone();
+            let spot_formatted = processed_thermal_frame
+                .spot_readings
+                .iter()
+                .map(|reading| format!("{}: {:.1}°C", reading.name, reading.temperature))
+                .chain(processed_thermal_frame.delta_readings.iter().map(|reading| {
+                    format!(
+                        "Δ{}-{}: {:+.1}°C",
+                        reading.delta.first, reading.delta.second, reading.difference
+                    )
+                }))
+                .collect::<Vec<String>>()
+                .join(" ");
+            let roi_stats_formatted = processed_thermal_frame
+                .roi_stats
+                .iter()
+                .map(|roi_stats| {
+                    format!(
+                        "{}: {:.1}/{:.1}/{:.1}°C σ{:.1}",
+                        roi_stats.name,
+                        roi_stats.stats.min.value,
+                        roi_stats.stats.mean,
+                        roi_stats.stats.max.value,
+                        roi_stats.stats.std_dev
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(" ");
+            if DEBUG_FEATURES {
+                for roi_stats in processed_thermal_frame.roi_stats.iter() {
+                    println!("ROI {} hotspot: {}", roi_stats.name, roi_stats.stats.max);
+                }
+            }
+
+            let isotherm_area_formatted = processed_thermal_frame
+                .isotherm_areas
+                .iter()
+                .map(|area| {
+                    format!(
+                        "{:.0}-{:.0}°C: {:.1}%",
+                        area.isotherm.min_temp, area.isotherm.max_temp, area.percentage
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(" ");
+            if DEBUG_FEATURES {
+                for area in processed_thermal_frame.isotherm_areas.iter() {
+                    println!(
+                        "Isotherm {}: {} pixels ({:.1}%)",
+                        area.isotherm, area.pixel_count, area.percentage
+                    );
+                }
+            }
+            let min_pixel_formatted = format!("Min: {:.2}°C", stats.min.value);
+            let mean_pixel_formatted = format!("Mean: {:.2}°C", stats.mean);
+            let max_pixel_formatted = format!("Max: {:.2}°C", stats.max.value);
 
             let min_scale_pixel_formatted = format!("{:.0}°C", min_manual_scale_temp);
             let max_scale_pixel_formatted = format!("{:.0}°C", max_manual_scale_temp);
@@ -265,6 +643,16 @@ fn main() -> std::io::Result<()> {
                 mw.set_min_temp_text(slint::SharedString::from(&min_pixel_formatted));
                 mw.set_mean_temp_text(slint::SharedString::from(&mean_pixel_formatted));
                 mw.set_max_temp_text(slint::SharedString::from(&max_pixel_formatted));
+                mw.set_isotherm_area_text(slint::SharedString::from(&isotherm_area_formatted));
+                mw.set_roi_stats_text(slint::SharedString::from(&roi_stats_formatted));
+                mw.set_spot_text(slint::SharedString::from(&spot_formatted));
+                mw.set_profile_active(profile_active);
+                mw.set_profile_image(slint::Image::from_rgb8(slint::SharedPixelBuffer::clone_from_slice(
+                    &profile_plot,
+                    profile_plot.width(),
+                    profile_plot.height(),
+                )));
+                mw.set_profile_text(slint::SharedString::from(&profile_formatted));
 
                 mw.set_lower_scale_temp_text(slint::SharedString::from(&min_scale_pixel_formatted));
                 mw.set_upper_scale_temp_text(slint::SharedString::from(&max_scale_pixel_formatted));
@@ -279,8 +667,204 @@ fn main() -> std::io::Result<()> {
     Ok(())
 }
 
-fn parse_cli() -> (bool, bool, u32, u32, String, f32, u32) {
-    let matches = clap::Command::new("thermocam")
+/// Estimates the registration from the point pairs in `calibration_file` and saves it to `registration_file`.
+fn calibrate_registration(calibration_file: &str, registration_file: Option<&str>) {
+    let calibration = Calibration::load(calibration_file).expect("Could not load the calibration file");
+    let registration = calibration.estimate().expect("Could not estimate the registration");
+    println!(
+        "{:?} registration from {} point pairs, RMS error {:.2} thermal pixels:",
+        calibration.model,
+        calibration.pairs.len(),
+        calibration.rms_error(&registration)
+    );
+    print!("{registration}");
+    if let Some(registration_file) = registration_file {
+        registration
+            .save(registration_file)
+            .expect("Could not save the registration file");
+        println!("saved to {registration_file}");
+    }
+}
+
+fn show_display_mode(main_window: &MainWindow, settings: &ThermoImageProcessor) {
+    let mode = settings.mode;
+    let mode_name = match mode {
+        DisplayMode::TemperatureGated => {
+            format!("{} {:.0}-{:.0}°C", mode, settings.gate_min_temp, settings.gate_max_temp)
+        }
+        _ => mode.to_string(),
+    };
+    main_window.set_mode_name(slint::SharedString::from(mode_name));
+    main_window.set_split_screen_active(mode == DisplayMode::SplitScreen);
+    main_window.set_edge_enhancement_active(mode == DisplayMode::EdgeEnhanced);
+    main_window.set_gate_active(mode == DisplayMode::TemperatureGated);
+}
+
+/// Applies `adjust` to the compensation of the target, regions without an own one start from the global one.
+fn adjust_emissivity(
+    settings: &mut ThermoImageProcessor,
+    target: usize,
+    adjust: impl Fn(EmissivityCompensation) -> EmissivityCompensation,
+) {
+    let global = settings.emissivity;
+    match target.checked_sub(1).and_then(|index| settings.rois.get_mut(index)) {
+        Some(roi) => roi.emissivity = Some(adjust(roi.emissivity.unwrap_or(global))),
+        None => settings.emissivity = adjust(global),
+    }
+}
+
+/// Global emissivity compensation and those of the regions of interest.
+fn emissivities_of_scene(settings: &ThermoImageProcessor) -> (EmissivityCompensation, Vec<RoiEmissivity>) {
+    let roi_emissivities = settings.rois.iter().filter_map(RoiEmissivity::from_roi).collect();
+    (settings.emissivity, roi_emissivities)
+}
+
+fn show_emissivity(main_window: &MainWindow, settings: &ThermoImageProcessor, target: usize) {
+    let (target_name, emissivity) = match target.checked_sub(1).and_then(|index| settings.rois.get(index)) {
+        Some(roi) => (roi.name.as_str(), roi.emissivity.unwrap_or(settings.emissivity)),
+        None => ("All", settings.emissivity),
+    };
+    main_window.set_emissivity_text(slint::SharedString::from(format!(
+        "{} ε{:.2} Tr{:.0}°C",
+        target_name, emissivity.emissivity, emissivity.reflected_temp
+    )));
+    let material_name = emissivity.material.map_or("custom", |material| material.name);
+    main_window.set_material_name(slint::SharedString::from(material_name));
+}
+
+/// Settings and main window shared by the UI callbacks.
+struct CallbackContext {
+    thermo_process_settings: Arc<Mutex<ThermoImageProcessor>>,
+    main_window_weak: slint::Weak<MainWindow>,
+}
+
+impl CallbackContext {
+    fn new(thermo_process_settings: &Arc<Mutex<ThermoImageProcessor>>, main_window: &MainWindow) -> Self {
+        CallbackContext {
+            thermo_process_settings: Arc::clone(thermo_process_settings),
+            main_window_weak: main_window.as_weak(),
+        }
+    }
+
+    /// Turns `handler` into a UI callback working on the locked settings and the main window.
+    fn settings(&self, handler: impl Fn(&mut ThermoImageProcessor, &MainWindow) + 'static) -> impl Fn() + 'static {
+        let thermo_process_settings = Arc::clone(&self.thermo_process_settings);
+        let main_window_weak = self.main_window_weak.clone();
+        move || handler(&mut thermo_process_settings.lock().unwrap(), &main_window_weak.unwrap())
+    }
+
+    /// Turns `adjust` into a UI callback changing the compensation selected by `emissivity_target`.
+    fn emissivity(
+        &self,
+        emissivity_target: &Arc<Mutex<usize>>,
+        adjust: impl Fn(EmissivityCompensation) -> EmissivityCompensation + 'static,
+    ) -> impl Fn() + 'static {
+        let emissivity_target = Arc::clone(emissivity_target);
+        self.settings(move |settings, main_window| {
+            let target = *emissivity_target.lock().unwrap();
+            adjust_emissivity(settings, target, &adjust);
+            show_emissivity(main_window, settings, target);
+        })
+    }
+}
+
+fn generate_scale_image(palette: &Palette) -> slint::Image {
+    let col_buf = palette.discrete_blend(COLOR_BLEND_STEPS);
+    let mut buf: Vec<u8> = Vec::new();
+    for c in col_buf.iter().rev() {
+        buf.extend(c.to_vec());
+    }
+    let scale_img = image::RgbImage::from_raw(1, COLOR_BLEND_STEPS, buf).unwrap();
+    let scale_upscaled_img = image::imageops::resize(&scale_img, 15, scale_img.height(), FilterType::Nearest);
+    slint::Image::from_rgb8(slint::SharedPixelBuffer::clone_from_slice(
+        scale_upscaled_img.as_raw(),
+        scale_upscaled_img.width(),
+        scale_upscaled_img.height(),
+    ))
+}
+
+fn open_thermal_source(
+    use_simulation_data: bool,
+    recording: Option<RecordedSource>,
+    frame_rate: mlx9064x::FrameRate,
+    period: u64,
+) -> Box<dyn ThermalSource> {
+    if let Some(recording) = recording {
+        return Box::new(recording);
+    }
+    if use_simulation_data {
+        #[cfg(not(target_arch = "arm"))]
+        return Box::new(
+            thermocam::thermal_source::NpySimulationSource::open("data/flir_f32.npy", period)
+                .expect("Failed to load thermal simulation data"),
+        );
+        #[cfg(target_arch = "arm")]
+        panic!("thermal simulation data is not supported on arm");
+    }
+    // Default address for these cameras is 0x33
+    Box::new(Mlx90640Source::new("/dev/i2c-1", 0x33, frame_rate).expect("/dev/i2c-1 needs to be an I2C controller"))
+}
+
+fn open_visible_source(
+    use_simulation_data: bool,
+    camera_image_width: u32,
+    camera_image_height: u32,
+    fourcc: FourCC,
+    decoders: &DecoderRegistry,
+) -> Box<dyn VisibleSource> {
+    if use_simulation_data {
+        // recorded frame is SGRBG10P
+        let sim_fourcc = FourCC::new(b"pGAA");
+        let sim_shape = (camera_image_width, camera_image_height);
+        let frame_size = decoders.frame_size(sim_fourcc, sim_shape).unwrap().unwrap();
+        return Box::new(
+            FileReplaySource::open("data/received_image_data.bin", sim_fourcc, sim_shape, frame_size)
+                .expect("Failed to load camera simulation data"),
+        );
+    }
+    Box::new(V4lSource::new(0, camera_image_width, camera_image_height, fourcc).expect("Failed to open camera device"))
+}
+
+/// Settings given on the command line.
+struct CliOptions {
+    use_simulation_data: bool,
+    deactivate_autoscale: bool,
+    camera_image_width: u32,
+    camera_image_height: u32,
+    /// Requested pixel format of the visible camera
+    new_fourcc: String,
+    foreground_alpha: f32,
+    mode_in: DisplayMode,
+    yuv_conversion: YuvConversion,
+    palette: Palette,
+    upscale_filter: UpscaleFilter,
+    registration_file: Option<String>,
+    calibration_file: Option<String>,
+    auto_alignment_enabled: bool,
+    edge_strength: f32,
+    gate_min_temp: f32,
+    gate_max_temp: f32,
+    isotherms: Vec<Isotherm>,
+    contour_interval: Option<f32>,
+    /// `None` if not given, so a recording can provide it
+    emissivity: Option<EmissivityCompensation>,
+    /// `None` if not given, so a recording can provide it
+    atmosphere: Option<Atmosphere>,
+    rois: Vec<Roi>,
+    spot_meters: Vec<SpotMeter>,
+    temperature_deltas: Vec<TemperatureDelta>,
+    line_profile: Option<LineProfile>,
+    profile_csv_file: String,
+    thermal_orientation: Orientation,
+    visible_orientation: Orientation,
+    temporal_filter: TemporalFilter,
+    motion_threshold: Option<f32>,
+    record_file: Option<String>,
+    replay_file: Option<String>,
+}
+
+fn parse_cli() -> CliOptions {
+    let mut command = clap::Command::new("thermocam")
         .arg(
             clap::Arg::new("deactivate_autoscale")
                 .short('d')
@@ -310,6 +894,7 @@ fn parse_cli() -> (bool, bool, u32, u32, String, f32, u32) {
         .arg(
             clap::Arg::new("fourcc")
                 .short('f')
+                .help("Camera pixel format (pGAA, YUYV, YU12, NV12, RGB3 or MJPG)")
                 .default_value("pGAA")
                 .value_parser(clap::value_parser!(String)),
         )
@@ -322,10 +907,222 @@ fn parse_cli() -> (bool, bool, u32, u32, String, f32, u32) {
         .arg(
             clap::Arg::new("mode")
                 .short('m')
-                .default_value("0")
-                .value_parser(clap::value_parser!(u32)),
+                .help("Display mode (blend, visible, thermal, pip, split, side-by-side, msx, gated or contours)")
+                .default_value("blend")
+                .value_parser(clap::value_parser!(DisplayMode)),
+        )
+        .arg(
+            clap::Arg::new("yuv_matrix")
+                .long("yuv-matrix")
+                .help("YUV matrix of YUYV/YU12/NV12 cameras (bt601 or bt709)")
+                .default_value("bt601")
+                .value_parser(clap::value_parser!(YuvMatrix)),
+        )
+        .arg(
+            clap::Arg::new("yuv_range")
+                .long("yuv-range")
+                .help("YUV range of YUYV/YU12/NV12 cameras (limited or full)")
+                .default_value("limited")
+                .value_parser(clap::value_parser!(YuvRange)),
+        )
+        .arg(
+            clap::Arg::new("palette")
+                .short('p')
+                .help("False-color palette (ironbow, rainbow, whitehot, blackhot, viridis, cividis, magma or bluered)")
+                .default_value("ironbow")
+                .value_parser(clap::value_parser!(Palette)),
+        )
+        .arg(
+            clap::Arg::new("palette_file")
+                .long("palette-file")
+                .help("Load a custom false-color palette from a file (overrides -p)")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            clap::Arg::new("upscale_filter")
+                .long("upscale-filter")
+                .help("Filter for upscaling the temperatures (bilinear, bicubic or lanczos)")
+                .default_value("lanczos")
+                .value_parser(clap::value_parser!(UpscaleFilter)),
+        )
+        .arg(
+            clap::Arg::new("registration_file")
+                .long("registration")
+                .help("Registration of the thermal image in the visible image (written by --calibrate)")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            clap::Arg::new("calibration_file")
+                .long("calibrate")
+                .help("Estimate the registration from the point pairs in a file, save it to --registration and exit")
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            clap::Arg::new("auto_align")
+                .long("auto-align")
+                .help("Continuously refine the registration by correlating the edges of both images")
+                .action(clap::ArgAction::SetTrue),
         )
-        .get_matches();
+        .arg(
+            clap::Arg::new("edge_strength")
+                .long("edge-strength")
+                .help("Amount of camera detail added to the thermal image in msx mode")
+                .default_value("1.0")
+                .value_parser(thermocam::parse_edge_strength),
+        )
+        .arg(
+            clap::Arg::new("gate_min_temp")
+                .long("gate-min")
+                .help("Lower temperature of the band that is colorized in gated mode")
+                .default_value("30.0")
+                .value_parser(clap::value_parser!(f32)),
+        )
+        .arg(
+            clap::Arg::new("gate_max_temp")
+                .long("gate-max")
+                .help("Upper temperature of the band that is colorized in gated mode")
+                .default_value("100.0")
+                .value_parser(clap::value_parser!(f32)),
+        )
+        .arg(
+            clap::Arg::new("isotherm")
+                .long("isotherm")
+                .help("Draw a temperature band in a solid color, e.g. 40:60:#ffff00 (can be repeated)")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::value_parser!(Isotherm)),
+        )
+        .arg(
+            clap::Arg::new("contour_interval")
+                .long("contour-interval")
+                .help("Draw isolines every given number of °C (at least 0.01) on the thermal image")
+                .value_parser(thermocam::contour::parse_contour_interval),
+        )
+        .arg(
+            clap::Arg::new("emissivity")
+                .long("emissivity")
+                .help("Emissivity of the scene (0.01-1.0), temperatures are corrected for it")
+                .default_value("1.0")
+                .value_parser(thermocam::emissivity::parse_emissivity),
+        )
+        .arg(
+            clap::Arg::new("material")
+                .long("material")
+                .help("Material of the scene whose typical emissivity is used instead of --emissivity, e.g. brick or oxidized-copper")
+                .value_parser(clap::value_parser!(Material)),
+        )
+        .arg(
+            clap::Arg::new("reflected_temp")
+                .long("reflected-temp")
+                .help("Apparent temperature of the surroundings reflected by the scene")
+                .default_value("20.0")
+                .value_parser(clap::value_parser!(f32)),
+        )
+        .arg(
+            clap::Arg::new("distance")
+                .long("distance")
+                .help("Distance (m) to the scene, the absorption of the air in between is corrected")
+                .default_value("0.0")
+                .value_parser(clap::value_parser!(f32)),
+        )
+        .arg(
+            clap::Arg::new("air_temp")
+                .long("air-temp")
+                .help("Temperature of the air between camera and scene")
+                .default_value("20.0")
+                .value_parser(clap::value_parser!(f32)),
+        )
+        .arg(
+            clap::Arg::new("humidity")
+                .long("humidity")
+                .help("Relative humidity (%) of the air between camera and scene")
+                .default_value("50.0")
+                .value_parser(clap::value_parser!(f32)),
+        )
+        .arg(
+            clap::Arg::new("atmosphere_file")
+                .long("atmosphere")
+                .help("Load distance, air temperature and humidity from a file, e.g. saved next to a recording (instead of --distance, --air-temp and --humidity)")
+                .conflicts_with_all(["distance", "air_temp", "humidity"])
+                .value_parser(clap::value_parser!(String)),
+        )
+        .arg(
+            clap::Arg::new("roi")
+                .long("roi")
+                .help("Region of interest in sensor coordinates, e.g. A:rect:4,4,8,6, B:ellipse:20,12,3,2 or C:polygon:1,1,5,1,3,4 (can be repeated)")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::value_parser!(Roi)),
+        )
+        .arg(
+            clap::Arg::new("roi_emissivity")
+                .long("roi-emissivity")
+                .help("Emissivity (value or material) and optional reflected temperature of a region of interest, e.g. A:0.95 or B:oxidized-copper:25 (can be repeated)")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::value_parser!(RoiEmissivity)),
+        )
+        .arg(
+            clap::Arg::new("spot")
+                .long("spot")
+                .help("Spot meter in sensor coordinates with an optional averaging radius, e.g. S1:16,12 or S2:8.5,4.5:1.5 (can be repeated)")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::value_parser!(SpotMeter)),
+        )
+        .arg(
+            clap::Arg::new("delta")
+                .long("delta")
+                .help("Temperature difference between two spots or regions of interest (hotspot), e.g. S1:S2 (can be repeated)")
+                .action(clap::ArgAction::Append)
+                .value_parser(clap::value_parser!(TemperatureDelta)),
+        )
+        .arg(
+            clap::Arg::new("thermal_orientation")
+                .long("thermal-orientation")
+                .help("Flips/rotation of the thermal image: none or a combination of fliph, flipv and rot90 (clockwise), e.g. flipv,rot90")
+                .default_value("none")
+                .value_parser(clap::value_parser!(Orientation)),
+        )
+        .arg(
+            clap::Arg::new("visible_orientation")
+                .long("visible-orientation")
+                .help("Flips/rotation of the camera image: none or a combination of fliph, flipv and rot90 (clockwise)")
+                .default_value("fliph")
+                .value_parser(clap::value_parser!(Orientation)),
+        )
+        .arg(
+            clap::Arg::new("temporal_filter")
+                .long("temporal-filter")
+                .help("Filter over consecutive thermal frames against noise and flicker: none, average[:<frames>], ema[:<alpha>] or kalman[:<process noise>,<measurement noise>]")
+                .default_value("none")
+                .value_parser(clap::value_parser!(TemporalFilter)),
+        )
+        .arg(
+            clap::Arg::new("motion_threshold")
+                .long("motion-threshold")
+                .help("Pixels that change by more than this (°C) restart the temporal filter, so moving objects don't smear")
+                .value_parser(thermocam::temporal_filter::parse_motion_threshold),
+        )
+        .arg(
+            clap::Arg::new("profile")
+                .long("profile")
+                .help("Line profile from x1,y1 to x2,y2 in sensor coordinates, e.g. 2,12,30,12")
+                .value_parser(clap::value_parser!(LineProfile)),
+        )
+        .arg(
+            clap::Arg::new("profile_csv")
+                .long("profile-csv")
+                .help("CSV file the line profile is exported to, relative paths start at the working directory at startup")
+                .default_value("data/line_profile.csv"),
+        )
+        .arg(
+            clap::Arg::new("record_file")
+                .long("record")
+                .help("Record the raw thermal frames to a file, the frame size, the emissivity and the atmosphere are saved next to it (<file>.shape, <file>.emissivity, <file>.atmosphere)"),
+        )
+        .arg(
+            clap::Arg::new("replay_file")
+                .long("replay")
+                .help("Replay thermal frames recorded with --record instead of reading the sensor, with the emissivity and the atmosphere saved next to them unless set on the command line"),
+        );
+    let matches = command.get_matches_mut();
     let use_simulation_data = matches.get_flag("simulation_data");
     let deactivate_autoscale = matches.get_flag("deactivate_autoscale");
     let camera_image_width = matches
@@ -345,16 +1142,177 @@ fn parse_cli() -> (bool, bool, u32, u32, String, f32, u32) {
         .expect("Could not read a foreground_alpha")
         .expect("Could not read a foreground_alpha");
     let mode = matches
-        .try_get_one::<u32>("mode")
+        .try_get_one::<DisplayMode>("mode")
         .expect("Could not read a mode")
         .expect("Could not read a mode");
-    (
+    let yuv_matrix = matches
+        .try_get_one::<YuvMatrix>("yuv_matrix")
+        .expect("Could not read a yuv_matrix")
+        .expect("Could not read a yuv_matrix");
+    let yuv_range = matches
+        .try_get_one::<YuvRange>("yuv_range")
+        .expect("Could not read a yuv_range")
+        .expect("Could not read a yuv_range");
+    let palette = matches
+        .try_get_one::<Palette>("palette")
+        .expect("Could not read a palette")
+        .expect("Could not read a palette");
+    let palette = match matches.get_one::<String>("palette_file") {
+        Some(palette_file) => Palette::load(palette_file).expect("Could not load the palette file"),
+        None => palette.clone(),
+    };
+    let upscale_filter = matches
+        .try_get_one::<UpscaleFilter>("upscale_filter")
+        .expect("Could not read an upscale_filter")
+        .expect("Could not read an upscale_filter");
+    let registration_file = matches.get_one::<String>("registration_file").cloned();
+    let calibration_file = matches.get_one::<String>("calibration_file").cloned();
+    let auto_alignment_enabled = matches.get_flag("auto_align");
+    let edge_strength = matches
+        .try_get_one::<f32>("edge_strength")
+        .expect("Could not read an edge_strength")
+        .expect("Could not read an edge_strength");
+    let gate_min_temp = matches
+        .try_get_one::<f32>("gate_min_temp")
+        .expect("Could not read a gate_min_temp")
+        .expect("Could not read a gate_min_temp");
+    let gate_max_temp = matches
+        .try_get_one::<f32>("gate_max_temp")
+        .expect("Could not read a gate_max_temp")
+        .expect("Could not read a gate_max_temp");
+    if gate_min_temp > gate_max_temp {
+        command
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                format!("--gate-min ({gate_min_temp}) is above --gate-max ({gate_max_temp})"),
+            )
+            .exit();
+    }
+    let isotherms = matches
+        .get_many::<Isotherm>("isotherm")
+        .map(|isotherms| isotherms.copied().collect())
+        .unwrap_or_default();
+    let contour_interval = matches.get_one::<f32>("contour_interval").copied();
+    let emissivity = matches
+        .try_get_one::<f32>("emissivity")
+        .expect("Could not read an emissivity")
+        .expect("Could not read an emissivity");
+    let reflected_temp = matches
+        .try_get_one::<f32>("reflected_temp")
+        .expect("Could not read a reflected temperature")
+        .expect("Could not read a reflected temperature");
+    let emissivity = match matches.get_one::<Material>("material") {
+        Some(material) => EmissivityCompensation::new(*emissivity, *reflected_temp).with_material(*material),
+        None => EmissivityCompensation::new(*emissivity, *reflected_temp),
+    };
+    // only set on the command line it takes precedence over the emissivity saved with a replayed recording
+    let emissivity_given = ["emissivity", "reflected_temp", "material"]
+        .iter()
+        .any(|id| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine));
+    let distance = matches
+        .try_get_one::<f32>("distance")
+        .expect("Could not read a distance")
+        .expect("Could not read a distance");
+    let air_temp = matches
+        .try_get_one::<f32>("air_temp")
+        .expect("Could not read an air temperature")
+        .expect("Could not read an air temperature");
+    let humidity = matches
+        .try_get_one::<f32>("humidity")
+        .expect("Could not read a humidity")
+        .expect("Could not read a humidity");
+    let atmosphere = match matches.get_one::<String>("atmosphere_file") {
+        Some(atmosphere_file) => Atmosphere::load(atmosphere_file).expect("Could not load the atmosphere file"),
+        None => Atmosphere::new(*distance, *air_temp, *humidity),
+    };
+    let atmosphere_given = ["distance", "air_temp", "humidity", "atmosphere_file"]
+        .iter()
+        .any(|id| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine));
+    let mut rois: Vec<Roi> = matches
+        .get_many::<Roi>("roi")
+        .map(|rois| rois.cloned().collect())
+        .unwrap_or_default();
+    for roi_emissivity in matches
+        .get_many::<RoiEmissivity>("roi_emissivity")
+        .into_iter()
+        .flatten()
+    {
+        let Some(roi) = rois.iter_mut().find(|roi| roi.name == roi_emissivity.name) else {
+            command
+                .error(
+                    clap::error::ErrorKind::InvalidValue,
+                    format!(
+                        "--roi-emissivity refers to the unknown region '{}'",
+                        roi_emissivity.name
+                    ),
+                )
+                .exit();
+        };
+        roi.emissivity = Some(roi_emissivity.compensation(emissivity.reflected_temp));
+    }
+    let spot_meters = matches
+        .get_many::<SpotMeter>("spot")
+        .map(|spot_meters| spot_meters.cloned().collect())
+        .unwrap_or_default();
+    let temperature_deltas = matches
+        .get_many::<TemperatureDelta>("delta")
+        .map(|temperature_deltas| temperature_deltas.cloned().collect())
+        .unwrap_or_default();
+    let line_profile = matches.get_one::<LineProfile>("profile").copied();
+    let thermal_orientation = matches
+        .try_get_one::<Orientation>("thermal_orientation")
+        .expect("Could not read the thermal orientation")
+        .expect("thermal orientation not found");
+    let visible_orientation = matches
+        .try_get_one::<Orientation>("visible_orientation")
+        .expect("Could not read the camera orientation")
+        .expect("camera orientation not found");
+    let temporal_filter = matches
+        .try_get_one::<TemporalFilter>("temporal_filter")
+        .expect("Could not read a temporal filter")
+        .expect("Could not read a temporal filter");
+    let motion_threshold = matches.get_one::<f32>("motion_threshold").copied();
+    let profile_csv_file = matches
+        .try_get_one::<String>("profile_csv")
+        .expect("Could not read the profile CSV file")
+        .expect("profile CSV file not found");
+    // resolved once, so the UI can show where the export goes
+    let profile_csv_file = std::env::current_dir()
+        .expect("Could not read the working directory")
+        .join(profile_csv_file)
+        .to_string_lossy()
+        .into_owned();
+    CliOptions {
         use_simulation_data,
         deactivate_autoscale,
-        *camera_image_width,
-        *camera_image_height,
-        fourcc.clone(),
-        *foreground_alpha,
-        *mode,
-    )
+        camera_image_width: *camera_image_width,
+        camera_image_height: *camera_image_height,
+        new_fourcc: fourcc.clone(),
+        foreground_alpha: *foreground_alpha,
+        mode_in: *mode,
+        yuv_conversion: YuvConversion::new(*yuv_matrix, *yuv_range),
+        palette,
+        upscale_filter: *upscale_filter,
+        registration_file,
+        calibration_file,
+        auto_alignment_enabled,
+        edge_strength: *edge_strength,
+        gate_min_temp: *gate_min_temp,
+        gate_max_temp: *gate_max_temp,
+        isotherms,
+        contour_interval,
+        emissivity: emissivity_given.then_some(emissivity),
+        atmosphere: atmosphere_given.then_some(atmosphere),
+        rois,
+        spot_meters,
+        temperature_deltas,
+        line_profile,
+        profile_csv_file,
+        thermal_orientation: *thermal_orientation,
+        visible_orientation: *visible_orientation,
+        temporal_filter: *temporal_filter,
+        motion_threshold,
+        record_file: matches.get_one::<String>("record_file").cloned(),
+        replay_file: matches.get_one::<String>("replay_file").cloned(),
+    }
 }
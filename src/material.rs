@@ -0,0 +1,122 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Material with its typical emissivity in the long-wave infrared (8-14 µm).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Material {
+    pub name: &'static str,
+    pub emissivity: f32,
+}
+
+/// Built-in materials in the order they are cycled through in the UI. The values are typical, the emissivity of
+/// a real surface depends on its finish and condition.
+pub const MATERIALS: [Material; 20] = [
+    Material::new("human-skin", 0.98),
+    Material::new("water", 0.96),
+    Material::new("ice", 0.97),
+    Material::new("black-paint", 0.97),
+    Material::new("electrical-tape", 0.95),
+    Material::new("rubber", 0.95),
+    Material::new("asphalt", 0.95),
+    Material::new("paper", 0.93),
+    Material::new("pvc", 0.93),
+    Material::new("brick", 0.93),
+    Material::new("concrete", 0.92),
+    Material::new("glass", 0.92),
+    Material::new("plaster", 0.91),
+    Material::new("wood", 0.90),
+    Material::new("oxidized-steel", 0.80),
+    Material::new("oxidized-copper", 0.78),
+    Material::new("anodized-aluminium", 0.77),
+    Material::new("stainless-steel", 0.16),
+    Material::new("polished-aluminium", 0.05),
+    Material::new("polished-copper", 0.03),
+];
+
+impl Material {
+    pub const fn new(name: &'static str, emissivity: f32) -> Self {
+        Material { name, emissivity }
+    }
+
+    /// Built-in material by name, case-insensitive and with spaces or underscores instead of hyphens.
+    pub fn builtin(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase().replace([' ', '_'], "-");
+        MATERIALS.iter().find(|material| material.name == name).copied()
+    }
+
+    /// The built-in material following this one in [`MATERIALS`] (wraps around).
+    pub fn next_builtin(&self) -> Self {
+        let next_idx = match MATERIALS.iter().position(|material| material.name == self.name) {
+            Some(idx) => (idx + 1) % MATERIALS.len(),
+            None => 0,
+        };
+        MATERIALS[next_idx]
+    }
+}
+
+impl FromStr for Material {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Material::builtin(s).ok_or_else(|| {
+            let names: Vec<&str> = MATERIALS.iter().map(|material| material.name).collect();
+            format!("unknown material '{s}' (choose one of {})", names.join(", "))
+        })
+    }
+}
+
+impl fmt::Display for Material {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emissivity::MIN_EMISSIVITY;
+
+    #[test]
+    fn builtin_names_are_normalized() {
+        for name in ["human-skin", "Human Skin", "human_skin", " HUMAN-SKIN "] {
+            assert_eq!(Material::builtin(name), Some(MATERIALS[0]), "{name:?}");
+        }
+        assert_eq!("Polished Copper".parse::<Material>().unwrap().emissivity, 0.03);
+        assert_eq!(MATERIALS[0].to_string(), "human-skin");
+    }
+
+    #[test]
+    fn unknown_materials() {
+        assert_eq!(Material::builtin("humanskin"), None);
+        assert_eq!(Material::builtin(""), None);
+        let error = "unobtainium".parse::<Material>().unwrap_err();
+        assert!(error.starts_with("unknown material 'unobtainium' (choose one of human-skin, water,"));
+        assert!(error.ends_with("polished-copper)"));
+    }
+
+    #[test]
+    fn cycles_through_the_builtin_materials() {
+        let mut material = MATERIALS[0];
+        for expected in MATERIALS.iter().skip(1).chain(MATERIALS.iter().take(1)) {
+            material = material.next_builtin();
+            assert_eq!(material, *expected);
+        }
+        assert_eq!(Material::new("custom", 0.5).next_builtin(), MATERIALS[0]);
+    }
+
+    #[test]
+    fn builtin_materials_are_valid() {
+        for (i, material) in MATERIALS.iter().enumerate() {
+            assert!(
+                material.emissivity >= MIN_EMISSIVITY && material.emissivity <= 1.0,
+                "{material}"
+            );
+            assert!(
+                MATERIALS[i + 1..].iter().all(|other| other.name != material.name),
+                "{material} is listed twice"
+            );
+            // reachable from the command line
+            assert_eq!(Material::builtin(material.name), Some(*material));
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+use std::str::FromStr;
+
+use image;
+
+use crate::temperature_field::TemperatureField;
+
+/// Flips and rotation from the raw frame of a sensor to the orientation it is displayed in. The flips are applied
+/// to the raw frame first, then it is rotated by 90° clockwise.
+///
+/// Points are continuous pixel coordinates: pixel (x, y) covers x..x+1 and y..y+1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Orientation {
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    pub rotate_90: bool,
+}
+
+impl Orientation {
+    /// The raw frame as it is.
+    pub fn identity() -> Self {
+        Orientation::default()
+    }
+
+    pub fn with_flip_horizontal(mut self, flip_horizontal: bool) -> Self {
+        self.flip_horizontal = flip_horizontal;
+        self
+    }
+
+    pub fn with_flip_vertical(mut self, flip_vertical: bool) -> Self {
+        self.flip_vertical = flip_vertical;
+        self
+    }
+
+    pub fn with_rotate_90(mut self, rotate_90: bool) -> Self {
+        self.rotate_90 = rotate_90;
+        self
+    }
+
+    /// Size of a raw frame of size `shape` after orientation.
+    pub fn oriented_shape(&self, shape: (u32, u32)) -> (u32, u32) {
+        if self.rotate_90 {
+            (shape.1, shape.0)
+        } else {
+            shape
+        }
+    }
+
+    /// Position in the oriented frame of the point (x, y) of a raw frame of size `shape`.
+    pub fn apply(&self, (x, y): (f32, f32), shape: (u32, u32)) -> (f32, f32) {
+        let (width, height) = (shape.0 as f32, shape.1 as f32);
+        let x = if self.flip_horizontal { width - x } else { x };
+        let y = if self.flip_vertical { height - y } else { y };
+        if self.rotate_90 {
+            (height - y, x)
+        } else {
+            (x, y)
+        }
+    }
+
+    /// Position in the raw frame of size `shape` of the point (x, y) of the oriented frame.
+    pub fn revert(&self, (x, y): (f32, f32), shape: (u32, u32)) -> (f32, f32) {
+        let (width, height) = (shape.0 as f32, shape.1 as f32);
+        let (x, y) = if self.rotate_90 { (y, height - x) } else { (x, y) };
+        let x = if self.flip_horizontal { width - x } else { x };
+        let y = if self.flip_vertical { height - y } else { y };
+        (x, y)
+    }
+
+    /// The raw image in this orientation.
+    pub fn apply_to_image(&self, image: image::RgbImage) -> image::RgbImage {
+        let mut image = image;
+        if self.flip_horizontal {
+            image::imageops::flip_horizontal_in_place(&mut image);
+        }
+        if self.flip_vertical {
+            image::imageops::flip_vertical_in_place(&mut image);
+        }
+        if self.rotate_90 {
+            image = image::imageops::rotate90(&image);
+        }
+        image
+    }
+
+    /// The raw temperature field in this orientation.
+    pub fn apply_to_field(&self, field: &TemperatureField) -> TemperatureField {
+        if *self == Orientation::identity() {
+            return field.clone();
+        }
+        let shape = (field.width, field.height);
+        let (width, height) = self.oriented_shape(shape);
+        let mut data = Vec::with_capacity(field.data.len());
+        for y in 0..height {
+            for x in 0..width {
+                let (raw_x, raw_y) = self.revert((x as f32 + 0.5, y as f32 + 0.5), shape);
+                data.push(field.data[(raw_y as u32 * field.width + raw_x as u32) as usize]);
+            }
+        }
+        TemperatureField::new(width, height, data)
+    }
+}
+
+/// Comma-separated list of `fliph`, `flipv` and `rot90`, or `none`.
+impl FromStr for Orientation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut orientation = Orientation::identity();
+        for part in s.split(',').map(|part| part.trim().to_lowercase()) {
+            match part.as_str() {
+                "none" => {}
+                "fliph" => orientation.flip_horizontal = true,
+                "flipv" => orientation.flip_vertical = true,
+                "rot90" => orientation.rotate_90 = true,
+                _ => {
+                    return Err(format!(
+                        "unknown orientation '{part}' (choose none or a combination of fliph, flipv and rot90)"
+                    ))
+                }
+            }
+        }
+        Ok(orientation)
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<&str> = [
+            (self.flip_horizontal, "fliph"),
+            (self.flip_vertical, "flipv"),
+            (self.rotate_90, "rot90"),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, name)| *name)
+        .collect();
+        if parts.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", parts.join(","))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Non-square, so swapped width and height show up.
+    const SHAPE: (u32, u32) = (5, 3);
+
+    /// All combinations of the flips and the rotation, i.e. both mirrorings and the rotations by 90°, 180° and 270°.
+    fn all_orientations() -> Vec<Orientation> {
+        let mut orientations = Vec::new();
+        for bits in 0..8 {
+            orientations.push(
+                Orientation::identity()
+                    .with_flip_horizontal(bits & 1 != 0)
+                    .with_flip_vertical(bits & 2 != 0)
+                    .with_rotate_90(bits & 4 != 0),
+            );
+        }
+        orientations
+    }
+
+    #[test]
+    fn revert_undoes_apply() {
+        let points = [(0.0, 0.0), (0.5, 0.5), (4.5, 0.5), (1.25, 2.75), (5.0, 3.0), (3.0, 1.5)];
+        for orientation in all_orientations() {
+            for point in points {
+                let oriented = orientation.apply(point, SHAPE);
+                let (oriented_width, oriented_height) = orientation.oriented_shape(SHAPE);
+                assert!(
+                    (0.0..=oriented_width as f32).contains(&oriented.0)
+                        && (0.0..=oriented_height as f32).contains(&oriented.1),
+                    "{orientation}: {point:?} -> {oriented:?}"
+                );
+                assert_eq!(orientation.revert(oriented, SHAPE), point, "{orientation}");
+            }
+        }
+    }
+
+    #[test]
+    fn rotations_are_clockwise() {
+        let top_left = (0.5, 0.5);
+        let rotate_90 = Orientation::identity().with_rotate_90(true);
+        assert_eq!(rotate_90.oriented_shape(SHAPE), (3, 5));
+        assert_eq!(rotate_90.apply(top_left, SHAPE), (2.5, 0.5));
+        let rotate_180 = Orientation::identity()
+            .with_flip_horizontal(true)
+            .with_flip_vertical(true);
+        assert_eq!(rotate_180.apply(top_left, SHAPE), (4.5, 2.5));
+        let rotate_270 = rotate_180.with_rotate_90(true);
+        assert_eq!(rotate_270.apply(top_left, SHAPE), (0.5, 4.5));
+    }
+
+    #[test]
+    fn images_and_fields_follow_the_points() {
+        let (width, height) = SHAPE;
+        let value = |x: u32, y: u32| (y * width + x) as u8;
+        let image = image::RgbImage::from_fn(width, height, |x, y| image::Rgb([value(x, y), 0, 0]));
+        let field = TemperatureField::new(
+            width,
+            height,
+            (0..height)
+                .flat_map(|y| (0..width).map(move |x| value(x, y) as f32))
+                .collect(),
+        );
+        for orientation in all_orientations() {
+            let oriented_image = orientation.apply_to_image(image.clone());
+            let oriented_field = orientation.apply_to_field(&field);
+            assert_eq!(oriented_image.dimensions(), orientation.oriented_shape(SHAPE));
+            assert_eq!(
+                (oriented_field.width, oriented_field.height),
+                orientation.oriented_shape(SHAPE)
+            );
+            for y in 0..height {
+                for x in 0..width {
+                    let (oriented_x, oriented_y) = orientation.apply((x as f32 + 0.5, y as f32 + 0.5), SHAPE);
+                    let (oriented_x, oriented_y) = (oriented_x as u32, oriented_y as u32);
+                    assert_eq!(
+                        oriented_image.get_pixel(oriented_x, oriented_y).0[0],
+                        value(x, y),
+                        "{orientation}"
+                    );
+                    assert_eq!(
+                        oriented_field.data[(oriented_y * oriented_field.width + oriented_x) as usize],
+                        value(x, y) as f32,
+                        "{orientation}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_and_display() {
+        for orientation in all_orientations() {
+            assert_eq!(orientation.to_string().parse(), Ok(orientation));
+        }
+        assert!("rot180".parse::<Orientation>().is_err());
+    }
+}
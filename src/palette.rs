@@ -0,0 +1,502 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::rgb_color::RgbColor;
+
+/// Names of the built-in palettes, in the order they are cycled through in the UI.
+pub const BUILTIN_PALETTE_NAMES: [&str; 8] = [
+    "ironbow", "rainbow", "whitehot", "blackhot", "viridis", "cividis", "magma", "bluered",
+];
+
+const IRONBOW: [(f32, [u8; 3]); 8] = [
+    (0.0, [0, 0, 10]),
+    (0.15, [40, 0, 120]),
+    (0.3, [120, 0, 150]),
+    (0.45, [190, 30, 110]),
+    (0.6, [230, 80, 30]),
+    (0.75, [250, 150, 0]),
+    (0.9, [255, 220, 50]),
+    (1.0, [255, 255, 230]),
+];
+
+const RAINBOW: [(f32, [u8; 3]); 6] = [
+    (0.0, [0, 0, 130]),
+    (0.2, [0, 0, 255]),
+    (0.4, [0, 255, 255]),
+    (0.6, [0, 255, 0]),
+    (0.8, [255, 255, 0]),
+    (1.0, [255, 0, 0]),
+];
+
+const WHITEHOT: [(f32, [u8; 3]); 2] = [(0.0, [0, 0, 0]), (1.0, [255, 255, 255])];
+
+const BLACKHOT: [(f32, [u8; 3]); 2] = [(0.0, [255, 255, 255]), (1.0, [0, 0, 0])];
+
+// perceptually uniform and colorblind-safe (matplotlib), sampled at 10 equidistant positions
+const VIRIDIS: [(f32, [u8; 3]); 10] = [
+    (0.0 / 9.0, [0x44, 0x01, 0x54]),
+    (1.0 / 9.0, [0x48, 0x28, 0x78]),
+    (2.0 / 9.0, [0x3e, 0x49, 0x89]),
+    (3.0 / 9.0, [0x31, 0x68, 0x8e]),
+    (4.0 / 9.0, [0x26, 0x82, 0x8e]),
+    (5.0 / 9.0, [0x1f, 0x9e, 0x89]),
+    (6.0 / 9.0, [0x35, 0xb7, 0x79]),
+    (7.0 / 9.0, [0x6e, 0xce, 0x58]),
+    (8.0 / 9.0, [0xb5, 0xde, 0x2b]),
+    (1.0, [0xfd, 0xe7, 0x25]),
+];
+
+const CIVIDIS: [(f32, [u8; 3]); 10] = [
+    (0.0 / 9.0, [0x00, 0x22, 0x4e]),
+    (1.0 / 9.0, [0x12, 0x35, 0x70]),
+    (2.0 / 9.0, [0x3b, 0x49, 0x6c]),
+    (3.0 / 9.0, [0x57, 0x5d, 0x6d]),
+    (4.0 / 9.0, [0x70, 0x71, 0x73]),
+    (5.0 / 9.0, [0x8a, 0x86, 0x78]),
+    (6.0 / 9.0, [0xa5, 0x9c, 0x74]),
+    (7.0 / 9.0, [0xc3, 0xb3, 0x69]),
+    (8.0 / 9.0, [0xe1, 0xcc, 0x55]),
+    (1.0, [0xfe, 0xe8, 0x38]),
+];
+
+const MAGMA: [(f32, [u8; 3]); 10] = [
+    (0.0 / 9.0, [0x00, 0x00, 0x04]),
+    (1.0 / 9.0, [0x18, 0x0f, 0x3d]),
+    (2.0 / 9.0, [0x44, 0x0f, 0x76]),
+    (3.0 / 9.0, [0x72, 0x1f, 0x81]),
+    (4.0 / 9.0, [0x9e, 0x2f, 0x7f]),
+    (5.0 / 9.0, [0xcd, 0x40, 0x71]),
+    (6.0 / 9.0, [0xf1, 0x60, 0x5d]),
+    (7.0 / 9.0, [0xfd, 0x96, 0x68]),
+    (8.0 / 9.0, [0xfe, 0xca, 0x8d]),
+    (1.0, [0xfc, 0xfd, 0xbf]),
+];
+
+const BLUERED: [(f32, [u8; 3]); 2] = [(0.0, [0, 0, 255]), (1.0, [255, 0, 0])];
+
+/// Number of entries of the precomputed lookup table of a palette.
+pub const LUT_SIZE: usize = 256;
+
+/// Color space in which the colors between two stops are interpolated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Rgb,
+    Hsv,
+    /// CIELAB, perceptually uniform
+    Lab,
+}
+
+impl FromStr for Interpolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "rgb" => Ok(Interpolation::Rgb),
+            "hsv" => Ok(Interpolation::Hsv),
+            "lab" | "cielab" => Ok(Interpolation::Lab),
+            _ => Err(format!("unknown interpolation '{s}' (choose rgb, hsv or lab)")),
+        }
+    }
+}
+
+/// A color at a position (0.0-1.0) of a palette.
+#[derive(Debug, Clone, Copy)]
+pub struct ColorStop {
+    pub position: f32,
+    pub color: RgbColor,
+}
+
+/// False-color palette: a multi-stop gradient mapping a normalized temperature (0.0-1.0) to a color.
+///
+/// The gradient is evaluated once into a lookup table of [`LUT_SIZE`] colors when the palette is created.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    name: String,
+    stops: Vec<ColorStop>,
+    interpolation: Interpolation,
+    lut: Vec<RgbColor>,
+}
+
+impl Palette {
+    /// Creates a palette from at least one color stop, interpolated in RGB. Stops are sorted by position.
+    pub fn new(name: &str, mut stops: Vec<ColorStop>) -> Self {
+        assert!(!stops.is_empty(), "a palette needs at least one color stop");
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+        let mut palette = Palette {
+            name: name.to_string(),
+            stops,
+            interpolation: Interpolation::Rgb,
+            lut: Vec::new(),
+        };
+        palette.update_lut();
+        palette
+    }
+
+    /// Color space the colors between the stops are interpolated in.
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self.update_lut();
+        self
+    }
+
+    /// Loads a palette from a text file, named after the file stem.
+    ///
+    /// Every non-empty line that does not start with a `#` comment holds one color stop: a position (0.0-1.0) followed
+    /// by a color, either as `#rrggbb` or as three decimal values. A line `interpolation = rgb|hsv|lab`
+    /// selects the interpolation between the stops (default: rgb).
+    ///
+    /// ```text
+    /// # hot metal
+    /// interpolation = lab
+    /// 0.0  #000000
+    /// 0.5  200 40 0
+    /// 1.0  #ffffc0
+    /// ```
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("custom");
+        Palette::parse(name, &content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err)))
+    }
+
+    /// Parses a palette definition in the format described in [`Palette::load`].
+    pub fn parse(name: &str, definition: &str) -> Result<Self, String> {
+        let mut interpolation = Interpolation::Rgb;
+        let mut stops = Vec::new();
+        for (line_idx, line) in definition.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_err = |msg: &str| format!("line {}: {}", line_idx + 1, msg);
+
+            if let Some((key, value)) = line.split_once('=') {
+                if key.trim() != "interpolation" {
+                    return Err(line_err(&format!("unknown setting '{}'", key.trim())));
+                }
+                interpolation = value.trim().parse().map_err(|err: String| line_err(&err))?;
+                continue;
+            }
+            stops.push(parse_color_stop(line).map_err(|err| line_err(&err))?);
+        }
+        if stops.is_empty() {
+            return Err("no color stops defined".to_string());
+        }
+        Ok(Palette::new(name, stops).with_interpolation(interpolation))
+    }
+
+    /// Two-color gradient from `min_temp_color` to `max_temp_color`.
+    pub fn two_color(name: &str, min_temp_color: RgbColor, max_temp_color: RgbColor) -> Self {
+        Palette::new(
+            name,
+            vec![
+                ColorStop {
+                    position: 0.0,
+                    color: min_temp_color,
+                },
+                ColorStop {
+                    position: 1.0,
+                    color: max_temp_color,
+                },
+            ],
+        )
+    }
+
+    /// Looks up a built-in palette by name (case-insensitive, "grey"/"gray" are aliases of "whitehot").
+    pub fn builtin(name: &str) -> Option<Self> {
+        let name = match name.to_lowercase().as_str() {
+            "grey" | "gray" => "whitehot".to_string(),
+            name => name.to_string(),
+        };
+        let stops: &[(f32, [u8; 3])] = match name.as_str() {
+            "ironbow" => &IRONBOW,
+            "rainbow" => &RAINBOW,
+            "whitehot" => &WHITEHOT,
+            "blackhot" => &BLACKHOT,
+            "viridis" => &VIRIDIS,
+            "cividis" => &CIVIDIS,
+            "magma" => &MAGMA,
+            "bluered" => &BLUERED,
+            _ => return None,
+        };
+        Some(Palette::new(&name, stops_from_table(stops)))
+    }
+
+    /// The built-in palette following this one in [`BUILTIN_PALETTE_NAMES`] (wraps around). Custom palettes
+    /// are followed by the first built-in palette.
+    pub fn next_builtin(&self) -> Self {
+        let next_idx = match BUILTIN_PALETTE_NAMES.iter().position(|&name| name == self.name) {
+            Some(idx) => (idx + 1) % BUILTIN_PALETTE_NAMES.len(),
+            None => 0,
+        };
+        Palette::builtin(BUILTIN_PALETTE_NAMES[next_idx]).unwrap()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn stops(&self) -> &[ColorStop] {
+        &self.stops
+    }
+
+    pub fn interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    /// Precomputed colors for equidistant positions from 0.0 to 1.0.
+    pub fn lut(&self) -> &[RgbColor] {
+        &self.lut
+    }
+
+    /// Color at `fraction` (0.0-1.0) looked up in the precomputed table, values outside are clamped.
+    pub fn color_at(&self, fraction: f32) -> RgbColor {
+        if fraction.is_nan() {
+            return self.lut[0];
+        }
+        let idx = (fraction.clamp(0.0, 1.0) * (LUT_SIZE - 1) as f32).round() as usize;
+        self.lut[idx]
+    }
+
+    fn update_lut(&mut self) {
+        self.lut = (0..LUT_SIZE)
+            .map(|idx| self.interpolate(idx as f32 / (LUT_SIZE - 1) as f32))
+            .collect();
+    }
+
+    /// Exact color at `fraction` (0.0-1.0), values outside are clamped.
+    fn interpolate(&self, fraction: f32) -> RgbColor {
+        let first = self.stops[0];
+        let last = self.stops[self.stops.len() - 1];
+        if fraction.is_nan() || fraction <= first.position {
+            return first.color;
+        }
+        if fraction >= last.position {
+            return last.color;
+        }
+        for window in self.stops.windows(2) {
+            let (lower, upper) = (window[0], window[1]);
+            if fraction <= upper.position {
+                let stop_fraction = (fraction - lower.position) / (upper.position - lower.position);
+                return match self.interpolation {
+                    Interpolation::Rgb => RgbColor::lerp(lower.color, upper.color, stop_fraction),
+                    Interpolation::Hsv => RgbColor::lerp_hsv(lower.color, upper.color, stop_fraction),
+                    Interpolation::Lab => RgbColor::lerp_lab(lower.color, upper.color, stop_fraction),
+                };
+            }
+        }
+        last.color
+    }
+
+    /// Samples the palette at `steps` equidistant positions from 0.0 to 1.0.
+    pub fn discrete_blend(&self, steps: u32) -> Vec<RgbColor> {
+        (0..steps)
+            .map(|step| self.color_at(step as f32 / steps.saturating_sub(1).max(1) as f32))
+            .collect()
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::builtin("ironbow").unwrap()
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Palette::builtin(s).ok_or_else(|| {
+            format!(
+                "unknown palette '{s}' (choose one of {})",
+                BUILTIN_PALETTE_NAMES.join(", ")
+            )
+        })
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+fn stops_from_table(table: &[(f32, [u8; 3])]) -> Vec<ColorStop> {
+    table
+        .iter()
+        .map(|&(position, [r, g, b])| ColorStop {
+            position,
+            color: RgbColor { r, g, b },
+        })
+        .collect()
+}
+
+fn parse_color_stop(line: &str) -> Result<ColorStop, String> {
+    let mut fields = line.split_whitespace();
+    let position: f32 = fields
+        .next()
+        .ok_or("missing position")?
+        .parse()
+        .map_err(|_| "position needs to be a number".to_string())?;
+    if !(0.0..=1.0).contains(&position) {
+        return Err(format!("position {position} is outside of 0.0-1.0"));
+    }
+    let color_fields: Vec<&str> = fields.collect();
+    let color = match color_fields.as_slice() {
+        [hex] => parse_hex_color(hex)?,
+        [r, g, b] => {
+            let channel = |value: &str| {
+                value
+                    .parse::<u8>()
+                    .map_err(|_| format!("invalid color channel '{value}'"))
+            };
+            RgbColor {
+                r: channel(r)?,
+                g: channel(g)?,
+                b: channel(b)?,
+            }
+        }
+        _ => return Err("expected a color as #rrggbb or as three values 0-255".to_string()),
+    };
+    Ok(ColorStop { position, color })
+}
+
+pub(crate) fn parse_hex_color(hex: &str) -> Result<RgbColor, String> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    let value = u32::from_str_radix(digits, 16)
+        .ok()
+        .filter(|_| digits.len() == 6)
+        .ok_or(format!("invalid hex color '{hex}'"))?;
+    Ok(RgbColor {
+        r: (value >> 16) as u8,
+        g: (value >> 8) as u8,
+        b: value as u8,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(color: RgbColor) -> [u8; 3] {
+        [color.r, color.g, color.b]
+    }
+
+    #[test]
+    fn parses_the_example_palette_file() {
+        let palette = Palette::parse("hot_metal", include_str!("../data/hot_metal.palette")).unwrap();
+        assert_eq!(palette.name(), "hot_metal");
+        assert_eq!(palette.interpolation(), Interpolation::Lab);
+        let stops: Vec<(f32, [u8; 3])> = palette
+            .stops()
+            .iter()
+            .map(|stop| (stop.position, rgb(stop.color)))
+            .collect();
+        assert_eq!(
+            stops,
+            vec![
+                (0.0, [0, 0, 0]),
+                (0.35, [0x5a, 0x0a, 0x0a]),
+                (0.6, [200, 40, 0]),
+                (0.85, [0xff, 0xb0, 0x00]),
+                (1.0, [0xff, 0xff, 0xc0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_definitions() {
+        for (definition, error) in [
+            ("0.5 #12345", "line 1: invalid hex color '#12345'"),
+            ("0.5 #gg0000", "line 1: invalid hex color '#gg0000'"),
+            (
+                "0.0 #000000\n0.5 255 0",
+                "line 2: expected a color as #rrggbb or as three values 0-255",
+            ),
+            ("0.5 256 0 0", "line 1: invalid color channel '256'"),
+            ("1.5 #000000", "line 1: position 1.5 is outside of 0.0-1.0"),
+            ("-0.1 #000000", "line 1: position -0.1 is outside of 0.0-1.0"),
+            ("half #000000", "line 1: position needs to be a number"),
+            (
+                "interpolation = cubic\n0.0 #000000",
+                "line 1: unknown interpolation 'cubic' (choose rgb, hsv or lab)",
+            ),
+            ("gamma = 2.2", "line 1: unknown setting 'gamma'"),
+            ("# only a comment\ninterpolation = hsv", "no color stops defined"),
+            ("", "no color stops defined"),
+        ] {
+            assert_eq!(Palette::parse("test", definition).unwrap_err(), error, "{definition:?}");
+        }
+    }
+
+    #[test]
+    fn lut_ends_at_the_outer_stops() {
+        for interpolation in [Interpolation::Rgb, Interpolation::Hsv, Interpolation::Lab] {
+            let palette = Palette::parse("test", "0.2 10 20 30\n0.5 #ff8000\n0.9 200 220 240")
+                .unwrap()
+                .with_interpolation(interpolation);
+            assert_eq!(palette.lut().len(), LUT_SIZE);
+            assert_eq!(rgb(palette.lut()[0]), [10, 20, 30], "{interpolation:?}");
+            assert_eq!(rgb(palette.lut()[LUT_SIZE - 1]), [200, 220, 240], "{interpolation:?}");
+            assert_eq!(rgb(palette.color_at(-1.0)), [10, 20, 30]);
+            assert_eq!(rgb(palette.color_at(f32::NAN)), [10, 20, 30]);
+            assert_eq!(rgb(palette.color_at(2.0)), [200, 220, 240]);
+        }
+    }
+
+    #[test]
+    fn parses_every_builtin_name() {
+        for name in BUILTIN_PALETTE_NAMES {
+            let palette: Palette = name.parse().unwrap();
+            assert_eq!(palette.name(), name);
+            assert_eq!(palette.to_string(), name);
+            assert_eq!(name.to_uppercase().parse::<Palette>().unwrap().name(), name);
+        }
+        for alias in ["grey", "gray", "Gray"] {
+            assert_eq!(alias.parse::<Palette>().unwrap().name(), "whitehot");
+        }
+    }
+
+    #[test]
+    fn unknown_palette_names() {
+        assert_eq!(
+            "sepia".parse::<Palette>().unwrap_err(),
+            "unknown palette 'sepia' (choose one of ironbow, rainbow, whitehot, blackhot, viridis, cividis, magma, \
+             bluered)"
+        );
+        assert!(Palette::builtin("").is_none());
+    }
+
+    #[test]
+    fn cycles_through_the_builtin_palettes() {
+        let mut palette = Palette::default();
+        for name in BUILTIN_PALETTE_NAMES
+            .iter()
+            .skip(1)
+            .chain(BUILTIN_PALETTE_NAMES.iter().take(1))
+        {
+            palette = palette.next_builtin();
+            assert_eq!(palette.name(), *name);
+        }
+        let custom = Palette::parse("custom", "0.0 #000000\n1.0 #ffffff").unwrap();
+        assert_eq!(custom.next_builtin().name(), BUILTIN_PALETTE_NAMES[0]);
+        assert_eq!(Palette::builtin("gray").unwrap().next_builtin().name(), "blackhot");
+    }
+
+    #[test]
+    fn endpoint_colors_of_builtin_palettes() {
+        for (name, cold, hot) in [
+            ("ironbow", [0, 0, 10], [255, 255, 230]),
+            ("rainbow", [0, 0, 130], [255, 0, 0]),
+            ("whitehot", [0, 0, 0], [255, 255, 255]),
+            ("blackhot", [255, 255, 255], [0, 0, 0]),
+        ] {
+            let palette = Palette::builtin(name).unwrap();
+            assert_eq!(rgb(palette.color_at(0.0)), cold, "{name}");
+            assert_eq!(rgb(palette.color_at(1.0)), hot, "{name}");
+        }
+    }
+}
@@ -0,0 +1,522 @@
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Transform between the visible and the thermal image.
+///
+/// The transform maps normalized coordinates of the displayed visible image (0.0-1.0, i.e. after flipping) to
+/// normalized coordinates of the thermal image, so it does not depend on the camera resolution or the
+/// interpolation factor. The identity stretches the thermal image over the visible image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Registration {
+    /// Row-major 3x3 homography, affine transforms have a last row of (0, 0, 1)
+    matrix: [[f32; 3]; 3],
+}
+
+impl Registration {
+    pub fn identity() -> Self {
+        Registration::from_matrix([[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]])
+    }
+
+    pub fn from_matrix(matrix: [[f32; 3]; 3]) -> Self {
+        Registration { matrix }
+    }
+
+    /// Affine transform: scaling around the image center, rotation (degrees) and offset, all in normalized
+    /// coordinates of the thermal image.
+    pub fn affine(scale_x: f32, scale_y: f32, rotation: f32, offset_x: f32, offset_y: f32) -> Self {
+        let (sin, cos) = rotation.to_radians().sin_cos();
+        let a = cos * scale_x;
+        let b = -sin * scale_y;
+        let c = sin * scale_x;
+        let d = cos * scale_y;
+        // keep the center at the center
+        let tx = 0.5 - a * 0.5 - b * 0.5 + offset_x;
+        let ty = 0.5 - c * 0.5 - d * 0.5 + offset_y;
+        Registration::from_matrix([[a, b, tx], [c, d, ty], [0.0, 0.0, 1.0]])
+    }
+
+    pub fn matrix(&self) -> [[f32; 3]; 3] {
+        self.matrix
+    }
+
+    pub fn is_affine(&self) -> bool {
+        self.matrix[2] == [0.0, 0.0, 1.0]
+    }
+
+    /// Maps normalized visible coordinates to normalized thermal coordinates, `None` for points at infinity.
+    pub fn map(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        let m = &self.matrix;
+        let w = m[2][0] * x + m[2][1] * y + m[2][2];
+        if w.abs() < f32::EPSILON {
+            return None;
+        }
+        Some((
+            (m[0][0] * x + m[0][1] * y + m[0][2]) / w,
+            (m[1][0] * x + m[1][1] * y + m[1][2]) / w,
+        ))
+    }
+
+    /// Applies `self` first and then `next`.
+    pub fn then(&self, next: &Registration) -> Registration {
+        let mut matrix = [[0f32; 3]; 3];
+        for (row_idx, row) in matrix.iter_mut().enumerate() {
+            for (col_idx, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| next.matrix[row_idx][k] * self.matrix[k][col_idx]).sum();
+            }
+        }
+        Registration::from_matrix(matrix)
+    }
+
+    /// The transform from normalized thermal to normalized visible coordinates, `None` if not invertible.
+    pub fn inverse(&self) -> Option<Registration> {
+        let m = &self.matrix;
+        let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+        let adjugate = [
+            [cofactor(1, 2, 1, 2), -cofactor(0, 2, 1, 2), cofactor(0, 1, 1, 2)],
+            [-cofactor(1, 2, 0, 2), cofactor(0, 2, 0, 2), -cofactor(0, 1, 0, 2)],
+            [cofactor(1, 2, 0, 1), -cofactor(0, 2, 0, 1), cofactor(0, 1, 0, 1)],
+        ];
+        let determinant = m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+        let mut inverse = [[0f32; 3]; 3];
+        for (row, adjugate_row) in inverse.iter_mut().zip(adjugate.iter()) {
+            for (value, adjugate_value) in row.iter_mut().zip(adjugate_row.iter()) {
+                *value = adjugate_value / determinant;
+            }
+        }
+        Some(Registration::from_matrix(inverse))
+    }
+
+    /// Loads a registration saved with [`Registration::save`].
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        content
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl Default for Registration {
+    fn default() -> Self {
+        Registration::identity()
+    }
+}
+
+/// Three rows of three numbers, lines starting with `#` are comments.
+impl FromStr for Registration {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<&str> = s
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect();
+        if rows.len() != 3 {
+            return Err(format!("expected 3 rows of the matrix, got {}", rows.len()));
+        }
+        let mut matrix = [[0f32; 3]; 3];
+        for (row_idx, (row, line)) in matrix.iter_mut().zip(rows).enumerate() {
+            let values = parse_numbers(line).map_err(|err| format!("row {}: {}", row_idx + 1, err))?;
+            if values.len() != 3 {
+                return Err(format!("row {}: expected 3 values, got {}", row_idx + 1, values.len()));
+            }
+            row.copy_from_slice(&values);
+        }
+        Ok(Registration::from_matrix(matrix))
+    }
+}
+
+impl fmt::Display for Registration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "# registration: normalized visible coordinates -> normalized thermal coordinates"
+        )?;
+        for row in self.matrix.iter() {
+            writeln!(f, "{} {} {}", row[0], row[1], row[2])?;
+        }
+        Ok(())
+    }
+}
+
+/// Transform model estimated from point pairs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationModel {
+    /// Scale, rotation, shear and offset, needs at least 3 point pairs
+    Affine,
+    /// Additionally perspective, needs at least 4 point pairs
+    Homography,
+}
+
+impl FromStr for RegistrationModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "affine" => Ok(RegistrationModel::Affine),
+            "homography" => Ok(RegistrationModel::Homography),
+            _ => Err(format!(
+                "unknown registration model '{s}' (choose affine or homography)"
+            )),
+        }
+    }
+}
+
+/// A point of the visible image and the matching point of the thermal image, both in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointPair {
+    pub visible: (f32, f32),
+    pub thermal: (f32, f32),
+}
+
+/// Corresponding points picked in both images (e.g. corners of a heated checkerboard) for calibrating the
+/// registration.
+///
+/// File format: `visible_size = <width> <height>` (pixels of the displayed visible image), `thermal_size =
+/// <width> <height>` (pixels of the thermal image the points were picked in), an optional `model = affine|homography`
+/// and one `<visible x> <visible y> <thermal x> <thermal y>` pair per line. Lines starting with `#` are comments.
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub visible_size: (u32, u32),
+    pub thermal_size: (u32, u32),
+    pub model: RegistrationModel,
+    pub pairs: Vec<PointPair>,
+}
+
+impl Calibration {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        Calibration::parse(&content)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn parse(definition: &str) -> Result<Self, String> {
+        let mut visible_size = None;
+        let mut thermal_size = None;
+        let mut model = None;
+        let mut pairs = Vec::new();
+        for (line_idx, line) in definition.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_err = |msg: &str| format!("line {}: {}", line_idx + 1, msg);
+
+            if let Some((key, value)) = line.split_once('=') {
+                match key.trim() {
+                    "visible_size" => visible_size = Some(parse_size(value).map_err(|err| line_err(&err))?),
+                    "thermal_size" => thermal_size = Some(parse_size(value).map_err(|err| line_err(&err))?),
+                    "model" => model = Some(value.trim().parse().map_err(|err: String| line_err(&err))?),
+                    key => return Err(line_err(&format!("unknown setting '{key}'"))),
+                }
+                continue;
+            }
+            let values = parse_numbers(line).map_err(|err| line_err(&err))?;
+            if values.len() != 4 {
+                return Err(line_err("expected <visible x> <visible y> <thermal x> <thermal y>"));
+            }
+            pairs.push(PointPair {
+                visible: (values[0], values[1]),
+                thermal: (values[2], values[3]),
+            });
+        }
+        let model = model.unwrap_or(if pairs.len() >= 4 {
+            RegistrationModel::Homography
+        } else {
+            RegistrationModel::Affine
+        });
+        Ok(Calibration {
+            visible_size: visible_size.ok_or("visible_size is missing")?,
+            thermal_size: thermal_size.ok_or("thermal_size is missing")?,
+            model,
+            pairs,
+        })
+    }
+
+    /// Least-squares estimate of the registration.
+    pub fn estimate(&self) -> Result<Registration, String> {
+        let required = match self.model {
+            RegistrationModel::Affine => 3,
+            RegistrationModel::Homography => 4,
+        };
+        if self.pairs.len() < required {
+            return Err(format!(
+                "{:?} registration needs at least {required} point pairs, got {}",
+                self.model,
+                self.pairs.len()
+            ));
+        }
+        let normalized = self.normalized_pairs();
+        let matrix = match self.model {
+            RegistrationModel::Affine => estimate_affine(&normalized),
+            RegistrationModel::Homography => estimate_homography(&normalized),
+        };
+        matrix
+            .filter(|matrix| matrix.iter().flatten().all(|value| value.is_finite()))
+            .map(Registration::from_matrix)
+            .ok_or_else(|| "point pairs are degenerate (e.g. all on one line)".to_string())
+    }
+
+    /// Root mean square distance (thermal pixels) between the picked thermal points and the mapped visible points.
+    pub fn rms_error(&self, registration: &Registration) -> f32 {
+        let (thermal_width, thermal_height) = (self.thermal_size.0 as f32, self.thermal_size.1 as f32);
+        let squared_sum: f32 = self
+            .normalized_pairs()
+            .iter()
+            .map(|&((x, y), (u, v))| match registration.map(x, y) {
+                Some((mapped_u, mapped_v)) => {
+                    let du = (mapped_u - u) * thermal_width;
+                    let dv = (mapped_v - v) * thermal_height;
+                    du * du + dv * dv
+                }
+                None => f32::INFINITY,
+            })
+            .sum();
+        (squared_sum / self.pairs.len() as f32).sqrt()
+    }
+
+    /// Pairs of (visible, thermal) points in normalized coordinates (pixel centers).
+    fn normalized_pairs(&self) -> Vec<NormalizedPair> {
+        let normalize =
+            |(x, y): (f32, f32), (width, height): (u32, u32)| ((x + 0.5) / width as f32, (y + 0.5) / height as f32);
+        self.pairs
+            .iter()
+            .map(|pair| {
+                (
+                    normalize(pair.visible, self.visible_size),
+                    normalize(pair.thermal, self.thermal_size),
+                )
+            })
+            .collect()
+    }
+}
+
+/// (visible, thermal) point in normalized coordinates
+type NormalizedPair = ((f32, f32), (f32, f32));
+
+fn estimate_affine(pairs: &[NormalizedPair]) -> Option<[[f32; 3]; 3]> {
+    // u = a x + b y + c and v = d x + e y + f share the same normal equations
+    let mut normal = vec![vec![0f64; 3]; 3];
+    let mut rhs_u = vec![0f64; 3];
+    let mut rhs_v = vec![0f64; 3];
+    for &((x, y), (u, v)) in pairs {
+        let row = [x as f64, y as f64, 1.0];
+        for i in 0..3 {
+            for j in 0..3 {
+                normal[i][j] += row[i] * row[j];
+            }
+            rhs_u[i] += row[i] * u as f64;
+            rhs_v[i] += row[i] * v as f64;
+        }
+    }
+    let u = solve_linear_system(normal.clone(), rhs_u)?;
+    let v = solve_linear_system(normal, rhs_v)?;
+    Some([
+        [u[0] as f32, u[1] as f32, u[2] as f32],
+        [v[0] as f32, v[1] as f32, v[2] as f32],
+        [0.0, 0.0, 1.0],
+    ])
+}
+
+fn estimate_homography(pairs: &[NormalizedPair]) -> Option<[[f32; 3]; 3]> {
+    // direct linear transform with h33 = 1, solved via the normal equations
+    let mut normal = vec![vec![0f64; 8]; 8];
+    let mut rhs = vec![0f64; 8];
+    for &((x, y), (u, v)) in pairs {
+        let (x, y, u, v) = (x as f64, y as f64, u as f64, v as f64);
+        let rows = [
+            ([x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y], u),
+            ([0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y], v),
+        ];
+        for (row, target) in rows.iter() {
+            for i in 0..8 {
+                for j in 0..8 {
+                    normal[i][j] += row[i] * row[j];
+                }
+                rhs[i] += row[i] * target;
+            }
+        }
+    }
+    let h = solve_linear_system(normal, rhs)?;
+    Some([
+        [h[0] as f32, h[1] as f32, h[2] as f32],
+        [h[3] as f32, h[4] as f32, h[5] as f32],
+        [h[6] as f32, h[7] as f32, 1.0],
+    ])
+}
+
+/// Gaussian elimination with partial pivoting, `None` for (numerically) singular systems.
+fn solve_linear_system(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    // rounding leaves tiny pivots instead of zeros, compare them to the magnitude of the matrix
+    let tolerance = 1e-12 * a.iter().flatten().fold(1.0f64, |max, value| max.max(value.abs()));
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < tolerance {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col].clone();
+        for row in col + 1..n {
+            let factor = a[row][col] / pivot_row[col];
+            for (value, pivot_value) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot_value;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    let mut x = vec![0f64; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+fn parse_numbers(line: &str) -> Result<Vec<f32>, String> {
+    line.split_whitespace()
+        .map(|value| value.parse::<f32>().map_err(|_| format!("invalid number '{value}'")))
+        .collect()
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let values: Vec<&str> = value.split_whitespace().collect();
+    match values[..] {
+        [width, height] => match (width.parse(), height.parse()) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+            _ => Err(format!("invalid size '{}'", value.trim())),
+        },
+        _ => Err("expected <width> <height>".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VISIBLE_SIZE: (u32, u32) = (640, 480);
+    const THERMAL_SIZE: (u32, u32) = (32, 24);
+
+    /// Calibration with the thermal points of `visible_points` mapped by `registration`.
+    fn synthetic_calibration(
+        registration: &Registration,
+        model: RegistrationModel,
+        visible_points: &[(f32, f32)],
+    ) -> Calibration {
+        let pairs = visible_points
+            .iter()
+            .map(|&(x, y)| {
+                let normalized_x = (x + 0.5) / VISIBLE_SIZE.0 as f32;
+                let normalized_y = (y + 0.5) / VISIBLE_SIZE.1 as f32;
+                let (u, v) = registration.map(normalized_x, normalized_y).unwrap();
+                PointPair {
+                    visible: (x, y),
+                    thermal: (u * THERMAL_SIZE.0 as f32 - 0.5, v * THERMAL_SIZE.1 as f32 - 0.5),
+                }
+            })
+            .collect();
+        Calibration {
+            visible_size: VISIBLE_SIZE,
+            thermal_size: THERMAL_SIZE,
+            model,
+            pairs,
+        }
+    }
+
+    fn assert_matrix_close(actual: &Registration, expected: &Registration, tolerance: f32) {
+        for (actual_row, expected_row) in actual.matrix().iter().zip(expected.matrix().iter()) {
+            for (actual_value, expected_value) in actual_row.iter().zip(expected_row.iter()) {
+                assert!(
+                    (actual_value - expected_value).abs() < tolerance,
+                    "{:?} != {:?}",
+                    actual.matrix(),
+                    expected.matrix()
+                );
+            }
+        }
+    }
+
+    const GRID: [(f32, f32); 6] = [
+        (50.0, 40.0),
+        (590.0, 60.0),
+        (320.0, 240.0),
+        (80.0, 430.0),
+        (600.0, 420.0),
+        (200.0, 300.0),
+    ];
+
+    #[test]
+    fn recovers_affine_transform() {
+        let expected = Registration::affine(0.9, 1.1, 3.0, 0.02, -0.05);
+        let calibration = synthetic_calibration(&expected, RegistrationModel::Affine, &GRID);
+        let estimated = calibration.estimate().unwrap();
+        assert!(estimated.is_affine());
+        assert_matrix_close(&estimated, &expected, 1e-4);
+        assert!(calibration.rms_error(&estimated) < 1e-3);
+
+        // exactly determined by 3 pairs
+        let calibration = synthetic_calibration(&expected, RegistrationModel::Affine, &GRID[..3]);
+        assert_matrix_close(&calibration.estimate().unwrap(), &expected, 1e-4);
+    }
+
+    #[test]
+    fn recovers_homography() {
+        let expected = Registration::from_matrix([[0.95, 0.03, 0.02], [-0.02, 1.05, -0.01], [0.08, -0.05, 1.0]]);
+        let calibration = synthetic_calibration(&expected, RegistrationModel::Homography, &GRID);
+        let estimated = calibration.estimate().unwrap();
+        assert_matrix_close(&estimated, &expected, 1e-3);
+        assert!(calibration.rms_error(&estimated) < 1e-2);
+    }
+
+    #[test]
+    fn collinear_points_are_degenerate() {
+        let line = [
+            (10.0, 10.0),
+            (100.0, 80.0),
+            (190.0, 150.0),
+            (370.0, 290.0),
+            (550.0, 430.0),
+        ];
+        let registration = Registration::affine(1.0, 1.0, 0.0, 0.01, 0.02);
+        for model in [RegistrationModel::Affine, RegistrationModel::Homography] {
+            let calibration = synthetic_calibration(&registration, model, &line);
+            assert!(calibration.estimate().is_err(), "{model:?} of collinear points");
+        }
+    }
+
+    #[test]
+    fn too_few_pairs() {
+        let registration = Registration::identity();
+        let calibration = synthetic_calibration(&registration, RegistrationModel::Homography, &GRID[..3]);
+        assert!(calibration.estimate().is_err());
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let registration = Registration::from_matrix([[0.95, 0.03, 0.02], [-0.02, 1.05, -0.01], [0.08, -0.05, 1.0]]);
+        let path = std::env::temp_dir().join(format!("thermocam_registration_{}.txt", std::process::id()));
+        registration.save(&path).unwrap();
+        let loaded = Registration::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), registration);
+    }
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let registration = Registration::affine(0.8, 1.2, -5.0, 0.1, 0.0);
+        let round_trip = registration.then(&registration.inverse().unwrap());
+        assert_matrix_close(&round_trip, &Registration::identity(), 1e-5);
+    }
+}
@@ -38,4 +38,235 @@ impl RgbColor {
     pub fn to_vec(self) -> Vec<u8> {
         vec![self.r, self.g, self.b]
     }
+
+    /// Interpolates in HSV space along the shorter hue arc.
+    pub fn lerp_hsv(color1: Self, color2: Self, fraction: f32) -> Self {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let (h1, s1, v1) = color1.to_hsv();
+        let (h2, s2, v2) = color2.to_hsv();
+        let mut hue_diff = h2 - h1;
+        if hue_diff > 180.0 {
+            hue_diff -= 360.0;
+        } else if hue_diff < -180.0 {
+            hue_diff += 360.0;
+        }
+        let h = (h1 + hue_diff * fraction).rem_euclid(360.0);
+        Self::from_hsv(h, s1 + (s2 - s1) * fraction, v1 + (v2 - v1) * fraction)
+    }
+
+    /// Interpolates in CIELAB space (perceptually uniform).
+    pub fn lerp_lab(color1: Self, color2: Self, fraction: f32) -> Self {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let (l1, a1, b1) = color1.to_lab();
+        let (l2, a2, b2) = color2.to_lab();
+        Self::from_lab(
+            l1 + (l2 - l1) * fraction,
+            a1 + (a2 - a1) * fraction,
+            b1 + (b2 - b1) * fraction,
+        )
+    }
+
+    /// Returns (hue in degrees 0-360, saturation 0-1, value 0-1).
+    pub fn to_hsv(self) -> (f32, f32, f32) {
+        let r = self.r as f32 / 255.0;
+        let g = self.g as f32 / 255.0;
+        let b = self.b as f32 / 255.0;
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        let h = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        let s = if max == 0.0 { 0.0 } else { delta / max };
+        (h, s, max)
+    }
+
+    pub fn from_hsv(h: f32, s: f32, v: f32) -> Self {
+        let c = v * s;
+        let h_prime = h.rem_euclid(360.0) / 60.0;
+        let x = c * (1.0 - (h_prime % 2.0 - 1.0).abs());
+        let (r, g, b) = match h_prime as u32 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let m = v - c;
+        RgbColor {
+            r: to_u8((r + m) * 255.0),
+            g: to_u8((g + m) * 255.0),
+            b: to_u8((b + m) * 255.0),
+        }
+    }
+
+    /// Returns CIELAB (L*, a*, b*) for sRGB with D65 white point.
+    pub fn to_lab(self) -> (f32, f32, f32) {
+        let r = srgb_to_linear(self.r);
+        let g = srgb_to_linear(self.g);
+        let b = srgb_to_linear(self.b);
+
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / D65_WHITE.0;
+        let y = (0.2126 * r + 0.7152 * g + 0.0722 * b) / D65_WHITE.1;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / D65_WHITE.2;
+
+        let fx = lab_f(x);
+        let fy = lab_f(y);
+        let fz = lab_f(z);
+        (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+    }
+
+    pub fn from_lab(l: f32, a: f32, b: f32) -> Self {
+        let fy = (l + 16.0) / 116.0;
+        let fx = fy + a / 500.0;
+        let fz = fy - b / 200.0;
+
+        let x = lab_f_inv(fx) * D65_WHITE.0;
+        let y = lab_f_inv(fy) * D65_WHITE.1;
+        let z = lab_f_inv(fz) * D65_WHITE.2;
+
+        RgbColor {
+            r: linear_to_srgb(3.2406 * x - 1.5372 * y - 0.4986 * z),
+            g: linear_to_srgb(-0.9689 * x + 1.8758 * y + 0.0415 * z),
+            b: linear_to_srgb(0.0557 * x - 0.2040 * y + 1.0570 * z),
+        }
+    }
+}
+
+const D65_WHITE: (f32, f32, f32) = (0.95047, 1.0, 1.08883);
+
+fn to_u8(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let c = value.clamp(0.0, 1.0);
+    let c = if c <= 0.0031308 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    to_u8(c * 255.0)
+}
+
+fn lab_f(t: f32) -> f32 {
+    const DELTA: f32 = 6.0 / 29.0;
+    if t > DELTA * DELTA * DELTA {
+        t.cbrt()
+    } else {
+        t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+    }
+}
+
+fn lab_f_inv(t: f32) -> f32 {
+    const DELTA: f32 = 6.0 / 29.0;
+    if t > DELTA {
+        t * t * t
+    } else {
+        3.0 * DELTA * DELTA * (t - 4.0 / 29.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(r: u8, g: u8, b: u8) -> RgbColor {
+        RgbColor { r, g, b }
+    }
+
+    fn assert_close(actual: (f32, f32, f32), expected: (f32, f32, f32), tolerance: f32) {
+        assert!(
+            (actual.0 - expected.0).abs() < tolerance
+                && (actual.1 - expected.1).abs() < tolerance
+                && (actual.2 - expected.2).abs() < tolerance,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    const REFERENCE_COLORS: [[u8; 3]; 8] = [
+        [0, 0, 0],
+        [255, 255, 255],
+        [128, 128, 128],
+        [255, 0, 0],
+        [0, 255, 0],
+        [0, 0, 255],
+        [255, 128, 0],
+        [30, 144, 200],
+    ];
+
+    #[test]
+    fn hsv_of_reference_colors() {
+        assert_close(color(255, 0, 0).to_hsv(), (0.0, 1.0, 1.0), 1e-4);
+        assert_close(color(0, 255, 0).to_hsv(), (120.0, 1.0, 1.0), 1e-4);
+        assert_close(color(0, 0, 255).to_hsv(), (240.0, 1.0, 1.0), 1e-4);
+        assert_close(color(255, 0, 255).to_hsv(), (300.0, 1.0, 1.0), 1e-4);
+        assert_close(color(128, 128, 128).to_hsv(), (0.0, 0.0, 128.0 / 255.0), 1e-4);
+        assert_close(color(255, 128, 0).to_hsv(), (30.117647, 1.0, 1.0), 1e-3);
+    }
+
+    #[test]
+    fn hsv_round_trip() {
+        for [r, g, b] in REFERENCE_COLORS {
+            let (h, s, v) = color(r, g, b).to_hsv();
+            let round_trip = RgbColor::from_hsv(h, s, v);
+            assert_eq!([round_trip.r, round_trip.g, round_trip.b], [r, g, b]);
+        }
+    }
+
+    #[test]
+    fn lab_of_reference_colors() {
+        // sRGB, D65
+        assert_close(color(0, 0, 0).to_lab(), (0.0, 0.0, 0.0), 1e-3);
+        assert_close(color(255, 255, 255).to_lab(), (100.0, 0.0, 0.0), 0.05);
+        assert_close(color(255, 0, 0).to_lab(), (53.24, 80.09, 67.20), 0.1);
+        assert_close(color(0, 255, 0).to_lab(), (87.73, -86.18, 83.18), 0.1);
+        assert_close(color(0, 0, 255).to_lab(), (32.30, 79.19, -107.86), 0.1);
+    }
+
+    #[test]
+    fn lab_round_trip() {
+        for [r, g, b] in REFERENCE_COLORS {
+            let (l, a, b_star) = color(r, g, b).to_lab();
+            let round_trip = RgbColor::from_lab(l, a, b_star);
+            assert_eq!([round_trip.r, round_trip.g, round_trip.b], [r, g, b]);
+        }
+    }
+
+    #[test]
+    fn hsv_interpolation_takes_the_shorter_hue_arc() {
+        // from magenta (300°) to orange (30°) across red
+        let middle = RgbColor::lerp_hsv(color(255, 0, 255), color(255, 128, 0), 0.5);
+        let (h, s, v) = middle.to_hsv();
+        assert!(!(30.0..300.0).contains(&h), "hue {h}");
+        assert_close((s, v, 0.0), (1.0, 1.0, 0.0), 1e-4);
+    }
+
+    #[test]
+    fn interpolations_end_at_the_input_colors() {
+        let (start, end) = (color(30, 144, 200), color(255, 128, 0));
+        for lerp in [RgbColor::lerp, RgbColor::lerp_hsv, RgbColor::lerp_lab] {
+            let first = lerp(start, end, 0.0);
+            let last = lerp(start, end, 1.0);
+            assert_eq!([first.r, first.g, first.b], [30, 144, 200]);
+            assert_eq!([last.r, last.g, last.b], [255, 128, 0]);
+        }
+    }
 }
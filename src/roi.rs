@@ -0,0 +1,386 @@
+use std::fmt;
+use std::str::FromStr;
+
+use image;
+
+use crate::contour::draw_thermal_line_into_image;
+use crate::coordinate_mapper::CoordinateMapper;
+use crate::emissivity::EmissivityCompensation;
+use crate::rgb_color::RgbColor;
+use crate::temperature_pixel::TemperaturPixel;
+use crate::thermal_frame::{FrameStats, ThermalFrame};
+
+/// Number of points of the outline of an ellipse.
+const ELLIPSE_OUTLINE_POINTS: usize = 32;
+
+/// Shape of a region of interest in thermal-sensor coordinates: pixel (x, y) covers x..x+1 and y..y+1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RoiShape {
+    Rectangle {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    Ellipse {
+        center_x: f32,
+        center_y: f32,
+        radius_x: f32,
+        radius_y: f32,
+    },
+    /// Closed polygon through the points, contains nothing with fewer than 3 points
+    Polygon(Vec<(f32, f32)>),
+}
+
+impl RoiShape {
+    /// Whether the point (sensor coordinates) is inside the shape.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        match self {
+            RoiShape::Rectangle {
+                x: left,
+                y: top,
+                width,
+                height,
+            } => x >= *left && x < left + width && y >= *top && y < top + height,
+            RoiShape::Ellipse {
+                center_x,
+                center_y,
+                radius_x,
+                radius_y,
+            } => {
+                let dx = (x - center_x) / radius_x;
+                let dy = (y - center_y) / radius_y;
+                dx * dx + dy * dy <= 1.0
+            }
+            RoiShape::Polygon(points) if points.len() < 3 => false,
+            RoiShape::Polygon(points) => {
+                // even-odd rule
+                let mut inside = false;
+                for (i, &(x1, y1)) in points.iter().enumerate() {
+                    let (x2, y2) = points[(i + 1) % points.len()];
+                    if (y1 > y) != (y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+        }
+    }
+
+    /// Closed outline (sensor coordinates), the last point connects to the first one.
+    pub fn outline(&self) -> Vec<(f32, f32)> {
+        match self {
+            RoiShape::Rectangle { x, y, width, height } => {
+                vec![(*x, *y), (x + width, *y), (x + width, y + height), (*x, y + height)]
+            }
+            RoiShape::Ellipse {
+                center_x,
+                center_y,
+                radius_x,
+                radius_y,
+            } => (0..ELLIPSE_OUTLINE_POINTS)
+                .map(|i| {
+                    let angle = i as f32 / ELLIPSE_OUTLINE_POINTS as f32 * std::f32::consts::TAU;
+                    (center_x + radius_x * angle.cos(), center_y + radius_y * angle.sin())
+                })
+                .collect(),
+            RoiShape::Polygon(points) => points.clone(),
+        }
+    }
+}
+
+/// Named region of interest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Roi {
+    pub name: String,
+    pub shape: RoiShape,
+    /// Emissivity of the material in the region, `None` for the global one
+    pub emissivity: Option<EmissivityCompensation>,
+}
+
+impl Roi {
+    pub fn new(name: &str, shape: RoiShape) -> Self {
+        Roi {
+            name: name.to_string(),
+            shape,
+            emissivity: None,
+        }
+    }
+
+    pub fn with_emissivity(mut self, emissivity: Option<EmissivityCompensation>) -> Self {
+        self.emissivity = emissivity;
+        self
+    }
+
+    /// Pixels of the frame whose centers are inside the region.
+    pub fn pixels<'a>(&'a self, frame: &'a ThermalFrame) -> impl Iterator<Item = TemperaturPixel> + 'a {
+        (0..frame.height).flat_map(move |y| {
+            (0..frame.width).filter_map(move |x| {
+                if self.shape.contains(x as f32 + 0.5, y as f32 + 0.5) {
+                    Some(TemperaturPixel {
+                        x,
+                        y,
+                        value: frame.data[(y * frame.width + x) as usize],
+                    })
+                } else {
+                    None
+                }
+            })
+        })
+    }
+
+    /// Statistics of the region, `None` if it does not contain any pixel of the frame.
+    pub fn stats(&self, frame: &ThermalFrame) -> Option<RoiStats> {
+        self.pixels(frame).next()?;
+        Some(RoiStats {
+            name: self.name.clone(),
+            stats: FrameStats::from_pixels(self.pixels(frame)),
+        })
+    }
+}
+
+/// `<name>:rect:<x>,<y>,<width>,<height>`, `<name>:ellipse:<center x>,<center y>,<radius x>,<radius y>` or
+/// `<name>:polygon:<x1>,<y1>,<x2>,<y2>,<x3>,<y3>,...` in sensor coordinates.
+impl FromStr for Roi {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(':').collect();
+        let [name, kind, values] = fields[..] else {
+            return Err(format!(
+                "invalid region '{s}' (expected <name>:<rect|ellipse|polygon>:<values>)"
+            ));
+        };
+        let values: Vec<f32> = values
+            .split(',')
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<f32>()
+                    .map_err(|_| format!("invalid coordinate '{value}' in region '{name}'"))
+            })
+            .collect::<Result<_, _>>()?;
+        let shape = match (kind, &values[..]) {
+            ("rect", &[x, y, width, height]) if width > 0.0 && height > 0.0 => {
+                RoiShape::Rectangle { x, y, width, height }
+            }
+            ("ellipse", &[center_x, center_y, radius_x, radius_y]) if radius_x > 0.0 && radius_y > 0.0 => {
+                RoiShape::Ellipse {
+                    center_x,
+                    center_y,
+                    radius_x,
+                    radius_y,
+                }
+            }
+            ("polygon", points) if points.len() >= 6 && points.len() % 2 == 0 => {
+                RoiShape::Polygon(points.chunks_exact(2).map(|point| (point[0], point[1])).collect())
+            }
+            ("rect" | "ellipse", _) => return Err(format!("region '{name}' needs 4 values with a positive size")),
+            ("polygon", _) => return Err(format!("region '{name}' needs at least 3 points")),
+            _ => {
+                return Err(format!(
+                    "unknown region type '{kind}' (choose rect, ellipse or polygon)"
+                ))
+            }
+        };
+        Ok(Roi::new(name, shape))
+    }
+}
+
+impl fmt::Display for Roi {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, values) = match &self.shape {
+            RoiShape::Rectangle { x, y, width, height } => ("rect", vec![*x, *y, *width, *height]),
+            RoiShape::Ellipse {
+                center_x,
+                center_y,
+                radius_x,
+                radius_y,
+            } => ("ellipse", vec![*center_x, *center_y, *radius_x, *radius_y]),
+            RoiShape::Polygon(points) => ("polygon", points.iter().flat_map(|&(x, y)| [x, y]).collect()),
+        };
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        write!(f, "{}:{}:{}", self.name, kind, values.join(","))
+    }
+}
+
+/// Statistics of a region of interest, `stats.max` is the hotspot.
+#[derive(Debug, Clone)]
+pub struct RoiStats {
+    pub name: String,
+    pub stats: FrameStats,
+}
+
+/// Draws the outlines of the regions into the displayed image of the mapper.
+pub fn draw_roi_outlines_into_image(
+    rois: &[Roi],
+    mapper: &CoordinateMapper,
+    color: RgbColor,
+    image: &mut image::RgbImage,
+) {
+    for roi in rois {
+        let outline = roi.shape.outline();
+        for (i, &start) in outline.iter().enumerate() {
+            let end = outline[(i + 1) % outline.len()];
+            draw_thermal_line_into_image(start, end, mapper, color, image);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emissivity::compensate_frame;
+
+    /// Frame whose temperature is the sum of the pixel coordinates.
+    fn gradient_frame(width: u32, height: u32) -> ThermalFrame {
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x + y) as f32))
+            .collect();
+        ThermalFrame::new(width, height, data)
+    }
+
+    #[test]
+    fn rectangle_includes_its_top_left_edges_only() {
+        let rect = RoiShape::Rectangle {
+            x: 2.0,
+            y: 3.0,
+            width: 4.0,
+            height: 5.0,
+        };
+        assert!(rect.contains(2.0, 3.0));
+        assert!(rect.contains(5.99, 7.99));
+        assert!(!rect.contains(6.0, 5.0));
+        assert!(!rect.contains(4.0, 8.0));
+        assert!(!rect.contains(1.99, 5.0));
+    }
+
+    #[test]
+    fn ellipse_includes_its_boundary() {
+        let ellipse = RoiShape::Ellipse {
+            center_x: 10.0,
+            center_y: 5.0,
+            radius_x: 4.0,
+            radius_y: 2.0,
+        };
+        assert!(ellipse.contains(14.0, 5.0));
+        assert!(ellipse.contains(10.0, 3.0));
+        assert!(!ellipse.contains(14.01, 5.0));
+        assert!(!ellipse.contains(13.0, 6.5));
+    }
+
+    #[test]
+    fn concave_polygon() {
+        // U shape, open at the top
+        let polygon = RoiShape::Polygon(vec![
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 2.0),
+            (2.0, 2.0),
+            (2.0, 0.0),
+            (3.0, 0.0),
+            (3.0, 3.0),
+            (0.0, 3.0),
+        ]);
+        assert!(polygon.contains(0.5, 1.0));
+        assert!(polygon.contains(2.5, 1.0));
+        assert!(polygon.contains(1.5, 2.5));
+        assert!(!polygon.contains(1.5, 1.0));
+        assert!(!polygon.contains(3.5, 1.0));
+    }
+
+    #[test]
+    fn degenerate_polygons_contain_nothing() {
+        assert!(!RoiShape::Polygon(vec![]).contains(0.0, 0.0));
+        assert!(!RoiShape::Polygon(vec![(0.0, 0.0), (2.0, 2.0)]).contains(1.0, 1.0));
+    }
+
+    #[test]
+    fn parse_invalid_regions() {
+        assert!("spot:rect:1,2,3".parse::<Roi>().unwrap_err().contains("4 values"));
+        assert!("spot:rect:1,2,0,3"
+            .parse::<Roi>()
+            .unwrap_err()
+            .contains("positive size"));
+        assert!("spot:ellipse:1,2,3,-1"
+            .parse::<Roi>()
+            .unwrap_err()
+            .contains("positive size"));
+        assert!("spot:polygon:0,0,1,1"
+            .parse::<Roi>()
+            .unwrap_err()
+            .contains("at least 3 points"));
+        assert!("spot:polygon:0,0,1,1,2"
+            .parse::<Roi>()
+            .unwrap_err()
+            .contains("at least 3 points"));
+        assert!("spot:rect:1,x,3,4"
+            .parse::<Roi>()
+            .unwrap_err()
+            .contains("invalid coordinate 'x'"));
+        assert!("spot:circle:1,2,3"
+            .parse::<Roi>()
+            .unwrap_err()
+            .contains("unknown region type 'circle'"));
+        assert!("spot:rect".parse::<Roi>().unwrap_err().contains("expected <name>"));
+        assert!("a:b:rect:1,2,3,4"
+            .parse::<Roi>()
+            .unwrap_err()
+            .contains("expected <name>"));
+    }
+
+    #[test]
+    fn regions_round_trip() {
+        for s in [
+            "window:rect:1.5,2,10,4.25",
+            "pipe:ellipse:16,12,3,1.5",
+            "roof:polygon:0,0,8,0,4,6.5",
+        ] {
+            let roi: Roi = s.parse().unwrap();
+            assert_eq!(roi.to_string(), s);
+            assert_eq!(roi.to_string().parse::<Roi>().unwrap(), roi);
+        }
+    }
+
+    #[test]
+    fn stats_of_a_region() {
+        let frame = gradient_frame(8, 6);
+        let roi: Roi = "window:rect:1,1,3,2".parse().unwrap();
+        let roi_stats = roi.stats(&frame).unwrap();
+        assert_eq!(roi_stats.name, "window");
+        assert_eq!(roi_stats.stats.count(), 6);
+        assert_eq!((roi_stats.stats.max.x, roi_stats.stats.max.y), (3, 2));
+        assert_eq!(roi_stats.stats.max.value, 5.0);
+        assert_eq!((roi_stats.stats.min.x, roi_stats.stats.min.y), (1, 1));
+        assert_eq!(roi_stats.stats.mean, 3.5);
+    }
+
+    #[test]
+    fn no_stats_outside_of_the_frame() {
+        let frame = gradient_frame(8, 6);
+        let roi: Roi = "outside:rect:8,0,4,4".parse().unwrap();
+        assert!(roi.stats(&frame).is_none());
+        let roi: Roi = "between:ellipse:2,2,0.2,0.2".parse().unwrap();
+        assert!(roi.stats(&frame).is_none());
+    }
+
+    #[test]
+    fn stats_with_the_emissivity_of_the_region() {
+        let frame = ThermalFrame::new(4, 4, vec![30.0; 16]);
+        let global = EmissivityCompensation::black_body();
+        let roi = "metal:rect:0,0,2,2"
+            .parse::<Roi>()
+            .unwrap()
+            .with_emissivity(Some(EmissivityCompensation::new(0.5, 20.0)));
+        let other: Roi = "wall:rect:2,2,2,2".parse().unwrap();
+        let compensated = compensate_frame(&frame, &global, &[roi.clone(), other.clone()]);
+
+        let metal = roi.stats(&compensated).unwrap();
+        let expected = EmissivityCompensation::new(0.5, 20.0).object_temperature(30.0);
+        assert!(expected > 35.0);
+        assert!((metal.stats.max.value - expected).abs() < 1e-4);
+        assert!((metal.stats.min.value - expected).abs() < 1e-4);
+        let wall = other.stats(&compensated).unwrap();
+        assert_eq!(wall.stats.max.value, 30.0);
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+use std::str::FromStr;
+
+use image;
+
+use crate::contour::{draw_line_into_image, draw_thermal_line_into_image};
+use crate::coordinate_mapper::{CoordinateMapper, CoordinateSpace};
+use crate::rgb_color::RgbColor;
+use crate::roi::{RoiShape, RoiStats};
+use crate::thermal_frame::ThermalFrame;
+
+/// Half length (displayed pixels) of the arms of the marker cross.
+const MARKER_SIZE: f32 = 4.0;
+
+/// Named spot meter at a position in thermal-sensor coordinates (pixel (x, y) covers x..x+1 and y..y+1).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpotMeter {
+    pub name: String,
+    pub x: f32,
+    pub y: f32,
+    /// Pixels whose centers are within this distance (sensor pixels) are averaged, 0.0 for the interpolated
+    /// temperature at the position
+    pub radius: f32,
+}
+
+impl SpotMeter {
+    pub fn new(name: &str, x: f32, y: f32) -> Self {
+        SpotMeter {
+            name: name.to_string(),
+            x,
+            y,
+            radius: 0.0,
+        }
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius.max(0.0);
+        self
+    }
+
+    /// Temperature at the spot, `None` if it is outside of the frame.
+    pub fn temperature(&self, frame: &ThermalFrame) -> Option<f32> {
+        if !(0.0..=frame.width as f32).contains(&self.x) || !(0.0..=frame.height as f32).contains(&self.y) {
+            return None;
+        }
+        if self.radius > 0.0 {
+            let area = RoiShape::Ellipse {
+                center_x: self.x,
+                center_y: self.y,
+                radius_x: self.radius,
+                radius_y: self.radius,
+            };
+            let (mut sum, mut count) = (0.0, 0);
+            for y in 0..frame.height {
+                for x in 0..frame.width {
+                    if area.contains(x as f32 + 0.5, y as f32 + 0.5) {
+                        sum += frame.data[(y * frame.width + x) as usize];
+                        count += 1;
+                    }
+                }
+            }
+            if count > 0 {
+                return Some(sum / count as f32);
+            }
+        }
+        Some(frame.sample(self.x - 0.5, self.y - 0.5))
+    }
+
+    pub fn reading(&self, frame: &ThermalFrame) -> Option<SpotReading> {
+        Some(SpotReading {
+            name: self.name.clone(),
+            temperature: self.temperature(frame)?,
+        })
+    }
+}
+
+/// `<name>:<x>,<y>` or `<name>:<x>,<y>:<radius>` in sensor coordinates.
+impl FromStr for SpotMeter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(':').collect();
+        let (name, position, radius) = match fields[..] {
+            [name, position] => (name, position, None),
+            [name, position, radius] => (name, position, Some(radius)),
+            _ => return Err(format!("invalid spot '{s}' (expected <name>:<x>,<y>[:<radius>])")),
+        };
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<f32>()
+                .map_err(|_| format!("invalid value '{value}' in spot '{name}'"))
+        };
+        let (x, y) = position
+            .split_once(',')
+            .ok_or(format!("spot '{name}' needs a position <x>,<y>"))?;
+        let spot = SpotMeter::new(name, parse(x)?, parse(y)?);
+        match radius.map(parse).transpose()? {
+            Some(radius) if radius < 0.0 => Err(format!("spot '{name}' needs a non-negative radius")),
+            Some(radius) => Ok(spot.with_radius(radius)),
+            None => Ok(spot),
+        }
+    }
+}
+
+impl fmt::Display for SpotMeter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{},{}", self.name, self.x, self.y)?;
+        if self.radius > 0.0 {
+            write!(f, ":{}", self.radius)?;
+        }
+        Ok(())
+    }
+}
+
+/// Temperature of a spot meter in a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpotReading {
+    pub name: String,
+    pub temperature: f32,
+}
+
+/// Temperature difference between two spots or regions of interest (referenced by name), regions contribute
+/// their hotspot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemperatureDelta {
+    pub first: String,
+    pub second: String,
+}
+
+impl TemperatureDelta {
+    /// `first - second` (°C), `None` if one of them has no reading in the frame.
+    pub fn difference(&self, spot_readings: &[SpotReading], roi_stats: &[RoiStats]) -> Option<f32> {
+        let temperature_of = |name: &str| {
+            spot_readings
+                .iter()
+                .find(|reading| reading.name == name)
+                .map(|reading| reading.temperature)
+                .or_else(|| {
+                    roi_stats
+                        .iter()
+                        .find(|roi_stats| roi_stats.name == name)
+                        .map(|roi_stats| roi_stats.stats.max.value)
+                })
+        };
+        Some(temperature_of(&self.first)? - temperature_of(&self.second)?)
+    }
+}
+
+/// `<first>:<second>`, e.g. `A:B` for the temperature of A minus the temperature of B. Names can't contain `:`
+/// (it separates the fields of spots and regions), `<first>-<second>` is accepted as long as it contains a
+/// single `-`.
+impl FromStr for TemperatureDelta {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let names = if s.contains(':') {
+            s.split_once(':').filter(|(_, second)| !second.contains(':'))
+        } else if s.matches('-').count() == 1 {
+            s.split_once('-')
+        } else {
+            None
+        };
+        match names {
+            Some((first, second)) if !first.is_empty() && !second.is_empty() => Ok(TemperatureDelta {
+                first: first.to_string(),
+                second: second.to_string(),
+            }),
+            _ => Err(format!(
+                "invalid delta '{s}' (expected <name>:<name>, or <name>-<name> for names without '-')"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TemperatureDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.first, self.second)
+    }
+}
+
+/// Difference of a temperature delta in a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeltaReading {
+    pub delta: TemperatureDelta,
+    pub difference: f32,
+}
+
+/// Draws a cross at every spot (and the circle it averages) into the displayed image of the mapper.
+pub fn draw_spot_markers_into_image(
+    spots: &[SpotMeter],
+    mapper: &CoordinateMapper,
+    color: RgbColor,
+    image: &mut image::RgbImage,
+) {
+    for spot in spots {
+        let (x, y) = match mapper.map_point((spot.x, spot.y), CoordinateSpace::Thermal, CoordinateSpace::Display) {
+            Some((x, y)) => (x - 0.5, y - 0.5),
+            None => continue,
+        };
+        draw_line_into_image((x - MARKER_SIZE, y), (x + MARKER_SIZE, y), color, image);
+        draw_line_into_image((x, y - MARKER_SIZE), (x, y + MARKER_SIZE), color, image);
+        if spot.radius > 0.0 {
+            let circle = RoiShape::Ellipse {
+                center_x: spot.x,
+                center_y: spot.y,
+                radius_x: spot.radius,
+                radius_y: spot.radius,
+            }
+            .outline();
+            for (i, &start) in circle.iter().enumerate() {
+                let end = circle[(i + 1) % circle.len()];
+                draw_thermal_line_into_image(start, end, mapper, color, image);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(first: &str, second: &str) -> TemperatureDelta {
+        TemperatureDelta {
+            first: first.to_string(),
+            second: second.to_string(),
+        }
+    }
+
+    #[test]
+    fn delta_of_names_with_hyphens() {
+        assert_eq!("left-plug:right-plug".parse(), Ok(delta("left-plug", "right-plug")));
+        assert_eq!("S1:S2".parse(), Ok(delta("S1", "S2")));
+        assert_eq!("S1-S2".parse(), Ok(delta("S1", "S2")));
+        assert!("left-plug-S2".parse::<TemperatureDelta>().is_err());
+        assert!("S1:S2:S3".parse::<TemperatureDelta>().is_err());
+        assert!(":S2".parse::<TemperatureDelta>().is_err());
+        assert_eq!(
+            delta("left-plug", "S2").to_string().parse(),
+            Ok(delta("left-plug", "S2"))
+        );
+    }
+
+    #[test]
+    fn spot_temperature_is_interpolated_from_the_frame() {
+        let frame = ThermalFrame::new(3, 2, vec![10.0, 20.0, 30.0, 40.0, 50.0, 60.0]);
+        // center of pixel (1, 0)
+        assert_eq!(SpotMeter::new("A", 1.5, 0.5).temperature(&frame), Some(20.0));
+        // between the centers of all four pixels on the left
+        assert_eq!(SpotMeter::new("B", 1.0, 1.0).temperature(&frame), Some(30.0));
+        assert_eq!(SpotMeter::new("C", 3.5, 1.0).temperature(&frame), None);
+        assert_eq!(
+            SpotMeter::new("D", 1.0, 1.0).with_radius(1.0).temperature(&frame),
+            Some(30.0)
+        );
+        assert_eq!(
+            SpotMeter::new("E", 1.5, 1.0).temperature(&frame),
+            Some(frame.to_temperature_field().sample(1.0, 0.5))
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Resampling filter used to upscale the temperature grid of the sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpscaleFilter {
+    Bilinear,
+    /// Catmull-Rom spline
+    Bicubic,
+    Lanczos3,
+}
+
+impl UpscaleFilter {
+    /// Radius of the filter kernel in source pixels.
+    fn support(&self) -> f32 {
+        match self {
+            UpscaleFilter::Bilinear => 1.0,
+            UpscaleFilter::Bicubic => 2.0,
+            UpscaleFilter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn kernel(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            UpscaleFilter::Bilinear => (1.0 - x).max(0.0),
+            UpscaleFilter::Bicubic => {
+                // Catmull-Rom (a = -0.5)
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            UpscaleFilter::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for UpscaleFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bilinear" => Ok(UpscaleFilter::Bilinear),
+            "bicubic" => Ok(UpscaleFilter::Bicubic),
+            "lanczos" | "lanczos3" => Ok(UpscaleFilter::Lanczos3),
+            _ => Err(format!(
+                "unknown upscale filter '{s}' (choose bilinear, bicubic or lanczos)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for UpscaleFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpscaleFilter::Bilinear => write!(f, "bilinear"),
+            UpscaleFilter::Bicubic => write!(f, "bicubic"),
+            UpscaleFilter::Lanczos3 => write!(f, "lanczos"),
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let pi_x = std::f32::consts::PI * x;
+        pi_x.sin() / pi_x
+    }
+}
+
+/// Temperatures (°C) on a regular grid, row-major.
+#[derive(Debug, Clone)]
+pub struct TemperatureField {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<f32>,
+}
+
+impl TemperatureField {
+    pub fn new(width: u32, height: u32, data: Vec<f32>) -> Self {
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize,
+            "temperature field data does not match its shape"
+        );
+        TemperatureField { width, height, data }
+    }
+
+    /// Temperature of the grid point (x, y), `None` outside of the field.
+    pub fn temperature_at(&self, x: u32, y: u32) -> Option<f32> {
+        if x < self.width && y < self.height {
+            Some(self.data[(y * self.width + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Bilinearly interpolated temperature at the continuous position (x, y) in grid coordinates, clamped to
+    /// the field. NaN for an empty field.
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        sample_grid(&self.data, self.width, self.height, x, y)
+    }
+
+    /// Upscales the field by an integer `factor` with the given filter (separable, edges are clamped).
+    pub fn upscale(&self, factor: u32, filter: UpscaleFilter) -> TemperatureField {
+        let new_width = self.width * factor;
+        let new_height = self.height * factor;
+
+        // horizontal pass
+        let horizontal_weights = resample_weights(self.width, new_width, filter);
+        let mut horizontal = vec![0f32; new_width as usize * self.height as usize];
+        for y in 0..self.height as usize {
+            let src_row = &self.data[y * self.width as usize..(y + 1) * self.width as usize];
+            let dst_row = &mut horizontal[y * new_width as usize..(y + 1) * new_width as usize];
+            for (dst, weights) in dst_row.iter_mut().zip(horizontal_weights.iter()) {
+                *dst = weights.iter().map(|&(idx, weight)| src_row[idx] * weight).sum();
+            }
+        }
+
+        // vertical pass
+        let vertical_weights = resample_weights(self.height, new_height, filter);
+        let mut data = vec![0f32; new_width as usize * new_height as usize];
+        for (y, weights) in vertical_weights.iter().enumerate() {
+            for x in 0..new_width as usize {
+                data[y * new_width as usize + x] = weights
+                    .iter()
+                    .map(|&(idx, weight)| horizontal[idx * new_width as usize + x] * weight)
+                    .sum();
+            }
+        }
+
+        TemperatureField::new(new_width, new_height, data)
+    }
+}
+
+/// Bilinear interpolation in a row-major grid of the given size, see [`TemperatureField::sample`].
+pub(crate) fn sample_grid(data: &[f32], width: u32, height: u32, x: f32, y: f32) -> f32 {
+    if width == 0 || height == 0 {
+        return f32::NAN;
+    }
+    let x = x.clamp(0.0, (width - 1) as f32);
+    let y = y.clamp(0.0, (height - 1) as f32);
+    let x0 = x.floor() as u32;
+    let y0 = y.floor() as u32;
+    let x1 = (x0 + 1).min(width - 1);
+    let y1 = (y0 + 1).min(height - 1);
+    let fx = x - x0 as f32;
+    let fy = y - y0 as f32;
+
+    let at = |x: u32, y: u32| data[(y * width + x) as usize];
+    let top = at(x0, y0) + (at(x1, y0) - at(x0, y0)) * fx;
+    let bottom = at(x0, y1) + (at(x1, y1) - at(x0, y1)) * fx;
+    top + (bottom - top) * fy
+}
+
+/// Normalized (source index, weight) pairs for every destination index of one dimension.
+fn resample_weights(src_size: u32, dst_size: u32, filter: UpscaleFilter) -> Vec<Vec<(usize, f32)>> {
+    let scale = src_size as f32 / dst_size as f32;
+    let support = filter.support();
+    (0..dst_size)
+        .map(|dst_idx| {
+            // pixel centers are at +0.5
+            let center = (dst_idx as f32 + 0.5) * scale - 0.5;
+            let start = (center - support).floor() as i64;
+            let end = (center + support).ceil() as i64;
+            let mut weights: Vec<(usize, f32)> = (start..=end)
+                .map(|src_idx| {
+                    let weight = filter.kernel(src_idx as f32 - center);
+                    let clamped_idx = src_idx.clamp(0, src_size as i64 - 1) as usize;
+                    (clamped_idx, weight)
+                })
+                .filter(|&(_, weight)| weight != 0.0)
+                .collect();
+            let weight_sum: f32 = weights.iter().map(|&(_, weight)| weight).sum();
+            for (_, weight) in weights.iter_mut() {
+                *weight /= weight_sum;
+            }
+            weights
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [UpscaleFilter; 3] = [UpscaleFilter::Bilinear, UpscaleFilter::Bicubic, UpscaleFilter::Lanczos3];
+
+    fn gradient_field() -> TemperatureField {
+        TemperatureField::new(
+            4,
+            3,
+            (0..12)
+                .map(|i| 20.0 + (i % 4) as f32 * 2.0 + (i / 4) as f32 * 0.5)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn constant_field_stays_constant() {
+        let field = TemperatureField::new(5, 4, vec![23.5; 20]);
+        for filter in FILTERS {
+            let upscaled = field.upscale(3, filter);
+            assert_eq!((upscaled.width, upscaled.height), (15, 12));
+            for value in upscaled.data {
+                assert!((value - 23.5).abs() < 1e-4, "{filter}: {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn factor_one_is_the_identity() {
+        let field = gradient_field();
+        for filter in FILTERS {
+            let upscaled = field.upscale(1, filter);
+            assert_eq!((upscaled.width, upscaled.height), (field.width, field.height));
+            for (value, expected) in upscaled.data.iter().zip(field.data.iter()) {
+                assert!((value - expected).abs() < 1e-4, "{filter}: {value} != {expected}");
+            }
+        }
+    }
+
+    #[test]
+    fn bilinear_upscale_interpolates_between_pixel_centers() {
+        let field = TemperatureField::new(2, 1, vec![0.0, 10.0]);
+        let upscaled = field.upscale(2, UpscaleFilter::Bilinear);
+        // the new pixel centers lie a quarter of a source pixel from the old ones, the outer ones are clamped
+        assert_eq!(upscaled.data, [0.0, 2.5, 7.5, 10.0].repeat(2));
+    }
+
+    #[test]
+    fn samples_midpoints_between_grid_points() {
+        let field = TemperatureField::new(2, 2, vec![10.0, 20.0, 30.0, 40.0]);
+        assert_eq!(field.sample(0.0, 0.0), 10.0);
+        assert_eq!(field.sample(0.5, 0.0), 15.0);
+        assert_eq!(field.sample(0.0, 0.5), 20.0);
+        assert_eq!(field.sample(0.5, 0.5), 25.0);
+        assert_eq!(field.sample(1.0, 1.0), 40.0);
+        // clamped to the field
+        assert_eq!(field.sample(-3.0, 7.0), 30.0);
+    }
+
+    #[test]
+    fn sampling_an_empty_field_is_nan() {
+        assert!(TemperatureField::new(0, 0, Vec::new()).sample(0.0, 0.0).is_nan());
+        assert!(TemperatureField::new(3, 0, Vec::new()).sample(1.0, 0.0).is_nan());
+    }
+
+    #[test]
+    fn parses_filter_names() {
+        for filter in FILTERS {
+            assert_eq!(filter.to_string().parse(), Ok(filter));
+        }
+        assert_eq!("Lanczos3".parse(), Ok(UpscaleFilter::Lanczos3));
+        assert!("nearest".parse::<UpscaleFilter>().is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use core::fmt;
 
+#[derive(Clone, Copy)]
 pub struct TemperaturPixel {
     pub x: u32,
     pub y: u32,
@@ -0,0 +1,423 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::thermal_frame::ThermalFrame;
+
+const DEFAULT_AVERAGE_FRAMES: usize = 4;
+const DEFAULT_EMA_ALPHA: f32 = 0.3;
+const DEFAULT_KALMAN_PROCESS_NOISE: f32 = 0.01;
+const DEFAULT_KALMAN_MEASUREMENT_NOISE: f32 = 0.25;
+
+/// Filter over consecutive thermal frames against sensor noise and the flicker between the subpages of the
+/// chess pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TemporalFilter {
+    #[default]
+    None,
+    /// Mean of the last `frames` frames
+    Average { frames: usize },
+    /// Exponential moving average, `alpha` (0.0-1.0) is the weight of a new frame
+    ExponentialMovingAverage { alpha: f32 },
+    /// Per-pixel Kalman filter of a constant temperature, noises are variances (°C²)
+    Kalman { process_noise: f32, measurement_noise: f32 },
+}
+
+impl TemporalFilter {
+    /// The following kind of filter with default parameters (wraps around).
+    pub fn next(&self) -> TemporalFilter {
+        match self {
+            TemporalFilter::None => TemporalFilter::Average {
+                frames: DEFAULT_AVERAGE_FRAMES,
+            },
+            TemporalFilter::Average { .. } => TemporalFilter::ExponentialMovingAverage {
+                alpha: DEFAULT_EMA_ALPHA,
+            },
+            TemporalFilter::ExponentialMovingAverage { .. } => TemporalFilter::Kalman {
+                process_noise: DEFAULT_KALMAN_PROCESS_NOISE,
+                measurement_noise: DEFAULT_KALMAN_MEASUREMENT_NOISE,
+            },
+            TemporalFilter::Kalman { .. } => TemporalFilter::None,
+        }
+    }
+
+    /// Name of the kind of filter.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TemporalFilter::None => "none",
+            TemporalFilter::Average { .. } => "average",
+            TemporalFilter::ExponentialMovingAverage { .. } => "ema",
+            TemporalFilter::Kalman { .. } => "kalman",
+        }
+    }
+}
+
+/// `none`, `average[:<frames>]`, `ema[:<alpha>]` or `kalman[:<process noise>,<measurement noise>]`.
+impl FromStr for TemporalFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, values) = match s.split_once(':') {
+            Some((kind, values)) => (kind, Some(values)),
+            None => (s, None),
+        };
+        let parse = |value: &str| {
+            value
+                .trim()
+                .parse::<f32>()
+                .map_err(|_| format!("invalid value '{value}' in temporal filter '{s}'"))
+        };
+        match (kind.to_lowercase().as_str(), values) {
+            ("none", None) => Ok(TemporalFilter::None),
+            ("average", values) => {
+                let frames = match values {
+                    Some(frames) => frames
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid number of frames '{frames}' in temporal filter '{s}'"))?,
+                    None => DEFAULT_AVERAGE_FRAMES,
+                };
+                if frames == 0 {
+                    return Err("the average needs at least 1 frame".to_string());
+                }
+                Ok(TemporalFilter::Average { frames })
+            }
+            ("ema", values) => {
+                let alpha = values.map(parse).transpose()?.unwrap_or(DEFAULT_EMA_ALPHA);
+                if !(alpha > 0.0 && alpha <= 1.0) {
+                    return Err("the alpha of the ema needs to be within 0.0-1.0 (without 0.0)".to_string());
+                }
+                Ok(TemporalFilter::ExponentialMovingAverage { alpha })
+            }
+            ("kalman", None) => Ok(TemporalFilter::Kalman {
+                process_noise: DEFAULT_KALMAN_PROCESS_NOISE,
+                measurement_noise: DEFAULT_KALMAN_MEASUREMENT_NOISE,
+            }),
+            ("kalman", Some(values)) => {
+                let (process_noise, measurement_noise) = values
+                    .split_once(',')
+                    .ok_or(format!("kalman filter '{s}' needs <process noise>,<measurement noise>"))?;
+                let (process_noise, measurement_noise) = (parse(process_noise)?, parse(measurement_noise)?);
+                if process_noise < 0.0 || measurement_noise <= 0.0 {
+                    return Err("the noises of the kalman filter need to be positive".to_string());
+                }
+                Ok(TemporalFilter::Kalman {
+                    process_noise,
+                    measurement_noise,
+                })
+            }
+            _ => Err(format!(
+                "unknown temporal filter '{s}' (choose none, average[:<frames>], ema[:<alpha>] or kalman[:<process noise>,<measurement noise>])"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TemporalFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemporalFilter::None => write!(f, "none"),
+            TemporalFilter::Average { frames } => write!(f, "average:{frames}"),
+            TemporalFilter::ExponentialMovingAverage { alpha } => write!(f, "ema:{alpha}"),
+            TemporalFilter::Kalman {
+                process_noise,
+                measurement_noise,
+            } => write!(f, "kalman:{process_noise},{measurement_noise}"),
+        }
+    }
+}
+
+/// Positive motion threshold (°C), e.g. as value parser of a command line argument.
+pub fn parse_motion_threshold(value: &str) -> Result<f32, String> {
+    let threshold = value
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| format!("invalid motion threshold '{value}'"))?;
+    if threshold > 0.0 {
+        Ok(threshold)
+    } else {
+        Err(format!("motion threshold {threshold} needs to be above 0"))
+    }
+}
+
+/// State of a temporal filter between the thermal source and the processing of the frames. It starts over when
+/// the filter or the size of the frames changes.
+///
+/// With a motion threshold, pixels that differ from their filtered value by more than the threshold (°C) restart
+/// their filter with the new value, so moving objects don't smear.
+#[derive(Debug, Clone, Default)]
+pub struct TemporalFilterStage {
+    filter: TemporalFilter,
+    shape: (u32, u32),
+    /// Average: last frames, the newest one at the back
+    history: VecDeque<Vec<f32>>,
+    /// Average: number of frames of the history every pixel is averaged over
+    history_lengths: Vec<usize>,
+    /// Exponential moving average and Kalman filter: filtered temperatures
+    estimate: Vec<f32>,
+    /// Kalman filter: variances of the estimate
+    variance: Vec<f32>,
+}
+
+impl TemporalFilterStage {
+    pub fn new() -> Self {
+        TemporalFilterStage::default()
+    }
+
+    /// Filters the frame and updates the state, the frame keeps its timestamp and index.
+    pub fn apply(
+        &mut self,
+        frame: &ThermalFrame,
+        filter: TemporalFilter,
+        motion_threshold: Option<f32>,
+    ) -> ThermalFrame {
+        let shape = (frame.width, frame.height);
+        if filter != self.filter || shape != self.shape {
+            *self = TemporalFilterStage {
+                filter,
+                shape,
+                ..TemporalFilterStage::default()
+            };
+        }
+        let is_motion = |filtered: f32, current: f32| {
+            motion_threshold.is_some_and(|threshold| (current - filtered).abs() > threshold)
+        };
+
+        let mut filtered = frame.clone();
+        match filter {
+            TemporalFilter::None => {}
+            TemporalFilter::Average { frames } => {
+                self.history.push_back(frame.data.clone());
+                while self.history.len() > frames {
+                    self.history.pop_front();
+                }
+                self.history_lengths.resize(frame.data.len(), 0);
+                let newest = self.history.len() - 1;
+                for (i, value) in filtered.data.iter_mut().enumerate() {
+                    let current = frame.data[i];
+                    let mut count = self.history_lengths[i].min(newest);
+                    if count > 0 {
+                        let previous_mean =
+                            (newest - count..newest).map(|k| self.history[k][i]).sum::<f32>() / count as f32;
+                        if is_motion(previous_mean, current) {
+                            count = 0;
+                        }
+                    }
+                    let count = (count + 1).min(frames);
+                    self.history_lengths[i] = count;
+                    *value = (self.history.len() - count..self.history.len())
+                        .map(|k| self.history[k][i])
+                        .sum::<f32>()
+                        / count as f32;
+                }
+            }
+            TemporalFilter::ExponentialMovingAverage { alpha } => {
+                if self.estimate.is_empty() {
+                    self.estimate = frame.data.clone();
+                }
+                for (estimate, &current) in self.estimate.iter_mut().zip(frame.data.iter()) {
+                    if is_motion(*estimate, current) {
+                        *estimate = current;
+                    } else {
+                        *estimate += alpha * (current - *estimate);
+                    }
+                }
+                filtered.data.copy_from_slice(&self.estimate);
+            }
+            TemporalFilter::Kalman {
+                process_noise,
+                measurement_noise,
+            } => {
+                if self.estimate.is_empty() {
+                    // the first frame is the estimate, with the uncertainty of a single measurement
+                    self.estimate = frame.data.clone();
+                    self.variance = vec![measurement_noise; frame.data.len()];
+                } else {
+                    for ((estimate, variance), &current) in self
+                        .estimate
+                        .iter_mut()
+                        .zip(self.variance.iter_mut())
+                        .zip(frame.data.iter())
+                    {
+                        if is_motion(*estimate, current) {
+                            *estimate = current;
+                            *variance = measurement_noise;
+                            continue;
+                        }
+                        let predicted_variance = *variance + process_noise;
+                        let gain = predicted_variance / (predicted_variance + measurement_noise);
+                        *estimate += gain * (current - *estimate);
+                        *variance = (1.0 - gain) * predicted_variance;
+                    }
+                }
+                filtered.data.copy_from_slice(&self.estimate);
+            }
+        }
+        filtered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uniform_frame(width: u32, height: u32, value: f32) -> ThermalFrame {
+        ThermalFrame::new(width, height, vec![value; (width * height) as usize])
+    }
+
+    fn assert_uniform(frame: &ThermalFrame, expected: f32, tolerance: f32) {
+        for value in frame.data.iter() {
+            assert!((value - expected).abs() <= tolerance, "{value} != {expected}");
+        }
+    }
+
+    fn all_filters() -> [TemporalFilter; 4] {
+        [
+            TemporalFilter::None,
+            TemporalFilter::Average { frames: 4 },
+            TemporalFilter::ExponentialMovingAverage { alpha: 0.3 },
+            TemporalFilter::Kalman {
+                process_noise: 0.01,
+                measurement_noise: 0.25,
+            },
+        ]
+    }
+
+    #[test]
+    fn constant_frames_pass_unchanged() {
+        for filter in all_filters() {
+            let mut stage = TemporalFilterStage::new();
+            for _ in 0..10 {
+                let filtered = stage.apply(&uniform_frame(4, 3, 25.5), filter, None);
+                assert_uniform(&filtered, 25.5, 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn converges_to_a_new_constant_frame() {
+        for filter in all_filters() {
+            let mut stage = TemporalFilterStage::new();
+            stage.apply(&uniform_frame(4, 3, 20.0), filter, None);
+            let mut filtered = uniform_frame(4, 3, 20.0);
+            for _ in 0..100 {
+                filtered = stage.apply(&uniform_frame(4, 3, 30.0), filter, None);
+            }
+            assert_uniform(&filtered, 30.0, 0.01);
+        }
+    }
+
+    #[test]
+    fn average_of_the_last_frames() {
+        let filter = TemporalFilter::Average { frames: 3 };
+        let mut stage = TemporalFilterStage::new();
+        let expected = [10.0, 15.0, 20.0, 30.0, 40.0];
+        for (value, expected) in [10.0, 20.0, 30.0, 40.0, 50.0].into_iter().zip(expected) {
+            assert_uniform(&stage.apply(&uniform_frame(2, 2, value), filter, None), expected, 1e-5);
+        }
+    }
+
+    #[test]
+    fn ema_step_response() {
+        let alpha = 0.25;
+        let filter = TemporalFilter::ExponentialMovingAverage { alpha };
+        let mut stage = TemporalFilterStage::new();
+        assert_uniform(&stage.apply(&uniform_frame(2, 2, 20.0), filter, None), 20.0, 0.0);
+        for step in 1..=10 {
+            let filtered = stage.apply(&uniform_frame(2, 2, 30.0), filter, None);
+            // 1 - (1 - alpha)^n of the step after n frames
+            let expected = 20.0 + 10.0 * (1.0 - (1.0 - alpha).powi(step));
+            assert_uniform(&filtered, expected, 1e-4);
+        }
+    }
+
+    #[test]
+    fn kalman_without_process_noise_is_the_running_mean() {
+        let filter = TemporalFilter::Kalman {
+            process_noise: 0.0,
+            measurement_noise: 0.5,
+        };
+        let mut stage = TemporalFilterStage::new();
+        let measurements = [20.0, 22.0, 18.0, 21.0, 19.0, 24.0];
+        for (i, &measurement) in measurements.iter().enumerate() {
+            let filtered = stage.apply(&uniform_frame(3, 1, measurement), filter, None);
+            // the gain drops to 1/n
+            let mean = measurements[..=i].iter().sum::<f32>() / (i + 1) as f32;
+            assert_uniform(&filtered, mean, 1e-4);
+        }
+    }
+
+    #[test]
+    fn kalman_gain_settles_with_process_noise() {
+        let (process_noise, measurement_noise) = (0.01f32, 0.25f32);
+        let filter = TemporalFilter::Kalman {
+            process_noise,
+            measurement_noise,
+        };
+        let mut stage = TemporalFilterStage::new();
+        for _ in 0..500 {
+            stage.apply(&uniform_frame(3, 1, 20.0), filter, None);
+        }
+        // steady state of the predicted variance: P² - qP - qr = 0
+        let predicted_variance =
+            (process_noise + (process_noise * process_noise + 4.0 * process_noise * measurement_noise).sqrt()) / 2.0;
+        let gain = predicted_variance / (predicted_variance + measurement_noise);
+        assert!(gain > 0.1 && gain < 0.5);
+        let filtered = stage.apply(&uniform_frame(3, 1, 30.0), filter, None);
+        assert_uniform(&filtered, 20.0 + 10.0 * gain, 1e-3);
+    }
+
+    #[test]
+    fn resets_when_the_frame_size_changes() {
+        for filter in all_filters() {
+            let mut stage = TemporalFilterStage::new();
+            for _ in 0..5 {
+                stage.apply(&uniform_frame(4, 3, 20.0), filter, None);
+            }
+            let filtered = stage.apply(&uniform_frame(3, 4, 30.0), filter, None);
+            assert_eq!((filtered.width, filtered.height), (3, 4));
+            assert_uniform(&filtered, 30.0, 0.0);
+            let filtered = stage.apply(&uniform_frame(4, 3, 40.0), filter, None);
+            assert_uniform(&filtered, 40.0, 0.0);
+        }
+    }
+
+    #[test]
+    fn motion_restarts_single_pixels() {
+        for filter in all_filters() {
+            let mut stage = TemporalFilterStage::new();
+            for _ in 0..5 {
+                stage.apply(&uniform_frame(2, 1, 20.0), filter, Some(2.0));
+            }
+            // the first pixel jumps beyond the threshold, the second one stays within it
+            let frame = ThermalFrame::new(2, 1, vec![35.0, 21.0]);
+            let filtered = stage.apply(&frame, filter, Some(2.0));
+            assert_eq!(filtered.data[0], 35.0, "{filter}");
+            if filter != TemporalFilter::None {
+                assert!(filtered.data[1] < 21.0, "{filter}");
+            }
+        }
+    }
+
+    #[test]
+    fn parse_and_display() {
+        for filter in all_filters() {
+            assert_eq!(filter.to_string().parse(), Ok(filter));
+        }
+        assert_eq!("average".parse(), Ok(TemporalFilter::Average { frames: 4 }));
+        assert!("average:0".parse::<TemporalFilter>().is_err());
+        assert!("ema:1.5".parse::<TemporalFilter>().is_err());
+        assert!("kalman:0.1".parse::<TemporalFilter>().is_err());
+        assert!("median".parse::<TemporalFilter>().is_err());
+    }
+
+    #[test]
+    fn parse_positive_motion_thresholds_only() {
+        assert_eq!(parse_motion_threshold("1.5"), Ok(1.5));
+        assert_eq!(parse_motion_threshold(" 3"), Ok(3.0));
+        assert!(parse_motion_threshold("0").is_err());
+        assert!(parse_motion_threshold("-1").is_err());
+        assert!(parse_motion_threshold("NaN").is_err());
+        assert!(parse_motion_threshold("fast").is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+use image;
+
+use crate::contour::ContourSegment;
+use crate::isotherm::IsothermArea;
+use crate::line_profile::ProfileSample;
+use crate::roi::RoiStats;
+use crate::spot_meter::{DeltaReading, SpotReading};
+use crate::temperature_field::{self, TemperatureField};
+use crate::temperature_pixel::TemperaturPixel;
+
+/// A frame of the thermal sensor: temperatures in °C, row-major (`data[y * width + x]`).
+#[derive(Debug, Clone)]
+pub struct ThermalFrame {
+    pub data: Vec<f32>,
+    pub width: u32,
+    pub height: u32,
+    /// Time of capture, relative to an arbitrary but fixed point in time of the source
+    pub timestamp: Duration,
+    /// Ambient temperature of the sensor itself (°C), if reported by the source
+    pub ambient_temperature: Option<f32>,
+    /// Running number of the frame, starting at 0 for every source
+    pub frame_index: u64,
+}
+
+impl ThermalFrame {
+    pub fn new(width: u32, height: u32, data: Vec<f32>) -> Self {
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize,
+            "thermal frame data does not match its shape"
+        );
+        ThermalFrame {
+            data,
+            width,
+            height,
+            timestamp: Duration::ZERO,
+            ambient_temperature: None,
+            frame_index: 0,
+        }
+    }
+
+    pub fn with_timestamp(mut self, timestamp: Duration) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn with_ambient_temperature(mut self, ambient_temperature: Option<f32>) -> Self {
+        self.ambient_temperature = ambient_temperature;
+        self
+    }
+
+    pub fn with_frame_index(mut self, frame_index: u64) -> Self {
+        self.frame_index = frame_index;
+        self
+    }
+
+    /// Temperature at column `x` and row `y`, `None` outside of the frame.
+    pub fn temperature_at(&self, x: u32, y: u32) -> Option<f32> {
+        if x < self.width && y < self.height {
+            Some(self.data[(y * self.width + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Bilinearly interpolated temperature at the continuous position (x, y) in grid coordinates (pixel centers),
+    /// clamped to the frame. Same as sampling [`ThermalFrame::to_temperature_field`], without copying the data.
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        temperature_field::sample_grid(&self.data, self.width, self.height, x, y)
+    }
+
+    /// The frame as a temperature field (e.g. for upscaling).
+    pub fn to_temperature_field(&self) -> TemperatureField {
+        TemperatureField::new(self.width, self.height, self.data.clone())
+    }
+}
+
+/// Statistics of the temperatures of a frame.
+#[derive(Debug, Clone)]
+pub struct FrameStats {
+    pub min: TemperaturPixel,
+    pub max: TemperaturPixel,
+    pub mean: f32,
+    pub median: f32,
+    pub std_dev: f32,
+    sorted: Vec<f32>,
+}
+
+impl FrameStats {
+    pub fn from_frame(frame: &ThermalFrame) -> Self {
+        Self::from_values(&frame.data, frame.width)
+    }
+
+    /// Statistics of row-major `values` of a grid with `width` columns.
+    pub fn from_values(values: &[f32], width: u32) -> Self {
+        Self::from_pixels(values.iter().enumerate().map(|(i, &value)| TemperaturPixel {
+            x: i as u32 % width,
+            y: i as u32 / width,
+            value,
+        }))
+    }
+
+    /// Statistics of arbitrary pixels, e.g. of a region of interest.
+    pub fn from_pixels<I: IntoIterator<Item = TemperaturPixel>>(pixels: I) -> Self {
+        let mut min = TemperaturPixel {
+            x: 0,
+            y: 0,
+            value: f32::INFINITY,
+        };
+        let mut max = TemperaturPixel {
+            x: 0,
+            y: 0,
+            value: f32::NEG_INFINITY,
+        };
+        let mut sum = 0.0;
+        let mut values = Vec::new();
+        for pixel in pixels {
+            if pixel.value < min.value {
+                min = pixel;
+            }
+            if pixel.value > max.value {
+                max = pixel;
+            }
+            sum += pixel.value;
+            values.push(pixel.value);
+        }
+        assert!(!values.is_empty(), "statistics need at least one value");
+        let mean = sum / values.len() as f32;
+        let variance = values.iter().map(|&t| (t - mean) * (t - mean)).sum::<f32>() / values.len() as f32;
+
+        let mut sorted = values;
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let mut stats = FrameStats {
+            min,
+            max,
+            mean,
+            median: 0.0,
+            std_dev: variance.sqrt(),
+            sorted,
+        };
+        stats.median = stats.percentile(50.0);
+        stats
+    }
+
+    /// Linearly interpolated percentile, `p` in 0-100.
+    pub fn percentile(&self, p: f32) -> f32 {
+        let rank = p.clamp(0.0, 100.0) / 100.0 * (self.sorted.len() - 1) as f32;
+        let lower = rank.floor() as usize;
+        let upper = rank.ceil() as usize;
+        self.sorted[lower] + (self.sorted[upper] - self.sorted[lower]) * (rank - lower as f32)
+    }
+
+    /// Number of values the statistics are based on.
+    pub fn count(&self) -> usize {
+        self.sorted.len()
+    }
+}
+
+/// Result of [`crate::process_raw_thermo_image_data`].
+pub struct ProcessedThermalFrame {
+    pub stats: FrameStats,
+    /// Colorized upscaled temperatures including min/max markers, in the thermal orientation of the settings
+    pub image: image::RgbImage,
+    /// Upscaled temperatures, one per pixel of `image` (also oriented)
+    pub temperature_field: TemperatureField,
+    /// Area of every isotherm of the settings, in the same order
+    pub isotherm_areas: Vec<IsothermArea>,
+    /// Isolines in thermal-sensor coordinates
+    pub contours: Vec<ContourSegment>,
+    /// Statistics of every region of interest of the settings that overlaps the frame
+    pub roi_stats: Vec<RoiStats>,
+    /// Temperatures of the spot meters of the settings that are inside the frame
+    pub spot_readings: Vec<SpotReading>,
+    /// Temperature differences of the settings whose spots or regions have a reading
+    pub delta_readings: Vec<DeltaReading>,
+    /// Temperatures along the line profile of the settings, empty without one
+    pub profile_samples: Vec<ProfileSample>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_position(pixel: TemperaturPixel, x: u32, y: u32, value: f32) {
+        assert_eq!((pixel.x, pixel.y, pixel.value), (x, y, value));
+    }
+
+    #[test]
+    fn stats_of_an_odd_number_of_values() {
+        let frame = ThermalFrame::new(3, 3, vec![4.0, 8.0, 1.0, 7.0, 2.0, 9.0, 3.0, 6.0, 5.0]);
+        let stats = FrameStats::from_frame(&frame);
+        assert_eq!(stats.count(), 9);
+        assert_position(stats.min, 2, 0, 1.0);
+        assert_position(stats.max, 2, 1, 9.0);
+        assert_eq!(stats.mean, 5.0);
+        assert_eq!(stats.median, 5.0);
+        assert!((stats.std_dev - (60.0f32 / 9.0).sqrt()).abs() < 1e-5);
+        assert_eq!(stats.percentile(0.0), 1.0);
+        assert_eq!(stats.percentile(25.0), 3.0);
+        assert_eq!(stats.percentile(100.0), 9.0);
+    }
+
+    #[test]
+    fn stats_of_an_even_number_of_values() {
+        let frame = ThermalFrame::new(2, 2, vec![10.0, 20.0, 40.0, 30.0]);
+        let stats = FrameStats::from_frame(&frame);
+        assert_eq!(stats.count(), 4);
+        assert_position(stats.min, 0, 0, 10.0);
+        assert_position(stats.max, 0, 1, 40.0);
+        assert_eq!(stats.mean, 25.0);
+        // interpolated between the two middle values
+        assert_eq!(stats.median, 25.0);
+        assert!((stats.std_dev - 125.0f32.sqrt()).abs() < 1e-5);
+        assert_eq!(stats.percentile(0.0), 10.0);
+        assert_eq!(stats.percentile(100.0), 40.0);
+        // clamped to 0-100
+        assert_eq!(stats.percentile(-5.0), 10.0);
+        assert_eq!(stats.percentile(150.0), 40.0);
+    }
+
+    #[test]
+    fn stats_of_a_single_value() {
+        let stats = FrameStats::from_values(&[21.5], 1);
+        assert_position(stats.min, 0, 0, 21.5);
+        assert_position(stats.max, 0, 0, 21.5);
+        assert_eq!((stats.mean, stats.median, stats.std_dev), (21.5, 21.5, 0.0));
+        assert_eq!(stats.percentile(90.0), 21.5);
+    }
+}
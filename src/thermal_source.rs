@@ -0,0 +1,463 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use linux_embedded_hal::I2cdev;
+use mlx9064x;
+use mlx9064x::Mlx90640Driver;
+
+#[cfg(not(target_arch = "arm"))]
+use npyz;
+
+use crate::atmosphere::Atmosphere;
+use crate::emissivity::{EmissivityCompensation, RoiEmissivity};
+use crate::roi::Roi;
+use crate::thermal_frame::ThermalFrame;
+
+/// A source of thermal frames.
+///
+/// Implement this trait to plug a new sensor or a deterministic frame generator into the processing pipeline.
+pub trait ThermalSource {
+    /// Number of columns of the delivered frames.
+    fn width(&self) -> u32;
+
+    /// Number of rows of the delivered frames.
+    fn height(&self) -> u32;
+
+    /// Blocks until the next frame is available.
+    fn next_frame(&mut self) -> io::Result<ThermalFrame>;
+}
+
+/// MLX90640 connected via I2C.
+pub struct Mlx90640Source {
+    sensor: Mlx90640Driver<I2cdev>,
+    period: u64,
+    buffer: Vec<f32>,
+    frame_index: u64,
+    start: Instant,
+}
+
+impl Mlx90640Source {
+    /// Opens the sensor at `i2c_path`/`address`, configures the frame rate and the chess access pattern and
+    /// synchronizes with the sensor's measurement timing.
+    pub fn new(i2c_path: &str, address: u8, frame_rate: mlx9064x::FrameRate) -> io::Result<Self> {
+        let i2c_bus = I2cdev::new(i2c_path).map_err(to_io_error)?;
+        let mut sensor = Mlx90640Driver::new(i2c_bus, address).map_err(to_io_error)?;
+
+        sensor.set_frame_rate(frame_rate).map_err(to_io_error)?;
+        sensor
+            .set_access_pattern(mlx9064x::AccessPattern::Chess)
+            .map_err(to_io_error)?;
+        sensor.synchronize().map_err(to_io_error)?;
+
+        let frame_rate: f32 = frame_rate.into();
+        let period = ((1.0 / frame_rate) * 1000.0) as u64;
+        let buffer = vec![0f32; sensor.height() * sensor.width()];
+
+        Ok(Mlx90640Source {
+            sensor,
+            period,
+            buffer,
+            frame_index: 0,
+            start: Instant::now(),
+        })
+    }
+
+    /// Frame period in milliseconds derived from the configured frame rate.
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+}
+
+impl ThermalSource for Mlx90640Source {
+    fn width(&self) -> u32 {
+        self.sensor.width() as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.sensor.height() as u32
+    }
+
+    fn next_frame(&mut self) -> io::Result<ThermalFrame> {
+        // chess pattern: both subpages are needed for a complete frame, the buffer keeps the previous values
+        self.sensor
+            .generate_image_if_ready(&mut self.buffer)
+            .map_err(to_io_error)?;
+        sleep(Duration::from_millis(self.period));
+        self.sensor
+            .generate_image_if_ready(&mut self.buffer)
+            .map_err(to_io_error)?;
+
+        let frame = ThermalFrame::new(self.width(), self.height(), self.buffer.clone())
+            .with_timestamp(self.start.elapsed())
+            .with_ambient_temperature(self.sensor.ambient_temperature())
+            .with_frame_index(self.frame_index);
+        self.frame_index += 1;
+        Ok(frame)
+    }
+}
+
+fn to_io_error<E: std::fmt::Display>(err: E) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+/// Replays a single 2D `.npy` array (f32, rows x columns) as a static scene, e.g. `data/flir_f32.npy`.
+#[cfg(not(target_arch = "arm"))]
+pub struct NpySimulationSource {
+    width: u32,
+    height: u32,
+    data: Vec<f32>,
+    period: u64,
+    frame_index: u64,
+    start: Instant,
+}
+
+#[cfg(not(target_arch = "arm"))]
+impl NpySimulationSource {
+    pub fn open(path: &str, period: u64) -> io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let reader = npyz::NpyFile::new(&bytes[..])?;
+        let shape_vec = reader.shape().to_vec();
+        if shape_vec.len() != 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("expected a 2D array in {path}, got shape {shape_vec:?}"),
+            ));
+        }
+        let data = reader.into_vec::<f32>()?;
+        Ok(NpySimulationSource {
+            width: shape_vec[1] as u32,
+            height: shape_vec[0] as u32,
+            data,
+            period,
+            frame_index: 0,
+            start: Instant::now(),
+        })
+    }
+}
+
+#[cfg(not(target_arch = "arm"))]
+impl ThermalSource for NpySimulationSource {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn next_frame(&mut self) -> io::Result<ThermalFrame> {
+        sleep(Duration::from_millis(self.period));
+        let frame = ThermalFrame::new(self.width, self.height, self.data.clone())
+            .with_timestamp(self.start.elapsed())
+            .with_frame_index(self.frame_index);
+        self.frame_index += 1;
+        Ok(frame)
+    }
+}
+
+/// Replays a recording of raw frames: little-endian f32 values, row-major, width * height per frame, frames
+/// stored back to back. Playback starts over at the end of the file.
+///
+/// The size of the frames is loaded from `<recording>.shape`. The emissivity compensation (incl. the ones of
+/// regions of interest) and the atmosphere of the scene are loaded from `<recording>.emissivity` and
+/// `<recording>.atmosphere` if they exist (see [`ThermalRecorder`]).
+pub struct RecordedSource {
+    width: u32,
+    height: u32,
+    frames: Vec<f32>,
+    period: u64,
+    frame_index: u64,
+    start: Instant,
+    emissivity: Option<EmissivityCompensation>,
+    roi_emissivities: Vec<RoiEmissivity>,
+    atmosphere: Option<Atmosphere>,
+}
+
+impl RecordedSource {
+    pub fn open(path: &str, period: u64) -> io::Result<Self> {
+        let (width, height) = load_shape(&shape_sidecar_path(path))?;
+        let bytes = std::fs::read(path)?;
+        let frame_byte_size = width as usize * height as usize * std::mem::size_of::<f32>();
+        if frame_byte_size == 0 || bytes.is_empty() || bytes.len() % frame_byte_size != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "size of {path} ({} bytes) is not a multiple of the frame size ({frame_byte_size} bytes)",
+                    bytes.len()
+                ),
+            ));
+        }
+        let frames = bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        let emissivity_path = emissivity_sidecar_path(path);
+        let (emissivity, roi_emissivities) = if Path::new(&emissivity_path).exists() {
+            let (emissivity, roi_emissivities) = load_emissivity(&emissivity_path)?;
+            (Some(emissivity), roi_emissivities)
+        } else {
+            (None, Vec::new())
+        };
+        let atmosphere_path = atmosphere_sidecar_path(path);
+        let atmosphere = if Path::new(&atmosphere_path).exists() {
+            Some(Atmosphere::load(&atmosphere_path)?)
+        } else {
+            None
+        };
+        Ok(RecordedSource {
+            width,
+            height,
+            frames,
+            period,
+            frame_index: 0,
+            start: Instant::now(),
+            emissivity,
+            roi_emissivities,
+            atmosphere,
+        })
+    }
+
+    /// Number of frames contained in the recording.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len() / (self.width as usize * self.height as usize)
+    }
+
+    /// Emissivity compensation of the scene saved with the recording, `None` if there is no sidecar file.
+    pub fn emissivity(&self) -> Option<EmissivityCompensation> {
+        self.emissivity
+    }
+
+    /// Emissivities of regions of interest saved with the recording, matched to the regions by name.
+    pub fn roi_emissivities(&self) -> &[RoiEmissivity] {
+        &self.roi_emissivities
+    }
+
+    /// Atmosphere between camera and scene saved with the recording, `None` if there is no sidecar file.
+    pub fn atmosphere(&self) -> Option<Atmosphere> {
+        self.atmosphere
+    }
+}
+
+impl ThermalSource for RecordedSource {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn next_frame(&mut self) -> io::Result<ThermalFrame> {
+        let frame_size = self.width as usize * self.height as usize;
+        let start = (self.frame_index as usize % self.frame_count()) * frame_size;
+        sleep(Duration::from_millis(self.period));
+        let frame = ThermalFrame::new(self.width, self.height, self.frames[start..start + frame_size].to_vec())
+            .with_timestamp(self.start.elapsed())
+            .with_frame_index(self.frame_index);
+        self.frame_index += 1;
+        Ok(frame)
+    }
+}
+
+/// Records raw frames in the format of [`RecordedSource`]. The settings the frames are compensated with are not
+/// applied to the recording but saved next to it, so a replay can be processed like the original scene.
+pub struct ThermalRecorder {
+    path: String,
+    writer: BufWriter<File>,
+    width: u32,
+    height: u32,
+}
+
+impl ThermalRecorder {
+    /// Creates (or truncates) the recording of frames of the given size and saves the size to
+    /// `<recording>.shape`.
+    pub fn create(path: &str, width: u32, height: u32) -> io::Result<Self> {
+        save_shape(&shape_sidecar_path(path), (width, height))?;
+        Ok(ThermalRecorder {
+            path: path.to_string(),
+            writer: BufWriter::new(File::create(path)?),
+            width,
+            height,
+        })
+    }
+
+    pub fn write_frame(&mut self, frame: &ThermalFrame) -> io::Result<()> {
+        if (frame.width, frame.height) != (self.width, self.height) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame of {}x{} pixels in a recording of {}x{} pixels",
+                    frame.width, frame.height, self.width, self.height
+                ),
+            ));
+        }
+        for value in frame.data.iter() {
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+        self.writer.flush()
+    }
+
+    /// Saves the emissivity compensation (incl. the material) and the ones of the regions of interest with an
+    /// own emissivity to `<recording>.emissivity`, as `roi = <region emissivity>` lines (see [`RoiEmissivity`]).
+    pub fn save_emissivity(&self, emissivity: &EmissivityCompensation, rois: &[Roi]) -> io::Result<()> {
+        let mut content = String::from("# emissivity compensation of the scene\n");
+        for setting in emissivity.settings() {
+            content.push_str(&setting);
+            content.push('\n');
+        }
+        for roi_emissivity in rois.iter().filter_map(RoiEmissivity::from_roi) {
+            content.push_str(&format!("roi = {roi_emissivity}\n"));
+        }
+        std::fs::write(emissivity_sidecar_path(&self.path), content)
+    }
+
+    /// Saves the atmosphere to `<recording>.atmosphere`.
+    pub fn save_atmosphere(&self, atmosphere: &Atmosphere) -> io::Result<()> {
+        atmosphere.save(atmosphere_sidecar_path(&self.path))
+    }
+}
+
+/// Lines `width = <columns>` and `height = <rows>`.
+fn save_shape(path: &str, (width, height): (u32, u32)) -> io::Result<()> {
+    std::fs::write(
+        path,
+        format!("# size of the recorded frames\nwidth = {width}\nheight = {height}\n"),
+    )
+}
+
+fn load_shape(path: &str) -> io::Result<(u32, u32)> {
+    let content = std::fs::read_to_string(path)?;
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {msg}"));
+    let mut width = None;
+    let mut height = None;
+    for (line_idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line_err = |msg: &str| invalid(format!("line {}: {}", line_idx + 1, msg));
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| line_err("expected <setting> = <value>"))?;
+        let value: u32 = value
+            .trim()
+            .parse()
+            .map_err(|_| line_err(&format!("invalid size '{}'", value.trim())))?;
+        match key.trim() {
+            "width" => width = Some(value),
+            "height" => height = Some(value),
+            key => return Err(line_err(&format!("unknown setting '{key}'"))),
+        }
+    }
+    match (width, height) {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err(invalid("expected a width and a height".to_string())),
+    }
+}
+
+/// Counterpart of [`ThermalRecorder::save_emissivity`].
+fn load_emissivity(path: &str) -> io::Result<(EmissivityCompensation, Vec<RoiEmissivity>)> {
+    let content = std::fs::read_to_string(path)?;
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {msg}"));
+    let mut roi_emissivities = Vec::new();
+    // the region lines are blanked, so the line numbers of errors in the other settings stay the same
+    let mut settings = String::new();
+    for (line_idx, line) in content.lines().enumerate() {
+        match line.split_once('=') {
+            Some((key, value)) if key.trim() == "roi" => {
+                let roi_emissivity = value
+                    .trim()
+                    .parse()
+                    .map_err(|err| invalid(format!("line {}: {}", line_idx + 1, err)))?;
+                roi_emissivities.push(roi_emissivity);
+            }
+            _ => settings.push_str(line),
+        }
+        settings.push('\n');
+    }
+    let emissivity = EmissivityCompensation::parse_settings(&settings).map_err(invalid)?;
+    Ok((emissivity, roi_emissivities))
+}
+
+fn shape_sidecar_path(recording: &str) -> String {
+    format!("{recording}.shape")
+}
+
+fn emissivity_sidecar_path(recording: &str) -> String {
+    format!("{recording}.emissivity")
+}
+
+fn atmosphere_sidecar_path(recording: &str) -> String {
+    format!("{recording}.atmosphere")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+    use crate::roi::RoiShape;
+
+    #[test]
+    fn replays_recorded_frames_with_their_settings() {
+        let path = std::env::temp_dir().join(format!("thermocam_recording_{}.raw", std::process::id()));
+        let path = path.to_str().unwrap();
+        let frames = [
+            ThermalFrame::new(3, 2, vec![20.0, 21.0, 22.0, 23.0, 24.0, 25.0]),
+            ThermalFrame::new(3, 2, vec![30.5, -1.0, 0.0, 99.9, 18.0, 17.0]),
+        ];
+        let emissivity = EmissivityCompensation::default().with_material(Material::builtin("brick").unwrap());
+        let mut recorder = ThermalRecorder::create(path, 3, 2).unwrap();
+        for frame in frames.iter() {
+            recorder.write_frame(frame).unwrap();
+        }
+        let rois = [
+            Roi::new("A", RoiShape::Polygon(vec![(0.0, 0.0), (2.0, 0.0), (1.0, 2.0)]))
+                .with_emissivity(Some(EmissivityCompensation::new(0.8, 25.0))),
+            Roi::new("B", RoiShape::Polygon(vec![(1.0, 0.0), (3.0, 0.0), (2.0, 2.0)])),
+        ];
+        recorder.save_emissivity(&emissivity, &rois).unwrap();
+        let atmosphere = Atmosphere::new(12.5, 8.0, 70.0);
+        recorder.save_atmosphere(&atmosphere).unwrap();
+
+        let recording = RecordedSource::open(path, 0);
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(shape_sidecar_path(path)).unwrap();
+        std::fs::remove_file(emissivity_sidecar_path(path)).unwrap();
+        std::fs::remove_file(atmosphere_sidecar_path(path)).unwrap();
+        let mut recording = recording.unwrap();
+        assert_eq!((recording.width(), recording.height()), (3, 2));
+        assert_eq!(recording.frame_count(), 2);
+        assert_eq!(recording.emissivity(), Some(emissivity));
+        assert_eq!(
+            recording.roi_emissivities(),
+            [RoiEmissivity::from_roi(&rois[0]).unwrap()]
+        );
+        assert_eq!(recording.atmosphere(), Some(atmosphere));
+        for expected in frames.iter().chain(frames.iter()) {
+            assert_eq!(recording.next_frame().unwrap().data, expected.data);
+        }
+    }
+
+    #[test]
+    fn recordings_without_sidecars() {
+        let path = std::env::temp_dir().join(format!("thermocam_plain_recording_{}.raw", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut recorder = ThermalRecorder::create(path, 2, 1).unwrap();
+        recorder.write_frame(&ThermalFrame::new(2, 1, vec![1.0, 2.0])).unwrap();
+        // the size of the frames can't change within a recording
+        assert!(recorder.write_frame(&ThermalFrame::new(1, 2, vec![1.0, 2.0])).is_err());
+        drop(recorder);
+
+        let recording = RecordedSource::open(path, 0);
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(shape_sidecar_path(path)).unwrap();
+        let recording = recording.unwrap();
+        assert_eq!(recording.frame_count(), 1);
+        assert_eq!(recording.emissivity(), None);
+        assert!(recording.roi_emissivities().is_empty());
+        assert_eq!(recording.atmosphere(), None);
+    }
+}
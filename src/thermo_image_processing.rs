@@ -1,29 +1,105 @@
-use crate::rgb_color::RgbColor;
+use crate::atmosphere::Atmosphere;
+use crate::display_mode::DisplayMode;
+use crate::emissivity::EmissivityCompensation;
+use crate::isotherm::Isotherm;
+use crate::line_profile::LineProfile;
+use crate::orientation::Orientation;
+use crate::palette::Palette;
+use crate::registration::Registration;
+use crate::roi::Roi;
+use crate::spot_meter::{SpotMeter, TemperatureDelta};
+use crate::temperature_field::UpscaleFilter;
+use crate::temporal_filter::TemporalFilter;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct ThermoImageProcessor {
+    /// Filter over consecutive raw frames, applied before the processing
+    pub temporal_filter: TemporalFilter,
+    /// Pixels that change by more than this (°C) restart their temporal filter, `None` to filter all pixels
+    pub motion_threshold: Option<f32>,
     pub interpolation_factor: u32,
+    pub upscale_filter: UpscaleFilter,
     pub autoscale_enabled: bool,
     pub manual_scale_min_temp: f32,
     pub manual_scale_max_temp: f32,
-    pub min_temp_color: RgbColor,
-    pub max_temp_color: RgbColor,
-    pub mode: u32,
+    pub palette: Palette,
+    /// Temperature bands drawn in solid colors instead of the palette
+    pub isotherms: Vec<Isotherm>,
+    /// Interval (°C) of the isolines drawn on the thermal image, `None` to disable them
+    pub contour_interval: Option<f32>,
+    /// Emissivity and reflected temperature of the scene, regions of interest may override it
+    pub emissivity: EmissivityCompensation,
+    /// Air between camera and scene
+    pub atmosphere: Atmosphere,
+    /// Regions of interest with own statistics
+    pub rois: Vec<Roi>,
+    pub spot_meters: Vec<SpotMeter>,
+    /// Temperature differences between spots or regions of interest
+    pub temperature_deltas: Vec<TemperatureDelta>,
+    pub line_profile: Option<LineProfile>,
+    pub mode: DisplayMode,
+    /// Position of the divider in split-screen mode, 0.0 (left) - 1.0 (right)
+    pub split_position: f32,
+    /// Amount of visible detail added in edge-enhanced mode
+    pub edge_strength: f32,
+    /// Temperature band (°C) that is colorized in temperature-gated mode
+    pub gate_min_temp: f32,
+    pub gate_max_temp: f32,
+    /// Flips/rotation of the raw frames, measurements stay in sensor coordinates
+    pub thermal_orientation: Orientation,
+    pub visible_orientation: Orientation,
+    /// Placement of the thermal image in the visible image (both oriented)
+    pub registration: Registration,
+    /// Refine the registration by correlating the edges of both images
+    pub auto_alignment_enabled: bool,
 }
 
 impl ThermoImageProcessor {
     pub fn new(interpolation_factor: u32) -> Self {
         ThermoImageProcessor {
+            temporal_filter: TemporalFilter::None,
+            motion_threshold: None,
             interpolation_factor,
+            upscale_filter: UpscaleFilter::Lanczos3,
             autoscale_enabled: true,
             manual_scale_min_temp: -5.0,
             manual_scale_max_temp: 35.0,
-            min_temp_color: RgbColor { r: 0, g: 0, b: 255 },
-            max_temp_color: RgbColor { r: 255, g: 0, b: 0 },
-            mode: 0,
+            palette: Palette::default(),
+            isotherms: Vec::new(),
+            contour_interval: None,
+            emissivity: EmissivityCompensation::black_body(),
+            atmosphere: Atmosphere::none(),
+            rois: Vec::new(),
+            spot_meters: Vec::new(),
+            temperature_deltas: Vec::new(),
+            line_profile: None,
+            mode: DisplayMode::Blend,
+            split_position: 0.5,
+            edge_strength: 1.0,
+            gate_min_temp: 30.0,
+            gate_max_temp: 100.0,
+            thermal_orientation: Orientation::identity(),
+            visible_orientation: Orientation::identity().with_flip_horizontal(true),
+            registration: Registration::identity(),
+            auto_alignment_enabled: false,
         }
     }
 
+    pub fn with_temporal_filter(mut self, temporal_filter: TemporalFilter) -> Self {
+        self.temporal_filter = temporal_filter;
+        self
+    }
+
+    pub fn with_motion_threshold(mut self, motion_threshold: Option<f32>) -> Self {
+        self.motion_threshold = motion_threshold;
+        self
+    }
+
+    pub fn with_upscale_filter(mut self, upscale_filter: UpscaleFilter) -> Self {
+        self.upscale_filter = upscale_filter;
+        self
+    }
+
     pub fn with_autoscale_enabled(mut self, autoscale_enabled: bool) -> Self {
         self.autoscale_enabled = autoscale_enabled;
         self
@@ -39,18 +115,93 @@ impl ThermoImageProcessor {
         self
     }
 
-    pub fn with_min_temp_color(mut self, min_temp_color: RgbColor) -> Self {
-        self.min_temp_color = min_temp_color;
+    pub fn with_palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    pub fn with_isotherms(mut self, isotherms: Vec<Isotherm>) -> Self {
+        self.isotherms = isotherms;
+        self
+    }
+
+    pub fn with_contour_interval(mut self, contour_interval: Option<f32>) -> Self {
+        self.contour_interval = contour_interval;
+        self
+    }
+
+    pub fn with_emissivity(mut self, emissivity: EmissivityCompensation) -> Self {
+        self.emissivity = emissivity;
+        self
+    }
+
+    pub fn with_atmosphere(mut self, atmosphere: Atmosphere) -> Self {
+        self.atmosphere = atmosphere;
+        self
+    }
+
+    pub fn with_rois(mut self, rois: Vec<Roi>) -> Self {
+        self.rois = rois;
+        self
+    }
+
+    pub fn with_spot_meters(mut self, spot_meters: Vec<SpotMeter>) -> Self {
+        self.spot_meters = spot_meters;
         self
     }
 
-    pub fn with_max_temp_color(mut self, max_temp_color: RgbColor) -> Self {
-        self.max_temp_color = max_temp_color;
+    pub fn with_temperature_deltas(mut self, temperature_deltas: Vec<TemperatureDelta>) -> Self {
+        self.temperature_deltas = temperature_deltas;
         self
     }
 
-    pub fn with_mode(mut self, mode: u32) -> Self {
+    pub fn with_line_profile(mut self, line_profile: Option<LineProfile>) -> Self {
+        self.line_profile = line_profile;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: DisplayMode) -> Self {
         self.mode = mode;
         self
     }
+
+    pub fn with_split_position(mut self, split_position: f32) -> Self {
+        self.split_position = split_position.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_edge_strength(mut self, edge_strength: f32) -> Self {
+        self.edge_strength = edge_strength;
+        self
+    }
+
+    pub fn with_gate_min_temp(mut self, gate_min_temp: f32) -> Self {
+        self.gate_min_temp = gate_min_temp;
+        self
+    }
+
+    pub fn with_gate_max_temp(mut self, gate_max_temp: f32) -> Self {
+        self.gate_max_temp = gate_max_temp;
+        self
+    }
+
+    pub fn with_thermal_orientation(mut self, thermal_orientation: Orientation) -> Self {
+        self.thermal_orientation = thermal_orientation;
+        self
+    }
+
+    pub fn with_visible_orientation(mut self, visible_orientation: Orientation) -> Self {
+        self.visible_orientation = visible_orientation;
+        self
+    }
+
+    pub fn with_registration(mut self, registration: Registration) -> Self {
+        self.registration = registration;
+        self
+    }
+
+    pub fn with_auto_alignment_enabled(mut self, auto_alignment_enabled: bool) -> Self {
+        self.auto_alignment_enabled = auto_alignment_enabled;
+        self
+    }
 }
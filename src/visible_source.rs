@@ -0,0 +1,252 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use v4l::buffer::Type;
+use v4l::io::mmap::Stream;
+use v4l::io::traits::CaptureStream;
+use v4l::video::Capture;
+use v4l::Device;
+use v4l::FourCC;
+
+/// A single frame of the visible camera as delivered by the source (not yet decoded).
+pub struct VisibleFrame<'a> {
+    pub data: &'a [u8],
+    pub fourcc: FourCC,
+    /// (width, height) in pixels
+    pub shape: (u32, u32),
+    pub sequence: u32,
+    /// Time of capture, relative to an arbitrary but fixed point in time of the source
+    pub timestamp: Duration,
+}
+
+/// A source of visible camera frames.
+///
+/// Implement this trait to run the fusion pipeline against other cameras or generated frames.
+pub trait VisibleSource {
+    /// Blocks until the next frame is available. The frame borrows the source's internal buffer.
+    fn next_frame(&mut self) -> io::Result<VisibleFrame<'_>>;
+
+    /// Pixel format of the delivered frames, e.g. the one negotiated with the driver.
+    fn fourcc(&self) -> FourCC;
+}
+
+/// Video4Linux capture device (e.g. the RPi camera via libcamera or a USB webcam).
+pub struct V4lSource {
+    stream: Stream<'static>,
+    fourcc: FourCC,
+    shape: (u32, u32),
+}
+
+impl V4lSource {
+    /// Opens `/dev/video<index>` and requests the given format. The format actually negotiated by the driver
+    /// may differ and is reported by the delivered frames.
+    pub fn new(index: usize, width: u32, height: u32, fourcc: FourCC) -> io::Result<Self> {
+        let dev = Device::new(index)?;
+        let mut fmt = dev.format()?;
+        fmt.width = width;
+        fmt.height = height;
+        fmt.fourcc = fourcc;
+        let fmt = dev.set_format(&fmt)?;
+
+        let stream = Stream::with_buffers(&dev, Type::VideoCapture, 4)?;
+        Ok(V4lSource {
+            stream,
+            fourcc: fmt.fourcc,
+            shape: (fmt.width, fmt.height),
+        })
+    }
+}
+
+impl VisibleSource for V4lSource {
+    fn next_frame(&mut self) -> io::Result<VisibleFrame<'_>> {
+        let (data, meta) = self.stream.next()?;
+        Ok(VisibleFrame {
+            data: &data[..meta.bytesused as usize],
+            fourcc: self.fourcc,
+            shape: self.shape,
+            sequence: meta.sequence,
+            timestamp: meta.timestamp.into(),
+        })
+    }
+
+    fn fourcc(&self) -> FourCC {
+        self.fourcc
+    }
+}
+
+/// Replays raw frames stored back to back in a file, e.g. `data/received_image_data.bin`.
+/// Playback starts over at the end of the file.
+pub struct FileReplaySource {
+    data: Vec<u8>,
+    frame_size: usize,
+    fourcc: FourCC,
+    shape: (u32, u32),
+    sequence: u32,
+    start: Instant,
+}
+
+impl FileReplaySource {
+    /// `frame_size` is the number of bytes of a single frame of the given format and shape.
+    pub fn open(path: &str, fourcc: FourCC, shape: (u32, u32), frame_size: usize) -> io::Result<Self> {
+        let data = std::fs::read(path)?;
+        if frame_size == 0 || data.len() < frame_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{path} ({} bytes) holds less than one frame ({frame_size} bytes)",
+                    data.len()
+                ),
+            ));
+        }
+        Ok(FileReplaySource {
+            data,
+            frame_size,
+            fourcc,
+            shape,
+            sequence: 0,
+            start: Instant::now(),
+        })
+    }
+
+    /// Number of complete frames contained in the file.
+    pub fn frame_count(&self) -> usize {
+        self.data.len() / self.frame_size
+    }
+}
+
+impl VisibleSource for FileReplaySource {
+    fn next_frame(&mut self) -> io::Result<VisibleFrame<'_>> {
+        let frame_idx = self.sequence as usize % self.frame_count();
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(VisibleFrame {
+            data: &self.data[frame_idx * self.frame_size..(frame_idx + 1) * self.frame_size],
+            fourcc: self.fourcc,
+            shape: self.shape,
+            sequence,
+            timestamp: self.start.elapsed(),
+        })
+    }
+
+    fn fourcc(&self) -> FourCC {
+        self.fourcc
+    }
+}
+
+/// Generates a deterministic RGB24 (`RGB3`) test pattern: a horizontal red ramp, a vertical green ramp and a
+/// blue channel that advances with every frame.
+pub struct SyntheticSource {
+    buffer: Vec<u8>,
+    shape: (u32, u32),
+    sequence: u32,
+    period: Duration,
+}
+
+impl SyntheticSource {
+    /// `period` is the simulated frame period, used for the timestamps of the frames.
+    pub fn new(shape: (u32, u32), period: Duration) -> Self {
+        SyntheticSource {
+            buffer: vec![0u8; 3 * shape.0 as usize * shape.1 as usize],
+            shape,
+            sequence: 0,
+            period,
+        }
+    }
+}
+
+impl VisibleSource for SyntheticSource {
+    fn next_frame(&mut self) -> io::Result<VisibleFrame<'_>> {
+        let (width, height) = self.shape;
+        let blue = (self.sequence % 256) as u8;
+        for (i, rgb_px) in self.buffer.chunks_exact_mut(3).enumerate() {
+            let x = i as u32 % width;
+            let y = i as u32 / width;
+            rgb_px[0] = (x * 255 / width.max(2).saturating_sub(1)).min(255) as u8;
+            rgb_px[1] = (y * 255 / height.max(2).saturating_sub(1)).min(255) as u8;
+            rgb_px[2] = blue;
+        }
+        let sequence = self.sequence;
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(VisibleFrame {
+            data: &self.buffer,
+            fourcc: FourCC::new(b"RGB3"),
+            shape: self.shape,
+            sequence,
+            timestamp: self.period * sequence,
+        })
+    }
+
+    fn fourcc(&self) -> FourCC {
+        FourCC::new(b"RGB3")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replay source of a file with `frames` frames of `frame_size` bytes, each filled with its index; a trailing
+    /// partial frame is appended.
+    fn replay_source(name: &str, frames: u8, frame_size: usize) -> io::Result<FileReplaySource> {
+        let path = std::env::temp_dir().join(format!("thermocam_{name}_{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut data: Vec<u8> = (0..frames).flat_map(|i| vec![i; frame_size]).collect();
+        data.extend([0xff; 2]);
+        std::fs::write(path, data).unwrap();
+        let source = FileReplaySource::open(path, FourCC::new(b"YUYV"), (2, 2), frame_size);
+        std::fs::remove_file(path).unwrap();
+        source
+    }
+
+    #[test]
+    fn replays_frames_in_order_and_starts_over() {
+        let mut source = replay_source("replay", 3, 8).unwrap();
+        assert_eq!(source.frame_count(), 3);
+        assert_eq!(source.fourcc(), FourCC::new(b"YUYV"));
+        for expected in [0, 1, 2, 0, 1] {
+            let frame = source.next_frame().unwrap();
+            assert_eq!(frame.data, &[expected; 8]);
+            assert_eq!(frame.shape, (2, 2));
+            assert_eq!(frame.fourcc, FourCC::new(b"YUYV"));
+        }
+        assert_eq!(source.next_frame().unwrap().sequence, 5);
+    }
+
+    #[test]
+    fn replay_needs_a_complete_frame() {
+        let error = replay_source("short_replay", 0, 8).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(replay_source("empty_frames", 1, 0).is_err());
+    }
+
+    #[test]
+    fn synthetic_pattern() {
+        let mut source = SyntheticSource::new((5, 3), Duration::from_millis(40));
+        assert_eq!(source.fourcc(), FourCC::new(b"RGB3"));
+        let frame = source.next_frame().unwrap();
+        assert_eq!(frame.shape, (5, 3));
+        assert_eq!(frame.data.len(), 5 * 3 * 3);
+        let pixel = |x: usize, y: usize| &frame.data[3 * (y * 5 + x)..3 * (y * 5 + x) + 3];
+        assert_eq!(pixel(0, 0), &[0, 0, 0]);
+        assert_eq!(pixel(4, 0), &[255, 0, 0]);
+        assert_eq!(pixel(0, 2), &[0, 255, 0]);
+        assert_eq!(pixel(2, 1), &[127, 127, 0]);
+
+        let frame = source.next_frame().unwrap();
+        assert_eq!(frame.data[2], 1);
+    }
+
+    #[test]
+    fn synthetic_timestamps_follow_the_period() {
+        let mut source = SyntheticSource::new((1, 1), Duration::from_millis(40));
+        let mut timestamps = Vec::new();
+        for expected_sequence in 0..4 {
+            let frame = source.next_frame().unwrap();
+            assert_eq!(frame.sequence, expected_sequence);
+            timestamps.push(frame.timestamp);
+        }
+        assert_eq!(timestamps[0], Duration::ZERO);
+        assert_eq!(timestamps[3], Duration::from_millis(120));
+        assert!(timestamps.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}